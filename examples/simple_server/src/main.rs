@@ -1,9 +1,10 @@
-use std::net::{IpAddr, Ipv4Addr};
-use log::error;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use log::{error, info};
 
 use tiny_dns::{
     builder::{ServerBuilder, ServerBuilderImpl},
-    resolver::ResolverType,
+    resolver::{CachingResolver, ForwardResolver, Strategy},
 };
 
 #[tokio::main]
@@ -17,10 +18,16 @@ async fn main() {
     let server = server_builder
         .set_port(5300)
         .set_bind_address(bind_address)
-        .set_resolver(ResolverType::Mirror {
-            mirror_address: IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
-            port: 53,
-        })
+        .set_resolver(Arc::new(CachingResolver::new(
+            Arc::new(ForwardResolver::new(
+                vec![
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 53),
+                ],
+                Strategy::Fastest,
+            )),
+            10_000,
+        )))
         .build()
         .await;
 
@@ -29,8 +36,28 @@ async fn main() {
         return;
     }
 
-    server.unwrap().start().await.unwrap_or_else(|e| {
-        error!("Failed to start server: {}", e);
-    });
+    let handle = server.unwrap().start();
 
+    if let Err(e) = shutdown_signal().await {
+        // Without signals the server can only run until it is killed.
+        error!("Failed to listen for shutdown signals: {}", e);
+        std::future::pending::<()>().await;
+    }
+    let stats = handle.shutdown().await;
+    info!("Answered {} of {} queries", stats.responses, stats.queries);
+}
+
+/// Waits for Ctrl+C, or SIGTERM on Unix.
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+//! Feeds arbitrary bytes to the packet parser, which must return an error
+//! instead of panicking or hanging. Run with `cargo fuzz run parse_packet`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tiny_dns::protocol::DnsPacket;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = DnsPacket::from_bytes(data) {
+        // Whatever parses has to be printable and serializable again.
+        let _ = packet.to_bytes();
+        for record in packet.answers.iter().chain(&packet.authority).chain(&packet.additional) {
+            let _ = record.to_string();
+        }
+    }
+});
@@ -1,9 +1,12 @@
-use std::{future::Future, net::IpAddr};
+use std::{future::Future, net::IpAddr, sync::Arc};
 use std::net::Ipv4Addr;
+use std::time::Duration;
 use log::{error, warn};
 
-use crate::{network::udp_server::Server, resolver::ResolverType};
-use crate::errors::ConfigError;
+use crate::{network::udp_server::Server, resolver::Resolver};
+use crate::network::options::{IpNetwork, MultiQuestionPolicy, ServerIdentity, ServerOptions};
+use crate::errors::{ConfigError, ErrorChain};
+use crate::protocol::dns_packet::TrailingBytesPolicy;
 
 pub trait ServerBuilder {
     fn new() -> Self;
@@ -11,13 +14,20 @@ pub trait ServerBuilder {
 
     fn set_port(&self, port: u16) -> Self;
     fn set_bind_address(&self, bind_address: IpAddr) -> Self;
-    fn set_resolver(&self, resolver: ResolverType) -> Self;
+    fn set_resolver(&self, resolver: Arc<dyn Resolver>) -> Self;
+    fn set_multi_question_policy(&self, policy: MultiQuestionPolicy) -> Self;
+    fn set_server_identity(&self, identity: ServerIdentity) -> Self;
+    fn set_trailing_bytes_policy(&self, policy: TrailingBytesPolicy) -> Self;
+    fn set_allowed_clients(&self, networks: Vec<IpNetwork>) -> Self;
+    fn set_shutdown_timeout(&self, timeout: Duration) -> Self;
 }
 
+#[derive(Clone)]
 pub struct ServerBuilderImpl {
     port: Option<u16>,
     bind_address: Option<IpAddr>,
-    resolver: Option<ResolverType>,
+    resolver: Option<Arc<dyn Resolver>>,
+    options: ServerOptions,
 }
 
 impl ServerBuilder for ServerBuilderImpl {
@@ -26,16 +36,15 @@ impl ServerBuilder for ServerBuilderImpl {
             port: None,
             bind_address: None,
             resolver: None,
+            options: ServerOptions::default(),
         }
     }
 
     async fn build(&self) -> Result<Server, ConfigError> {
-        let port = self.port;
-        if let None = port {
+        let Some(port) = self.port else {
             error!("{}", ConfigError::NoPortSpecified);
             return Err(ConfigError::NoPortSpecified);
-        }
-        let port = port.unwrap();        
+        };
         
         let bind_address = self
             .bind_address
@@ -44,20 +53,16 @@ impl ServerBuilder for ServerBuilderImpl {
                 IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))
             });
 
-        let resolver = self
-            .resolver
-            .as_ref();
-
-        if let None = resolver {
+        let Some(resolver) = self.resolver.clone() else {
             error!("{}", ConfigError::NoResolverEspecified);
             return Err(ConfigError::NoResolverEspecified);
-        }
+        };
 
-        match Server::new(bind_address, port, resolver.unwrap().clone()).await {
+        match Server::with_options(bind_address, port, resolver, self.options.clone()).await {
             Ok(server) => Ok(server),
             Err(e) => {
-                error!("Failed to create server: {}", e);
-                Err(ConfigError::FailedToCreateServer)
+                error!("Failed to create server: {}", ErrorChain(&e));
+                Err(ConfigError::FailedToCreateServer(e))
             }
         }
     }
@@ -65,21 +70,71 @@ impl ServerBuilder for ServerBuilderImpl {
     fn set_port(&self, port: u16) -> Self {
         ServerBuilderImpl {
             port: Some(port),
-            ..*self
+            ..self.clone()
         }
     }
 
     fn set_bind_address(&self, bind_address: IpAddr) -> Self {
         ServerBuilderImpl {
             bind_address: Some(bind_address),
-            ..*self
+            ..self.clone()
         }
     }
 
-    fn set_resolver(&self, resolver: ResolverType) -> Self {
+    fn set_resolver(&self, resolver: Arc<dyn Resolver>) -> Self {
         ServerBuilderImpl {
             resolver: Some(resolver),
-            ..*self
+            ..self.clone()
+        }
+    }
+
+    fn set_multi_question_policy(&self, policy: MultiQuestionPolicy) -> Self {
+        let mut options = self.options.clone();
+        options.multi_question_policy = policy;
+
+        ServerBuilderImpl {
+            options,
+            ..self.clone()
         }
     }
-}
\ No newline at end of file
+
+    fn set_server_identity(&self, identity: ServerIdentity) -> Self {
+        let mut options = self.options.clone();
+        options.identity = identity;
+
+        ServerBuilderImpl {
+            options,
+            ..self.clone()
+        }
+    }
+
+    fn set_trailing_bytes_policy(&self, policy: TrailingBytesPolicy) -> Self {
+        let mut options = self.options.clone();
+        options.trailing_bytes_policy = policy;
+
+        ServerBuilderImpl {
+            options,
+            ..self.clone()
+        }
+    }
+
+    fn set_allowed_clients(&self, networks: Vec<IpNetwork>) -> Self {
+        let mut options = self.options.clone();
+        options.allowed_clients = networks;
+
+        ServerBuilderImpl {
+            options,
+            ..self.clone()
+        }
+    }
+
+    fn set_shutdown_timeout(&self, timeout: Duration) -> Self {
+        let mut options = self.options.clone();
+        options.shutdown_timeout = timeout;
+
+        ServerBuilderImpl {
+            options,
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Authenticated denial of existence with NSEC (RFC 4035 5.4) and NSEC3
+//! (RFC 5155 8).
+//!
+//! Every function here expects NSEC and NSEC3 records whose signatures were
+//! already verified, and only looks at what they say.
+
+use std::cmp::Ordering;
+
+use ring::digest;
+
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{DnsRecordType, RecordType};
+use crate::protocol::domain_name::{canonical_cmp, is_subdomain_of, label_count, normalize, parent};
+use crate::protocol::encoding::from_base32hex;
+
+use super::canonical_name;
+
+/// NSEC3 records with more iterations are ignored (RFC 9276 3.2).
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+const NSEC3_SHA1: u8 = 1;
+const NSEC3_OPT_OUT: u8 = 0x01;
+
+/// Why a name with a proven absent DS RRset doesn't make data below it secure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingDs {
+    /// The name is a delegation to an unsigned zone, or may be one under an
+    /// NSEC3 opt-out span.
+    InsecureDelegation,
+    /// The name isn't a zone cut, so names below it are in the same zone.
+    NotAZoneCut,
+}
+
+struct Nsec<'a> {
+    owner: &'a str,
+    next: &'a str,
+    types: &'a [RecordType],
+}
+
+struct Nsec3<'a> {
+    zone: &'a str,
+    owner_hash: Vec<u8>,
+    next_hash: &'a [u8],
+    flags: u8,
+    iterations: u16,
+    salt: &'a [u8],
+    types: &'a [RecordType],
+}
+
+/// The NSEC3 hash of `name` (RFC 5155 5).
+pub fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> Vec<u8> {
+    let mut hash = canonical_name(name).unwrap_or_default();
+    for _ in 0..=iterations {
+        hash.extend_from_slice(salt);
+        hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &hash).as_ref().to_vec();
+    }
+
+    hash
+}
+
+/// Whether the records prove that `name` doesn't exist.
+pub fn proves_nxdomain(records: &[DnsRecord], name: &str) -> bool {
+    let nsecs = nsecs(records);
+    if !nsecs.is_empty() {
+        return nsec_nxdomain(&nsecs, name);
+    }
+
+    let nsec3s = nsec3s(records);
+    closest_encloser(&nsec3s, name).is_some_and(|(closest, _)| covering(&nsec3s, &wildcard(&closest)).is_some())
+}
+
+/// Whether the records prove that `name` exists without `qtype` records,
+/// either itself or through a wildcard.
+pub fn proves_nodata(records: &[DnsRecord], name: &str, qtype: RecordType) -> bool {
+    let lacks = |types: &[RecordType], at_delegation: bool| {
+        !types.contains(&qtype)
+            && !types.contains(&RecordType::CNAME)
+            // The parent side of a zone cut only speaks for the DS RRset.
+            && (qtype == RecordType::DS || !at_delegation)
+    };
+
+    let nsecs = nsecs(records);
+    if !nsecs.is_empty() {
+        if let Some(nsec) = nsecs.iter().find(|nsec| same_name(nsec.owner, name)) {
+            return lacks(nsec.types, is_delegation(nsec.types));
+        }
+        if nsecs.iter().any(|nsec| covers(nsec, name) && is_empty_non_terminal(nsec, name)) {
+            return true;
+        }
+        // Wildcard NODATA: the name is covered, the wildcard matched.
+        return nsecs.iter().find(|nsec| covers(nsec, name)).is_some_and(|nsec| {
+            let source = wildcard(&nsec_closest_encloser(nsec, name));
+            nsecs
+                .iter()
+                .find(|wildcard| same_name(wildcard.owner, &source))
+                .is_some_and(|wildcard| lacks(wildcard.types, false))
+        });
+    }
+
+    let nsec3s = nsec3s(records);
+    if let Some(nsec3) = matching(&nsec3s, name) {
+        return lacks(nsec3.types, is_delegation(nsec3.types));
+    }
+    closest_encloser(&nsec3s, name).is_some_and(|(closest, _)| {
+        matching(&nsec3s, &wildcard(&closest)).is_some_and(|nsec3| lacks(nsec3.types, false))
+    })
+}
+
+/// What the records say about `name`, whose DS RRset is missing from a
+/// response, or `None` if they don't prove its absence.
+pub fn missing_ds(records: &[DnsRecord], name: &str) -> Option<MissingDs> {
+    let status = |types: &[RecordType]| match types {
+        types if types.contains(&RecordType::DS) => None,
+        types if is_delegation(types) => Some(MissingDs::InsecureDelegation),
+        _ => Some(MissingDs::NotAZoneCut),
+    };
+
+    let nsecs = nsecs(records);
+    if !nsecs.is_empty() {
+        if let Some(nsec) = nsecs.iter().find(|nsec| same_name(nsec.owner, name)) {
+            return status(nsec.types);
+        }
+        let exists_below = nsecs.iter().any(|nsec| covers(nsec, name) && is_empty_non_terminal(nsec, name));
+        return (exists_below || nsec_nxdomain(&nsecs, name)).then_some(MissingDs::NotAZoneCut);
+    }
+
+    let nsec3s = nsec3s(records);
+    if let Some(nsec3) = matching(&nsec3s, name) {
+        return status(nsec3.types);
+    }
+    // RFC 5155 8.6: an opt-out span may hide an unsigned delegation.
+    closest_encloser(&nsec3s, name).map(|(_, next_closer)| match next_closer.flags & NSEC3_OPT_OUT != 0 {
+        true => MissingDs::InsecureDelegation,
+        false => MissingDs::NotAZoneCut,
+    })
+}
+
+/// Whether the records prove that `name`, answered from the wildcard at
+/// `*.closest_encloser`, doesn't exist itself (RFC 4035 5.3.4, RFC 5155 8.8).
+pub fn proves_wildcard_expansion(records: &[DnsRecord], name: &str, closest_encloser: &str) -> bool {
+    if !is_subdomain_of(name, closest_encloser) || label_count(name) <= label_count(closest_encloser) {
+        return false;
+    }
+
+    let nsecs = nsecs(records);
+    if !nsecs.is_empty() {
+        return nsecs.iter().any(|nsec| covers(nsec, name));
+    }
+
+    let labels: Vec<&str> = name.split('.').collect();
+    let next_closer = labels[labels.len() - label_count(closest_encloser) - 1..].join(".");
+    covering(&nsec3s(records), &next_closer).is_some()
+}
+
+fn nsecs(records: &[DnsRecord]) -> Vec<Nsec<'_>> {
+    records
+        .iter()
+        .filter_map(|record| match record.rdata() {
+            DnsRecordType::NSEC { next_domain_name, types } => {
+                Some(Nsec { owner: record.record(), next: next_domain_name, types })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn nsec3s(records: &[DnsRecord]) -> Vec<Nsec3<'_>> {
+    records
+        .iter()
+        .filter_map(|record| match record.rdata() {
+            DnsRecordType::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types }
+                if *hash_algorithm == NSEC3_SHA1 && *iterations <= MAX_NSEC3_ITERATIONS =>
+            {
+                let (label, zone) = record.record().split_once('.')?;
+                Some(Nsec3 {
+                    zone,
+                    owner_hash: from_base32hex(label)?,
+                    next_hash: next_hashed_owner,
+                    flags: *flags,
+                    iterations: *iterations,
+                    salt,
+                    types,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    canonical_cmp(a, b) == Ordering::Equal
+}
+
+/// A zone cut seen from the parent: NS without SOA.
+fn is_delegation(types: &[RecordType]) -> bool {
+    types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA)
+}
+
+fn wildcard(name: &str) -> String {
+    match name.is_empty() {
+        true => "*".to_string(),
+        false => format!("*.{}", name),
+    }
+}
+
+/// Whether `name` falls strictly between the owner and the next name of `nsec`.
+fn covers(nsec: &Nsec, name: &str) -> bool {
+    // Names below a delegation belong to another zone (RFC 4035 5.4).
+    if is_subdomain_of(name, nsec.owner) && !same_name(name, nsec.owner) && is_delegation(nsec.types) {
+        return false;
+    }
+
+    let after_owner = canonical_cmp(nsec.owner, name) == Ordering::Less;
+    let before_next = canonical_cmp(name, nsec.next) == Ordering::Less;
+    // The last NSEC of a zone points back to the apex.
+    let is_last = canonical_cmp(nsec.next, nsec.owner) != Ordering::Greater;
+
+    match is_last {
+        true => after_owner || before_next,
+        false => after_owner && before_next,
+    }
+}
+
+/// The closest encloser of `name` implied by an NSEC covering it: the
+/// longest of its ancestors shared with the owner or next name.
+fn nsec_closest_encloser(nsec: &Nsec, name: &str) -> String {
+    let mut closest = normalize(name);
+    while let Some(ancestor) = parent(&closest) {
+        closest = ancestor.to_string();
+        if is_subdomain_of(nsec.owner, &closest) || is_subdomain_of(nsec.next, &closest) {
+            break;
+        }
+    }
+
+    closest
+}
+
+/// Whether the NSEC covering `name` shows that names exist below it, which
+/// makes `name` an empty non-terminal.
+fn is_empty_non_terminal(nsec: &Nsec, name: &str) -> bool {
+    is_subdomain_of(nsec.next, name) && !same_name(nsec.next, name)
+}
+
+fn nsec_nxdomain(nsecs: &[Nsec], name: &str) -> bool {
+    let Some(nsec) = nsecs.iter().find(|nsec| covers(nsec, name)) else {
+        return false;
+    };
+    if is_empty_non_terminal(nsec, name) {
+        return false;
+    }
+
+    let source = wildcard(&nsec_closest_encloser(nsec, name));
+    nsecs.iter().any(|wildcard| covers(wildcard, &source))
+}
+
+fn hash_for(nsec3: &Nsec3, name: &str) -> Option<Vec<u8>> {
+    is_subdomain_of(name, nsec3.zone).then(|| nsec3_hash(name, nsec3.salt, nsec3.iterations))
+}
+
+fn matching<'a, 'b>(nsec3s: &'b [Nsec3<'a>], name: &str) -> Option<&'b Nsec3<'a>> {
+    nsec3s.iter().find(|nsec3| hash_for(nsec3, name).is_some_and(|hash| hash == nsec3.owner_hash))
+}
+
+fn covering<'a, 'b>(nsec3s: &'b [Nsec3<'a>], name: &str) -> Option<&'b Nsec3<'a>> {
+    nsec3s.iter().find(|nsec3| {
+        hash_for(nsec3, name).is_some_and(|hash| {
+            let after_owner = hash.as_slice() > nsec3.owner_hash.as_slice();
+            let before_next = hash.as_slice() < nsec3.next_hash;
+            match nsec3.next_hash <= nsec3.owner_hash.as_slice() {
+                true => after_owner || before_next,
+                false => after_owner && before_next,
+            }
+        })
+    })
+}
+
+/// The closest encloser proof (RFC 5155 8.3): the closest existing ancestor
+/// of `name`, and the NSEC3 covering the next closer name below it.
+fn closest_encloser<'a, 'b>(nsec3s: &'b [Nsec3<'a>], name: &str) -> Option<(String, &'b Nsec3<'a>)> {
+    let mut next_closer = normalize(name);
+    if matching(nsec3s, &next_closer).is_some() {
+        return None;
+    }
+
+    while let Some(ancestor) = parent(&next_closer) {
+        if matching(nsec3s, ancestor).is_some() {
+            return covering(nsec3s, &next_closer).map(|nsec3| (ancestor.to_string(), nsec3));
+        }
+        next_closer = ancestor.to_string();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::dns_record_type::Class;
+    use crate::protocol::encoding::to_base32hex;
+
+    fn nsec(owner: &str, next: &str, types: &[RecordType]) -> DnsRecord {
+        let rdata = DnsRecordType::NSEC { next_domain_name: next.to_string(), types: types.to_vec() };
+        DnsRecord::new(owner.to_string(), Class::IN, 300, rdata)
+    }
+
+    #[test]
+    fn nsec3_hash_matches_rfc_5155() {
+        let salt = [0xAA, 0xBB, 0xCC, 0xDD];
+
+        assert_eq!(to_base32hex(&nsec3_hash("example", &salt, 12)).to_lowercase(), "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom");
+        assert_eq!(to_base32hex(&nsec3_hash("a.example", &salt, 12)).to_lowercase(), "35mthgpgcu1qg68fab165klnsnk3dpvl");
+    }
+
+    #[test]
+    fn nsec_proves_nxdomain_and_nodata() {
+        let records = [
+            nsec("example", "a.example", &[RecordType::NS, RecordType::SOA, RecordType::RRSIG, RecordType::NSEC]),
+            nsec("a.example", "c.example", &[RecordType::A, RecordType::RRSIG, RecordType::NSEC]),
+            nsec("sub.example", "example", &[RecordType::NS, RecordType::NSEC]),
+        ];
+
+        assert!(proves_nxdomain(&records, "b.example"));
+        assert!(!proves_nxdomain(&records, "a.example"));
+        assert!(proves_nodata(&records, "a.example", RecordType::AAAA));
+        assert!(!proves_nodata(&records, "a.example", RecordType::A));
+        assert!(!proves_nodata(&records, "sub.example", RecordType::A));
+        assert_eq!(missing_ds(&records, "sub.example"), Some(MissingDs::InsecureDelegation));
+        assert_eq!(missing_ds(&records, "a.example"), Some(MissingDs::NotAZoneCut));
+        // Names below a delegation aren't covered by the NSEC at the cut.
+        assert!(!proves_nxdomain(&records, "www.sub.example"));
+    }
+
+    #[test]
+    fn nsec_treats_empty_non_terminals_as_existing() {
+        let records = [nsec("a.example", "x.b.example", &[RecordType::A, RecordType::RRSIG, RecordType::NSEC])];
+
+        assert!(!proves_nxdomain(&records, "b.example"));
+        assert!(proves_nodata(&records, "b.example", RecordType::A));
+        assert_eq!(missing_ds(&records, "b.example"), Some(MissingDs::NotAZoneCut));
+    }
+}
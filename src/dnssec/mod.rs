@@ -0,0 +1,363 @@
+//! DNSSEC (RFC 4033, 4034 and 4035).
+//!
+//! The building blocks [`crate::resolver::ValidatingResolver`] uses to check
+//! signed responses: key tags, DS digests, signature verification over the
+//! canonical form of RRsets, and NSEC/NSEC3 proofs of non-existence. The
+//! [`signer`] module signs the answers of authoritative zones.
+
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+use crate::errors::BufferError;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+use crate::protocol::domain_name::{label_count, normalize};
+use crate::protocol::encoding::from_hex;
+use crate::protocol::packet_buffer::PacketBuffer;
+
+pub mod denial;
+pub mod signer;
+#[cfg(test)]
+pub(crate) mod testing;
+
+type Result<T> = std::result::Result<T, BufferError>;
+
+pub const RSASHA256: u8 = 8;
+pub const ECDSAP256SHA256: u8 = 13;
+pub const ECDSAP384SHA384: u8 = 14;
+pub const ED25519: u8 = 15;
+
+pub const DIGEST_SHA256: u8 = 2;
+pub const DIGEST_SHA384: u8 = 4;
+
+/// DNSKEY flag marking a key that may sign zone data.
+pub const ZONE_KEY_FLAG: u16 = 0x0100;
+/// DNSKEY flag of a revoked key (RFC 5011).
+pub const REVOKE_FLAG: u16 = 0x0080;
+/// DNSKEY flag conventionally set on key signing keys.
+pub const SECURE_ENTRY_POINT_FLAG: u16 = 0x0001;
+
+/// The root zone KSK-2017 as a DS record, the default trust anchor.
+pub fn root_trust_anchor() -> DnsRecord {
+    let digest = from_hex("E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D")
+        .expect("constant is valid hex");
+
+    DnsRecord::new(
+        String::new(),
+        Class::IN,
+        172800,
+        DnsRecordType::DS { key_tag: 20326, algorithm: RSASHA256, digest_type: DIGEST_SHA256, digest },
+    )
+}
+
+pub fn is_supported_algorithm(algorithm: u8) -> bool {
+    matches!(algorithm, RSASHA256 | ECDSAP256SHA256 | ECDSAP384SHA384 | ED25519)
+}
+
+pub fn is_supported_digest(digest_type: u8) -> bool {
+    matches!(digest_type, DIGEST_SHA256 | DIGEST_SHA384)
+}
+
+/// The key tag of a DNSKEY (RFC 4034 appendix B), or `None` for other types.
+pub fn key_tag(dnskey: &DnsRecordType) -> Option<u16> {
+    if !matches!(dnskey, DnsRecordType::DNSKEY { .. }) {
+        return None;
+    }
+    let rdata = canonical_rdata(dnskey).ok()?;
+
+    let mut sum = rdata
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| if i % 2 == 0 { (*byte as u32) << 8 } else { *byte as u32 })
+        .sum::<u32>();
+    sum += sum >> 16 & 0xFFFF;
+
+    Some(sum as u16)
+}
+
+/// The DS digest of the DNSKEY `dnskey` owned by `owner`, or `None` if
+/// `digest_type` isn't supported.
+pub fn ds_digest(owner: &str, dnskey: &DnsRecordType, digest_type: u8) -> Option<Vec<u8>> {
+    let algorithm = match digest_type {
+        DIGEST_SHA256 => &digest::SHA256,
+        DIGEST_SHA384 => &digest::SHA384,
+        _ => return None,
+    };
+
+    let mut data = canonical_name(owner).ok()?;
+    data.extend(canonical_rdata(dnskey).ok()?);
+
+    Some(digest::digest(algorithm, &data).as_ref().to_vec())
+}
+
+/// Whether the DS record `ds` refers to the DNSKEY `dnskey` of `owner`.
+pub fn ds_matches(owner: &str, ds: &DnsRecordType, dnskey: &DnsRecordType) -> bool {
+    let (DnsRecordType::DS { key_tag: tag, algorithm, digest_type, digest }, DnsRecordType::DNSKEY { algorithm: key_algorithm, .. }) =
+        (ds, dnskey)
+    else {
+        return false;
+    };
+
+    key_tag(dnskey) == Some(*tag)
+        && algorithm == key_algorithm
+        && ds_digest(owner, dnskey, *digest_type).is_some_and(|computed| computed == *digest)
+}
+
+/// Checks `signature` over `message` with a DNSKEY public key in its wire form.
+pub fn verify_signature(algorithm: u8, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    match algorithm {
+        // RFC 3110 2: exponent length, exponent, modulus.
+        RSASHA256 => {
+            let (exponent_length, rest) = match public_key {
+                [0, high, low, rest @ ..] => ((*high as usize) << 8 | *low as usize, rest),
+                [length, rest @ ..] => (*length as usize, rest),
+                [] => return false,
+            };
+            if exponent_length == 0 || rest.len() <= exponent_length {
+                return false;
+            }
+            let (e, n) = rest.split_at(exponent_length);
+            let n = &n[n.iter().take_while(|byte| **byte == 0).count()..];
+
+            RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY, message, signature)
+                .is_ok()
+        }
+        // RFC 6605 4: the uncompressed point without its 0x04 prefix.
+        ECDSAP256SHA256 | ECDSAP384SHA384 => {
+            let algorithm = match algorithm {
+                ECDSAP256SHA256 => &signature::ECDSA_P256_SHA256_FIXED,
+                _ => &signature::ECDSA_P384_SHA384_FIXED,
+            };
+            let mut point = vec![0x04];
+            point.extend_from_slice(public_key);
+
+            UnparsedPublicKey::new(algorithm, point).verify(message, signature).is_ok()
+        }
+        ED25519 => UnparsedPublicKey::new(&signature::ED25519, public_key).verify(message, signature).is_ok(),
+        _ => false,
+    }
+}
+
+/// Verifies the RRSIG `rrsig` over `rrset` with the DNSKEY `dnskey`.
+///
+/// Only the cryptography is checked here; matching owner names, signers and
+/// validity periods is up to the caller.
+pub fn verify_rrsig(rrsig: &DnsRecordType, rrset: &[DnsRecord], dnskey: &DnsRecordType) -> bool {
+    let (DnsRecordType::RRSIG { algorithm, signature, .. }, DnsRecordType::DNSKEY { algorithm: key_algorithm, public_key, .. }) =
+        (rrsig, dnskey)
+    else {
+        return false;
+    };
+    if algorithm != key_algorithm {
+        return false;
+    }
+
+    match signed_data(rrsig, rrset) {
+        Ok(data) => verify_signature(*algorithm, public_key, &data, signature),
+        Err(_) => false,
+    }
+}
+
+/// The data an RRSIG signs (RFC 4034 3.1.8.1): its own RDATA without the
+/// signature, followed by the RRset in canonical form and order.
+pub fn signed_data(rrsig: &DnsRecordType, rrset: &[DnsRecord]) -> Result<Vec<u8>> {
+    let DnsRecordType::RRSIG { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, .. } =
+        rrsig
+    else {
+        return Err(BufferError::InvalidValue { field: "RRSIG" });
+    };
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&u16::from(*type_covered).to_be_bytes());
+    data.push(*algorithm);
+    data.push(*labels);
+    data.extend_from_slice(&original_ttl.to_be_bytes());
+    data.extend_from_slice(&expiration.to_be_bytes());
+    data.extend_from_slice(&inception.to_be_bytes());
+    data.extend_from_slice(&key_tag.to_be_bytes());
+    data.extend(canonical_name(signer_name)?);
+
+    let mut records = Vec::new();
+    for record in rrset {
+        // A record synthesized from a wildcard is signed with the wildcard as its owner.
+        let mut owner = normalize(record.record());
+        let owner_labels = label_count(&owner);
+        if (*labels as usize) < owner_labels {
+            let closest: Vec<&str> = owner.split('.').skip(owner_labels - *labels as usize).collect();
+            owner = std::iter::once("*").chain(closest).collect::<Vec<&str>>().join(".");
+        }
+
+        let rdata = canonical_rdata(record.rdata())?;
+        let mut encoded = canonical_name(&owner)?;
+        encoded.extend_from_slice(&u16::from(record.rdata().get_type()).to_be_bytes());
+        encoded.extend_from_slice(&u16::from(record.response_class()).to_be_bytes());
+        encoded.extend_from_slice(&original_ttl.to_be_bytes());
+        encoded.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        records.push((rdata, encoded));
+    }
+    records.sort();
+    records.dedup();
+    for (rdata, encoded) in records {
+        data.extend(encoded);
+        data.extend(rdata);
+    }
+
+    Ok(data)
+}
+
+/// `name` lowercased in wire form.
+pub fn canonical_name(name: &str) -> Result<Vec<u8>> {
+    let mut buffer = PacketBuffer::without_compression();
+    buffer.write_qname(&normalize(name))?;
+
+    Ok(buffer.buffer)
+}
+
+/// The RDATA in wire form without compression, with the embedded names of
+/// the types listed in RFC 4034 6.2 (as amended by RFC 6840 5.1) lowercased.
+pub fn canonical_rdata(rdata: &DnsRecordType) -> Result<Vec<u8>> {
+    let lowercased = match rdata.clone() {
+        DnsRecordType::NS { name_server } => DnsRecordType::NS { name_server: normalize(&name_server) },
+        DnsRecordType::CNAME { canonical_name } => DnsRecordType::CNAME { canonical_name: normalize(&canonical_name) },
+        DnsRecordType::SOA { mname, rname, serial, refresh, retry, expire, minimum } => DnsRecordType::SOA {
+            mname: normalize(&mname),
+            rname: normalize(&rname),
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        },
+        DnsRecordType::PTR { domain_name } => DnsRecordType::PTR { domain_name: normalize(&domain_name) },
+        DnsRecordType::MX { priority, exchange } => DnsRecordType::MX { priority, exchange: normalize(&exchange) },
+        DnsRecordType::SRV { priority, weight, port, target } => {
+            DnsRecordType::SRV { priority, weight, port, target: normalize(&target) }
+        }
+        DnsRecordType::NAPTR { order, preference, flags, services, regexp, replacement } => DnsRecordType::NAPTR {
+            order,
+            preference,
+            flags,
+            services,
+            regexp,
+            replacement: normalize(&replacement),
+        },
+        DnsRecordType::RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        } => DnsRecordType::RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name: normalize(&signer_name),
+            signature,
+        },
+        other => other,
+    };
+
+    let mut buffer = PacketBuffer::without_compression();
+    lowercased.serialize(&mut buffer)?;
+
+    // Drop the RDLENGTH written in front of the RDATA.
+    Ok(buffer.buffer.split_off(2))
+}
+
+/// Splits records into RRsets, leaving out the RRSIGs.
+pub fn rrsets(records: &[DnsRecord]) -> Vec<Vec<DnsRecord>> {
+    let mut rrsets: Vec<Vec<DnsRecord>> = Vec::new();
+    for record in records.iter().filter(|record| record.rdata().get_type() != RecordType::RRSIG) {
+        let record_type = record.rdata().get_type();
+        match rrsets.iter_mut().find(|rrset| {
+            normalize(rrset[0].record()) == normalize(record.record()) && rrset[0].rdata().get_type() == record_type
+        }) {
+            Some(rrset) => rrset.push(record.clone()),
+            None => rrsets.push(vec![record.clone()]),
+        }
+    }
+
+    rrsets
+}
+
+/// Whether `record` is one DNSSEC adds to responses for clients that set the
+/// DO bit (RFC 4035 3.2.1), and that others shouldn't get unless they asked.
+pub fn is_dnssec_record(record: &DnsRecord) -> bool {
+    matches!(record.rdata().get_type(), RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::encoding::from_base64;
+
+    /// KSK-2017 of the root zone.
+    const ROOT_KSK: &str = "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=";
+
+    #[test]
+    fn root_ksk_matches_the_trust_anchor() {
+        let dnskey = DnsRecordType::DNSKEY {
+            flags: 257,
+            protocol: 3,
+            algorithm: RSASHA256,
+            public_key: from_base64(ROOT_KSK).unwrap(),
+        };
+
+        assert_eq!(key_tag(&dnskey), Some(20326));
+        assert!(ds_matches("", root_trust_anchor().rdata(), &dnskey));
+    }
+
+    #[test]
+    fn verifies_every_supported_algorithm() {
+        for algorithm in [RSASHA256, ECDSAP256SHA256, ECDSAP384SHA384, ED25519] {
+            let key = testing::TestKey::new(algorithm, 256);
+            let DnsRecordType::DNSKEY { public_key, .. } = &key.dnskey else { unreachable!() };
+            let signature = key.sign_bytes(b"signed data");
+
+            assert!(verify_signature(algorithm, public_key, b"signed data", &signature), "algorithm {}", algorithm);
+            assert!(!verify_signature(algorithm, public_key, b"other data", &signature), "algorithm {}", algorithm);
+        }
+    }
+
+    #[test]
+    fn verifies_rrsigs_over_canonical_rrsets() {
+        let key = testing::TestKey::new(ECDSAP256SHA256, 256);
+        let rrset = [
+            DnsRecord::new("WWW.example.com".to_string(), Class::IN, 300, DnsRecordType::A { address: [192, 0, 2, 2].into() }),
+            DnsRecord::new("www.example.com".to_string(), Class::IN, 300, DnsRecordType::A { address: [192, 0, 2, 1].into() }),
+        ];
+        let rrsig = key.sign("example.com", &rrset);
+
+        let mut reordered = rrset.clone();
+        reordered.reverse();
+        assert!(verify_rrsig(rrsig.rdata(), &reordered, &key.dnskey));
+        assert!(!verify_rrsig(rrsig.rdata(), &rrset[..1], &key.dnskey));
+    }
+
+    #[test]
+    fn canonical_rdata_is_lowercase_and_uncompressed() {
+        let soa = DnsRecordType::SOA {
+            mname: "NS1.Example.com".to_string(),
+            rname: "hostmaster.example.COM".to_string(),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 5,
+        };
+
+        let rdata = canonical_rdata(&soa).unwrap();
+
+        assert!(rdata.starts_with(b"\x03ns1\x07example\x03com\x00\x0ahostmaster\x07example\x03com\x00"));
+        assert_eq!(rdata.len(), 17 + 24 + 20);
+    }
+}
@@ -0,0 +1,584 @@
+//! Online signing of authoritative answers (RFC 4035 3.1).
+//!
+//! Signatures are made when a response is built instead of being stored in
+//! the zone. Denial of existence uses the NSEC chain of the zone, or NSEC3
+//! "white lies" (RFC 7129 appendix B) that only cover the hash asked about.
+
+use std::cmp::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use ring::rand::SystemRandom;
+use ring::signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair};
+
+use crate::errors::SigningError;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+use crate::protocol::domain_name::{canonical_cmp, is_subdomain_of, label_count, normalize, parent};
+use crate::protocol::encoding::to_base32hex;
+use crate::zone::Zone;
+
+use super::denial::nsec3_hash;
+use super::{ds_digest, key_tag, rrsets, signed_data, DIGEST_SHA256, ECDSAP256SHA256, ED25519, SECURE_ENTRY_POINT_FLAG};
+
+/// How long signatures stay valid unless configured otherwise.
+const DEFAULT_VALIDITY: Duration = Duration::from_secs(7 * 86400);
+
+/// Signatures start this long in the past, for validators with slow clocks.
+const INCEPTION_OFFSET: u32 = 3600;
+
+/// A private key able to sign a zone, with ECDSA P-256 or Ed25519.
+pub struct SigningKey {
+    pair: Pair,
+    pkcs8: Vec<u8>,
+    dnskey: DnsRecordType,
+}
+
+enum Pair {
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+impl SigningKey {
+    /// Generates a new key. `flags` is 257 for a key signing key, 256 for a
+    /// zone signing key.
+    pub fn generate(algorithm: u8, flags: u16) -> Result<Self, SigningError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = match algorithm {
+            ECDSAP256SHA256 => EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng),
+            ED25519 => Ed25519KeyPair::generate_pkcs8(&rng),
+            _ => return Err(SigningError::UnsupportedAlgorithm(algorithm)),
+        }
+        .map_err(|_| SigningError::InvalidKey)?;
+
+        SigningKey::from_pkcs8(algorithm, flags, pkcs8.as_ref())
+    }
+
+    /// Loads a key from its PKCS#8 document, as returned by [`SigningKey::pkcs8`].
+    pub fn from_pkcs8(algorithm: u8, flags: u16, pkcs8: &[u8]) -> Result<Self, SigningError> {
+        let (pair, public_key) = match algorithm {
+            ECDSAP256SHA256 => {
+                let pair = EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8, &SystemRandom::new())
+                    .map_err(|_| SigningError::InvalidKey)?;
+                // Without the 0x04 prefix of the uncompressed point (RFC 6605 4).
+                let public_key = pair.public_key().as_ref()[1..].to_vec();
+                (Pair::Ecdsa(pair), public_key)
+            }
+            ED25519 => {
+                let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8).map_err(|_| SigningError::InvalidKey)?;
+                let public_key = pair.public_key().as_ref().to_vec();
+                (Pair::Ed25519(pair), public_key)
+            }
+            _ => return Err(SigningError::UnsupportedAlgorithm(algorithm)),
+        };
+
+        Ok(SigningKey {
+            pair,
+            pkcs8: pkcs8.to_vec(),
+            dnskey: DnsRecordType::DNSKEY { flags, protocol: 3, algorithm, public_key },
+        })
+    }
+
+    /// The private key as a PKCS#8 document, to store it.
+    pub fn pkcs8(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    /// The DNSKEY record data of the public key.
+    pub fn dnskey(&self) -> &DnsRecordType {
+        &self.dnskey
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        key_tag(&self.dnskey).unwrap_or_default()
+    }
+
+    pub fn is_key_signing_key(&self) -> bool {
+        matches!(self.dnskey, DnsRecordType::DNSKEY { flags, .. } if flags & SECURE_ENTRY_POINT_FLAG != 0)
+    }
+
+    /// An RRSIG of `zone` over `rrset`, valid between the two timestamps.
+    pub fn sign(&self, zone: &str, rrset: &[DnsRecord], inception: u32, expiration: u32) -> Result<DnsRecord, SigningError> {
+        rrsig(&self.dnskey, zone, rrset, inception, expiration, |data| match &self.pair {
+            Pair::Ecdsa(pair) => pair
+                .sign(&SystemRandom::new(), data)
+                .map(|signature| signature.as_ref().to_vec())
+                .map_err(|_| SigningError::FailedToSign),
+            Pair::Ed25519(pair) => Ok(pair.sign(data).as_ref().to_vec()),
+        })
+    }
+}
+
+/// Builds the RRSIG made with `dnskey` of `zone` over `rrset`, `sign`
+/// turning the data to sign into the signature.
+pub(crate) fn rrsig(
+    dnskey: &DnsRecordType,
+    zone: &str,
+    rrset: &[DnsRecord],
+    inception: u32,
+    expiration: u32,
+    sign: impl FnOnce(&[u8]) -> Result<Vec<u8>, SigningError>,
+) -> Result<DnsRecord, SigningError> {
+    let (DnsRecordType::DNSKEY { algorithm, .. }, Some(key_tag), Some(first)) = (dnskey, key_tag(dnskey), rrset.first()) else {
+        return Err(SigningError::InvalidKey);
+    };
+    let owner = first.record();
+    // The labels field doesn't count the asterisk of a wildcard (RFC 4034 3.1.3).
+    let labels = label_count(owner) - (owner == "*" || owner.starts_with("*.")) as usize;
+
+    let mut rdata = DnsRecordType::RRSIG {
+        type_covered: first.rdata().get_type(),
+        algorithm: *algorithm,
+        labels: labels as u8,
+        original_ttl: first.ttl(),
+        expiration,
+        inception,
+        key_tag,
+        signer_name: normalize(zone),
+        signature: Vec::new(),
+    };
+    let data = signed_data(&rdata, rrset).map_err(SigningError::InvalidRrset)?;
+    if let DnsRecordType::RRSIG { signature, .. } = &mut rdata {
+        *signature = sign(&data)?;
+    }
+
+    Ok(DnsRecord::new(owner.to_string(), first.response_class(), first.ttl(), rdata))
+}
+
+/// How a signed zone proves that names or types don't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    Nsec,
+    /// Hashed names, minimally covered. RFC 9276 recommends no extra
+    /// iterations and no salt.
+    Nsec3 { iterations: u16, salt: Vec<u8> },
+}
+
+/// Signs the answers of one zone with its keys.
+///
+/// With several keys, those with the SEP flag sign the DNSKEY, CDS and
+/// CDNSKEY RRsets and the others everything else. A single key signs it all.
+pub struct ZoneSigner {
+    keys: Vec<SigningKey>,
+    denial: Denial,
+    validity: Duration,
+    /// Owner names in canonical order, for the NSEC chain.
+    chain: Vec<String>,
+}
+
+impl ZoneSigner {
+    pub fn new(keys: Vec<SigningKey>) -> Self {
+        ZoneSigner {
+            keys,
+            denial: Denial::Nsec,
+            validity: DEFAULT_VALIDITY,
+            chain: Vec::new(),
+        }
+    }
+
+    /// Uses NSEC3 white lies instead of the NSEC chain.
+    pub fn with_nsec3(self, iterations: u16, salt: Vec<u8>) -> Self {
+        ZoneSigner { denial: Denial::Nsec3 { iterations, salt }, ..self }
+    }
+
+    /// How long signatures stay valid after they are made.
+    pub fn with_validity(self, validity: Duration) -> Self {
+        ZoneSigner { validity, ..self }
+    }
+
+    pub fn keys(&self) -> &[SigningKey] {
+        &self.keys
+    }
+
+    pub fn denial(&self) -> &Denial {
+        &self.denial
+    }
+
+    /// Adds the DNSKEY, CDS, CDNSKEY and NSEC3PARAM records to the apex of
+    /// `zone`, and prepares the NSEC chain of its names.
+    pub fn prepare(&mut self, zone: &mut Zone) {
+        let ttl = zone.soa().ttl();
+        let origin = zone.origin().to_string();
+        let record = |rdata: DnsRecordType| DnsRecord::new(origin.clone(), Class::IN, ttl, rdata);
+
+        let mut records: Vec<DnsRecord> = self.keys.iter().map(|key| record(key.dnskey.clone())).collect();
+        for key in self.signing_keys(RecordType::DNSKEY) {
+            let DnsRecordType::DNSKEY { flags, protocol, algorithm, public_key } = key.dnskey.clone() else {
+                continue;
+            };
+            let digest = ds_digest(&origin, &key.dnskey, DIGEST_SHA256).unwrap_or_default();
+            records.push(record(DnsRecordType::CDS { key_tag: key.key_tag(), algorithm, digest_type: DIGEST_SHA256, digest }));
+            records.push(record(DnsRecordType::CDNSKEY { flags, protocol, algorithm, public_key }));
+        }
+        if let Denial::Nsec3 { iterations, salt } = &self.denial {
+            records.push(record(DnsRecordType::NSEC3PARAM { hash_algorithm: 1, flags: 0, iterations: *iterations, salt: salt.clone() }));
+        }
+        for record in records {
+            zone.insert(record).expect("apex records are inside the zone");
+        }
+
+        // Names below a zone cut are glue, which isn't part of the chain.
+        let mut chain: Vec<String> = zone
+            .owner_names()
+            .filter(|name| zone.zone_cut(name).is_none_or(|cut| cut == *name))
+            .map(str::to_string)
+            .collect();
+        chain.sort_by(|a, b| canonical_cmp(a, b));
+        self.chain = chain;
+    }
+
+    /// Adds the RRSIGs of the answer RRsets and of the authoritative data in
+    /// the authority section of `response`.
+    pub fn sign_response(&self, zone: &Zone, response: DnsPacket) -> DnsPacket {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as u32);
+        let inception = now.wrapping_sub(INCEPTION_OFFSET);
+        let expiration = now.wrapping_add(self.validity.as_secs() as u32);
+
+        let sign = |records: &[DnsRecord]| -> Vec<DnsRecord> {
+            let mut rrsigs = Vec::new();
+            for rrset in rrsets(records) {
+                let record_type = rrset[0].rdata().get_type();
+                // The NS records of a referral belong to the child zone.
+                let owner = normalize(rrset[0].record());
+                let authoritative = is_subdomain_of(&owner, zone.origin())
+                    && (record_type != RecordType::NS || zone.zone_cut(&owner).is_none());
+                if !authoritative {
+                    continue;
+                }
+                for key in self.signing_keys(record_type) {
+                    match key.sign(zone.origin(), &rrset, inception, expiration) {
+                        Ok(rrsig) => rrsigs.push(rrsig),
+                        Err(e) => warn!("Failed to sign {} {}: {}", rrset[0].record(), record_type, e),
+                    }
+                }
+            }
+            rrsigs
+        };
+
+        let answer_rrsigs = sign(&response.answers);
+        let authority_rrsigs = sign(&response.authority);
+        let mut response = response;
+        for rrsig in answer_rrsigs {
+            response = response.with_answer(rrsig);
+        }
+        for rrsig in authority_rrsigs {
+            response = response.with_authority(rrsig);
+        }
+
+        response
+    }
+
+    /// The records proving that `name` doesn't exist in `zone`, none when
+    /// `name` is outside of it.
+    pub fn nxdomain_proof(&self, zone: &Zone, name: &str) -> Vec<DnsRecord> {
+        let name = normalize(name);
+        if name == zone.origin() || !is_subdomain_of(&name, zone.origin()) {
+            return Vec::new();
+        }
+        // The walk ends at the origin at the latest.
+        let mut closest_encloser = parent(&name).unwrap_or_default();
+        while closest_encloser != zone.origin() && !zone.contains_name(closest_encloser) {
+            closest_encloser = parent(closest_encloser).unwrap_or_default();
+        }
+        let wildcard = match closest_encloser.is_empty() {
+            true => "*".to_string(),
+            false => format!("*.{}", closest_encloser),
+        };
+
+        let mut proof = match self.denial {
+            Denial::Nsec => [self.covering_nsec(zone, &name), self.covering_nsec(zone, &wildcard)].into_iter().flatten().collect(),
+            Denial::Nsec3 { .. } => {
+                let labels: Vec<&str> = name.split('.').collect();
+                let next_closer = labels[labels.len() - label_count(closest_encloser) - 1..].join(".");
+                vec![
+                    self.matching_nsec3(zone, closest_encloser),
+                    self.covering_nsec3(zone, &next_closer),
+                    self.covering_nsec3(zone, &wildcard),
+                ]
+            }
+        };
+        proof.dedup();
+
+        proof
+    }
+
+    /// The records proving that `name` exists in `zone`, but not with the
+    /// type that was asked for.
+    pub fn nodata_proof(&self, zone: &Zone, name: &str) -> Vec<DnsRecord> {
+        let name = normalize(name);
+        match self.denial {
+            Denial::Nsec => match self.chain.binary_search_by(|owner| canonical_cmp(owner, &name)) {
+                Ok(index) => vec![self.nsec(zone, index)],
+                // An empty non-terminal, covered by an NSEC pointing below it.
+                Err(_) => self.covering_nsec(zone, &name).into_iter().collect(),
+            },
+            Denial::Nsec3 { .. } => vec![self.matching_nsec3(zone, &name)],
+        }
+    }
+
+    /// What goes along the NS records of a referral to `cut`: its DS RRset,
+    /// or the proof that there is none.
+    pub fn delegation_proof(&self, zone: &Zone, cut: &str) -> Vec<DnsRecord> {
+        let ds = zone.records_of_type(cut, RecordType::DS);
+        match ds.is_empty() {
+            true => self.nodata_proof(zone, cut),
+            false => ds,
+        }
+    }
+
+    /// Key signing keys sign the key RRsets, zone signing keys the rest,
+    /// unless there are only keys of one kind.
+    fn signing_keys(&self, record_type: RecordType) -> impl Iterator<Item = &SigningKey> {
+        let for_keys = matches!(record_type, RecordType::DNSKEY | RecordType::CDS | RecordType::CDNSKEY);
+        let has_ksk = self.keys.iter().any(SigningKey::is_key_signing_key);
+        let has_zsk = self.keys.iter().any(|key| !key.is_key_signing_key());
+
+        self.keys.iter().filter(move |key| match for_keys {
+            true => !has_ksk || key.is_key_signing_key(),
+            false => !has_zsk || !key.is_key_signing_key(),
+        })
+    }
+
+    /// The types at `name` for its NSEC or NSEC3 record.
+    fn types_at(&self, zone: &Zone, name: &str) -> Vec<RecordType> {
+        let mut types: Vec<RecordType> = zone.records(name).iter().map(|record| record.rdata().get_type()).collect();
+        let unsigned_delegation = name != zone.origin() && types.contains(&RecordType::NS) && !types.contains(&RecordType::DS);
+
+        match self.denial {
+            Denial::Nsec => types.extend([RecordType::RRSIG, RecordType::NSEC]),
+            Denial::Nsec3 { .. } if !types.is_empty() && !unsigned_delegation => types.push(RecordType::RRSIG),
+            Denial::Nsec3 { .. } => {}
+        }
+        types.sort_by_key(|record_type| u16::from(*record_type));
+        types.dedup();
+
+        types
+    }
+
+    fn negative_ttl(zone: &Zone) -> u32 {
+        zone.negative_soa().ttl()
+    }
+
+    fn nsec(&self, zone: &Zone, index: usize) -> DnsRecord {
+        let owner = &self.chain[index];
+        let next = &self.chain[(index + 1) % self.chain.len()];
+        let rdata = DnsRecordType::NSEC { next_domain_name: next.clone(), types: self.types_at(zone, owner) };
+
+        DnsRecord::new(owner.clone(), Class::IN, ZoneSigner::negative_ttl(zone), rdata)
+    }
+
+    /// The NSEC whose span contains `name`, which isn't in the chain. There
+    /// is none until the chain is built by [`ZoneSigner::prepare`].
+    fn covering_nsec(&self, zone: &Zone, name: &str) -> Option<DnsRecord> {
+        if self.chain.is_empty() {
+            return None;
+        }
+        let index = self.chain.partition_point(|owner| canonical_cmp(owner, name) == Ordering::Less);
+        Some(self.nsec(zone, (index + self.chain.len() - 1) % self.chain.len()))
+    }
+
+    fn nsec3(&self, zone: &Zone, owner_hash: &[u8], next_hashed_owner: Vec<u8>, types: Vec<RecordType>) -> DnsRecord {
+        let Denial::Nsec3 { iterations, salt } = &self.denial else {
+            unreachable!("only called when signing with NSEC3");
+        };
+        let label = to_base32hex(owner_hash).to_lowercase();
+        let owner = match zone.origin().is_empty() {
+            true => label,
+            false => format!("{}.{}", label, zone.origin()),
+        };
+        let rdata = DnsRecordType::NSEC3 {
+            hash_algorithm: 1,
+            flags: 0,
+            iterations: *iterations,
+            salt: salt.clone(),
+            next_hashed_owner,
+            types,
+        };
+
+        DnsRecord::new(owner, Class::IN, ZoneSigner::negative_ttl(zone), rdata)
+    }
+
+    fn hash(&self, name: &str) -> Vec<u8> {
+        match &self.denial {
+            Denial::Nsec3 { iterations, salt } => nsec3_hash(name, salt, *iterations),
+            Denial::Nsec => Vec::new(),
+        }
+    }
+
+    /// The NSEC3 of `name`, which exists in the zone.
+    fn matching_nsec3(&self, zone: &Zone, name: &str) -> DnsRecord {
+        let hash = self.hash(name);
+        self.nsec3(zone, &hash, increment(&hash), self.types_at(zone, name))
+    }
+
+    /// An NSEC3 covering the hash of `name` and nothing else.
+    fn covering_nsec3(&self, zone: &Zone, name: &str) -> DnsRecord {
+        let hash = self.hash(name);
+        self.nsec3(zone, &decrement(&hash), increment(&hash), Vec::new())
+    }
+}
+
+fn increment(hash: &[u8]) -> Vec<u8> {
+    let mut hash = hash.to_vec();
+    for byte in hash.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_add(1);
+        *byte = value;
+        if !overflow {
+            break;
+        }
+    }
+
+    hash
+}
+
+fn decrement(hash: &[u8]) -> Vec<u8> {
+    let mut hash = hash.to_vec();
+    for byte in hash.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_sub(1);
+        *byte = value;
+        if !overflow {
+            break;
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dnssec::{verify_rrsig, RSASHA256};
+    use crate::protocol::dns_header::ResponseCode;
+    use crate::protocol::edns::Edns;
+    use crate::resolver::{AuthoritativeResolver, Protocol, RequestContext, Resolver, ValidatingResolver};
+
+    const ZONE: &str = r#"
+$ORIGIN example.com.
+$TTL 3600
+@           SOA     ns1 hostmaster 1 7200 900 604800 300
+            NS      ns1
+ns1         A       192.0.2.1
+www         A       192.0.2.10
+alias       CNAME   www
+deep.empty  A       192.0.2.20
+child       NS      ns.child
+ns.child    A       192.0.2.53
+"#;
+
+    fn signed_zone(signer: ZoneSigner) -> AuthoritativeResolver {
+        AuthoritativeResolver::new(vec![Zone::parse(ZONE, "example.com").unwrap()]).with_signer("example.com", signer)
+    }
+
+    fn ask(resolver: &AuthoritativeResolver, name: &str, qtype: RecordType) -> DnsPacket {
+        let query = DnsPacket::new_query(1, name, qtype).with_edns(Edns { dnssec_ok: true, ..Edns::default() });
+        resolver.answer(&query)
+    }
+
+    #[test]
+    fn keys_survive_a_pkcs8_round_trip() {
+        for algorithm in [ECDSAP256SHA256, ED25519] {
+            let key = SigningKey::generate(algorithm, 257).unwrap();
+            let loaded = SigningKey::from_pkcs8(algorithm, 257, key.pkcs8()).unwrap();
+
+            assert_eq!(loaded.dnskey(), key.dnskey());
+            assert!(loaded.is_key_signing_key());
+        }
+        assert!(SigningKey::generate(RSASHA256, 256).is_err());
+    }
+
+    #[test]
+    fn key_signing_keys_only_sign_the_key_rrsets() {
+        let ksk = SigningKey::generate(ECDSAP256SHA256, 257).unwrap();
+        let zsk = SigningKey::generate(ED25519, 256).unwrap();
+        let (ksk_tag, zsk_tag, zsk_dnskey) = (ksk.key_tag(), zsk.key_tag(), zsk.dnskey().clone());
+        let resolver = signed_zone(ZoneSigner::new(vec![ksk, zsk]));
+
+        let signers = |response: &DnsPacket| -> Vec<u16> {
+            response
+                .answers
+                .iter()
+                .filter_map(|record| match record.rdata() {
+                    DnsRecordType::RRSIG { key_tag, .. } => Some(*key_tag),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let response = ask(&resolver, "example.com", RecordType::DNSKEY);
+        assert_eq!(response.answers.len(), 3);
+        assert_eq!(signers(&response), [ksk_tag]);
+        let response = ask(&resolver, "www.example.com", RecordType::A);
+        assert_eq!(signers(&response), [zsk_tag]);
+        assert!(verify_rrsig(response.answers[1].rdata(), &response.answers[..1], &zsk_dnskey));
+
+        let cds = ask(&resolver, "example.com", RecordType::CDS);
+        assert_eq!(cds.answers.len(), 2);
+        let DnsRecordType::CDS { key_tag, .. } = cds.answers[0].rdata() else { panic!("expected a CDS record") };
+        assert_eq!(*key_tag, ksk_tag);
+        assert_eq!(ask(&resolver, "example.com", RecordType::CDNSKEY).answers.len(), 2);
+
+        // Without the DO bit, nothing changes.
+        let response = resolver.answer(&DnsPacket::new_query(1, "www.example.com", RecordType::A));
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    async fn validate(signer: ZoneSigner) {
+        let anchor = signer.keys()[0].dnskey().clone();
+        let resolver = signed_zone(signer);
+        let validating = ValidatingResolver::new(Arc::new(resolver))
+            .with_trust_anchors(vec![DnsRecord::new("example.com".to_string(), Class::IN, 3600, anchor)]);
+        let ctx = RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp);
+
+        let cases = [
+            ("www.example.com", RecordType::A, ResponseCode::NoError, true),
+            ("alias.example.com", RecordType::A, ResponseCode::NoError, true),
+            ("www.example.com", RecordType::AAAA, ResponseCode::NoError, true),
+            ("empty.example.com", RecordType::A, ResponseCode::NoError, true),
+            ("missing.example.com", RecordType::A, ResponseCode::NXDomain, true),
+            ("a.b.missing.example.com", RecordType::TXT, ResponseCode::NXDomain, true),
+            ("child.example.com", RecordType::DS, ResponseCode::NoError, true),
+            // Referrals to unsigned children are insecure, not bogus.
+            ("host.child.example.com", RecordType::A, ResponseCode::NoError, false),
+        ];
+        for (name, qtype, rcode, secure) in cases {
+            let query = DnsPacket::new_query(1, name, qtype).with_edns(Edns { dnssec_ok: true, ..Edns::default() });
+            let response = validating.resolve(&query, &ctx).await.unwrap();
+
+            assert_eq!(response.header.rcode, rcode, "{} {}", name, qtype);
+            assert_eq!(response.header.authentic_data, secure, "{} {}", name, qtype);
+        }
+    }
+
+    #[test]
+    fn proofs_are_empty_outside_the_zone_or_without_a_chain() {
+        let key = || SigningKey::generate(ED25519, 257).unwrap();
+        let mut zone = Zone::parse(ZONE, "example.com").unwrap();
+
+        let unprepared = ZoneSigner::new(vec![key()]);
+        assert!(unprepared.nxdomain_proof(&zone, "missing.example.com").is_empty());
+
+        let mut signer = ZoneSigner::new(vec![key()]);
+        signer.prepare(&mut zone);
+        assert!(signer.nxdomain_proof(&zone, "www.example.org").is_empty());
+        assert!(!signer.nxdomain_proof(&zone, "missing.example.com").is_empty());
+
+        let mut signer = ZoneSigner::new(vec![key()]).with_nsec3(0, Vec::new());
+        signer.prepare(&mut zone);
+        assert!(signer.nxdomain_proof(&zone, "org").is_empty());
+    }
+
+    #[tokio::test]
+    async fn nsec_signed_answers_validate() {
+        validate(ZoneSigner::new(vec![SigningKey::generate(ECDSAP256SHA256, 257).unwrap()])).await;
+    }
+
+    #[tokio::test]
+    async fn nsec3_white_lies_validate() {
+        let keys = vec![SigningKey::generate(ED25519, 257).unwrap(), SigningKey::generate(ECDSAP256SHA256, 256).unwrap()];
+        validate(ZoneSigner::new(keys).with_nsec3(0, Vec::new())).await;
+    }
+}
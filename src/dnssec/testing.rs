@@ -0,0 +1,111 @@
+//! Test helpers for signing records with throwaway keys.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::rand::SystemRandom;
+use ring::signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents};
+
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::DnsRecordType;
+use crate::protocol::encoding::from_base64;
+
+use super::*;
+
+/// A 2048 bit RSA key in PKCS#1 DER, since ring can't generate RSA keys.
+const RSA_KEY: [&str; 17] = [
+    "MIIEpAIBAAKCAQEAltm7ZwgAj6hl6SNqfiWAVKWHxk4KNbtPZ5ZulFzn+mWvW/CWQIxF5DtbvSMm1Rica/aRrrL29V2PmjyY",
+    "Wm5AOztqW8OfqHWo+53GSnmPSl596FwjkiC8V5fHCOxLsfAN+UcTaslBhWT277bO/dxF/mvY8yAB0NjG9OyjQ+SlqE/nZ3CM",
+    "ZlShC2WPp1fEfzfn7b7MVtUs4ss2QvDZrlUkERyHflUziGrlmW7M/UVOenUOlGcKW2EWta5EddlOCR2HdVfwrP5KT0nufjpW",
+    "0lO8+/30Qi2FA3oxe90oDTt2S+jZtGGrlrmN8MUxJHDs/bfdeEurEu8Uxm/3518L+1msJQIDAQABAoIBABh22j4k3ZZuvIxZ",
+    "SomHwm6347Ok72H0bTXC/07/XD3PJOfXtj5koWcYu1GKtF8F3URjGhBHhOaZFFNytVChJbiVc7PLH4b+AfhAh6qHWVY5ydiX",
+    "rMsjlPftrQFE9Se340REoyED36TnEWch8iLYiN9wlSAk1jaNGYMaJ4Y4vCb2ApfFafrbtx8pUxFfEZS2xu6EnhHHi/zAwUbS",
+    "c9sbax+C5kWgL4SnABakclTYwbDqYhaZzqFvs0u3KWQgTLgjtZVVTUFlU3Rw6n5ttvIWZZaruKL+1I9Y9h9kK6oh1xJWoc1h",
+    "bN2oSJJIEsVBq47gbHb7YP5Wxb0Djg9RtDBLvIECgYEA0Rt+A63sZe6zUmZZjB8/XD1kviRam3mwITD6r2pjXyqtgo/YPYnn",
+    "vx9qXgylb7+kvuKp1rXbjdoe6Jd8DVmnydMbl3kvl73LDIBsdwFp5BVmU4JaarjluEoxlDvnU0XU0iWWH6yClprUWjG8FNL6",
+    "27q7CrSbzVtjZv1qQxJ7XkECgYEAuK3PNKQV8/boBKie3TnYiy0LdctZ2kCDxa4klkEepfBkjAfHlqcmuc7xwVHVjqatBmNs",
+    "5JDV7b6iLf1SjNLCi9Wjb2ey+lnMqJweHtSN5N61akXv8c9qLcWNZWV1ZRmSM5xyTSHcu9pyiuA+7EQHfC9T5+AF42Gf08Sj",
+    "7WNfXOUCgYEAkbIlIC0rvG4P1cbod8BB5mQEcJNl6lRkSwwUbuqiqGlVEdZl5aLTrRAXjcYj8vPFtxa5HZgCjRjAVnFOBwND",
+    "7FTCMc1HQuQDuQc2C8kZXcknTeJSW2/O55QSbWU7rYLHYVC9A/6v0n24nctvb9b84OJ/dlsW/4PAg77oLimPecECgYBCEQPA",
+    "TDcCSMDl8cCrWadK2rlv0Wh6eHvrewnhqrghGueNZLcVAQUMkajQxMrGNoBNecIZ3mp5m6n/E9G1SSyJw8qs7wcLIpbhdsAb",
+    "vWRpjPaPZyeYdOACMdsMfAhMO6Kezgfh6gxCM7qoTeKUuR2IYpH8sJrKpkD8DCusn9EctQKBgQCYStx3eEw8rqxGSaiZUQVQ",
+    "NLNeXu6qEuqD22j4HVGX1Sa9pYKDnffIBuwhXvs3/jNAC/07Yv9cHrKrobmydlPDcT5azjrXTrMyzqXHrTime8BPxpJQOBDa",
+    "hfhDMt7C/xUR1+2Sm5egNFvuh1i5nfF7eXhjGwZ4jTwvrqgllgVARg==",
+];
+
+enum Pair {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+/// A private key together with the DNSKEY record data of its public half.
+pub struct TestKey {
+    pair: Pair,
+    pub dnskey: DnsRecordType,
+}
+
+impl TestKey {
+    pub fn new(algorithm: u8, flags: u16) -> Self {
+        let rng = SystemRandom::new();
+        let (pair, public_key) = match algorithm {
+            RSASHA256 => {
+                let pair = RsaKeyPair::from_der(&from_base64(&RSA_KEY.concat()).unwrap()).unwrap();
+                let RsaPublicKeyComponents { n, e } = RsaPublicKeyComponents::<Vec<u8>>::from(pair.public());
+                let mut public_key = vec![e.len() as u8];
+                public_key.extend(e);
+                public_key.extend(n);
+                (Pair::Rsa(pair), public_key)
+            }
+            ECDSAP256SHA256 | ECDSAP384SHA384 => {
+                let signing = match algorithm {
+                    ECDSAP256SHA256 => &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+                    _ => &signature::ECDSA_P384_SHA384_FIXED_SIGNING,
+                };
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(signing, &rng).unwrap();
+                let pair = EcdsaKeyPair::from_pkcs8(signing, pkcs8.as_ref(), &rng).unwrap();
+                // Without the 0x04 prefix of the uncompressed point.
+                let public_key = pair.public_key().as_ref()[1..].to_vec();
+                (Pair::Ecdsa(pair), public_key)
+            }
+            ED25519 => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+                let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+                let public_key = pair.public_key().as_ref().to_vec();
+                (Pair::Ed25519(pair), public_key)
+            }
+            _ => panic!("unsupported algorithm {}", algorithm),
+        };
+
+        TestKey { pair, dnskey: DnsRecordType::DNSKEY { flags, protocol: 3, algorithm, public_key } }
+    }
+
+    pub fn sign_bytes(&self, message: &[u8]) -> Vec<u8> {
+        let rng = SystemRandom::new();
+        match &self.pair {
+            Pair::Rsa(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&signature::RSA_PKCS1_SHA256, &rng, message, &mut signature).unwrap();
+                signature
+            }
+            Pair::Ecdsa(pair) => pair.sign(&rng, message).unwrap().as_ref().to_vec(),
+            Pair::Ed25519(pair) => pair.sign(message).as_ref().to_vec(),
+        }
+    }
+
+    /// An RRSIG by this key of `zone` over `rrset`, valid for an hour either way.
+    pub fn sign(&self, zone: &str, rrset: &[DnsRecord]) -> DnsRecord {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        signer::rrsig(&self.dnskey, zone, rrset, now - 3600, now + 3600, |data| Ok(self.sign_bytes(data))).unwrap()
+    }
+
+    /// The SHA-256 DS record data for this key as the DNSKEY of `zone`.
+    pub fn ds(&self, zone: &str) -> DnsRecordType {
+        let DnsRecordType::DNSKEY { algorithm, .. } = self.dnskey else { unreachable!() };
+        DnsRecordType::DS {
+            key_tag: key_tag(&self.dnskey).unwrap(),
+            algorithm,
+            digest_type: DIGEST_SHA256,
+            digest: ds_digest(zone, &self.dnskey, DIGEST_SHA256).unwrap(),
+        }
+    }
+}
@@ -1,23 +1,53 @@
 use std::error::Error;
 use std::io::Error as IoError;
 use std::fmt::Display;
-use std::net::{IpAddr};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::protocol::dns_header::ResponseCode;
+use crate::protocol::dns_packet::Section;
+use crate::protocol::dns_record_type::RecordType;
+
+/// Displays an error followed by each of its sources, as in
+/// `Failed to resolve query: Timeout, no response from 192.0.2.1:53 in 5s`.
+pub struct ErrorChain<'a>(pub &'a dyn Error);
+
+impl Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+
+        let mut source = self.0.source();
+        while let Some(error) = source {
+            write!(f, ": {}", error)?;
+            source = error.source();
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub enum ConfigError {
     NoResolverEspecified,
     NoPortSpecified,
-    FailedToCreateServer,
+    FailedToCreateServer(ServerError),
 }
 
-impl Error for ConfigError {}
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::FailedToCreateServer(source) => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let message = match self {
             ConfigError::NoResolverEspecified => "No resolver specified",
             ConfigError::NoPortSpecified => "No port specified",
-            ConfigError::FailedToCreateServer => "Error creating server"
+            ConfigError::FailedToCreateServer(_) => "Error creating server"
         };
 
         write!(f, "{}", message)
@@ -26,125 +56,387 @@ impl Display for ConfigError {
 
 #[derive(Debug)]
 pub enum ServerError {
-    FailedToBindSocket,
+    FailedToBindSocket { address: SocketAddr, source: IoError },
 }
 
-impl Error for ServerError {}
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServerError::FailedToBindSocket { source, .. } => Some(source),
+        }
+    }
+}
 
 impl Display for ServerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            ServerError::FailedToBindSocket => "Failed to bind socket, maybe the port is already in use or you don't have permission to bind it.",
-        };
-
-        write!(f, "{}", message)
+        match self {
+            ServerError::FailedToBindSocket { address, .. } => write!(
+                f,
+                "Failed to bind socket to {}, maybe the port is already in use or you don't have permission to bind it.",
+                address
+            ),
+        }
     }
 }
 
 
 #[derive(Debug)]
 pub enum QueryError {
-    FailedToSerializeQuery,
+    FailedToDeserializeQuery(DeserializeError),
     FailetToResolveQuery,
-    FailedToDeserializeResponse,
-    FailedToSerializeResponse,
+    FailedToSerializeResponse(SerializeError),
+    /// No upstream gave a usable answer, this is why the last one failed.
+    Lookup(LookupError),
+    /// The client is not allowed to query the server.
+    Refused { client: IpAddr },
+    /// The request has an opcode other than QUERY.
+    NotImplemented { opcode: u8 },
+    /// The recursive resolver stopped resolving `name`.
+    GaveUp { name: String, reason: GiveUpReason },
 }
 
-impl Error for QueryError {}
+/// Why the recursive resolver stopped resolving a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GiveUpReason {
+    MaxDepthReached,
+    LookupLoop,
+    TooManyReferrals,
+    QueryBudgetExhausted,
+    /// None of the name servers of `zone` has a known address.
+    NoNameServerAddress { zone: String },
+    CnameChainTooLong,
+}
 
-impl Display for QueryError {
+impl Display for GiveUpReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            QueryError::FailedToSerializeQuery => "Failed to serialize query",
-            QueryError::FailedToDeserializeResponse => "Failed to deserialize response",
-            QueryError::FailedToSerializeResponse => "Failed to serialize response",
-            QueryError::FailetToResolveQuery => "Failed to resolve query"
-        };
+        match self {
+            GiveUpReason::MaxDepthReached => write!(f, "maximum depth reached"),
+            GiveUpReason::LookupLoop => write!(f, "lookup loop detected"),
+            GiveUpReason::TooManyReferrals => write!(f, "too many referrals"),
+            GiveUpReason::QueryBudgetExhausted => write!(f, "query budget exhausted"),
+            GiveUpReason::NoNameServerAddress { zone } => write!(f, "no usable name server address for {}", zone),
+            GiveUpReason::CnameChainTooLong => write!(f, "CNAME chain too long"),
+        }
+    }
+}
 
-        write!(f, "{}", message)
+impl QueryError {
+    /// The RCODE of the response sent back to the client when answering
+    /// fails with this error.
+    pub fn response_code(&self) -> ResponseCode {
+        match self {
+            QueryError::FailedToDeserializeQuery(_) => ResponseCode::FormatError,
+            QueryError::Refused { .. } => ResponseCode::Refused,
+            QueryError::NotImplemented { .. } => ResponseCode::NotImplemented,
+            QueryError::FailetToResolveQuery
+            | QueryError::FailedToSerializeResponse(_)
+            | QueryError::Lookup(_)
+            | QueryError::GaveUp { .. } => ResponseCode::ServerFailure,
+        }
     }
 }
 
+impl Error for QueryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            QueryError::FailedToDeserializeQuery(source) => Some(source),
+            QueryError::FailedToSerializeResponse(source) => Some(source),
+            QueryError::Lookup(source) => Some(source),
+            QueryError::FailetToResolveQuery
+            | QueryError::Refused { .. }
+            | QueryError::NotImplemented { .. }
+            | QueryError::GaveUp { .. } => None,
+        }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::FailedToDeserializeQuery(_) => write!(f, "Failed to deserialize query"),
+            QueryError::FailedToSerializeResponse(_) => write!(f, "Failed to serialize response"),
+            QueryError::FailetToResolveQuery | QueryError::Lookup(_) => write!(f, "Failed to resolve query"),
+            QueryError::Refused { client } => write!(f, "Queries from {} are not allowed", client),
+            QueryError::NotImplemented { opcode } => write!(f, "Opcode {} is not implemented", opcode),
+            QueryError::GaveUp { name, reason } => write!(f, "Gave up resolving {}: {}", name, reason),
+        }
+    }
+}
+
+/// Why a query to an upstream server at `server` failed.
 #[derive(Debug)]
 pub enum LookupError {
-    FailedToBindSocket(IoError),
-    FailedToConnectSocket {
-        ip: IpAddr,
-        port: u16,
-    },
-    FailedToSetReadTimeout,
-    FailedToSendQuery,
-    FailedToReceiveResponse(IoError),
-    Timeout,
+    FailedToBindSocket { server: SocketAddr, source: IoError },
+    FailedToConnectSocket { server: SocketAddr, source: IoError },
+    FailedToSerializeQuery { server: SocketAddr, source: SerializeError },
+    FailedToSendQuery { server: SocketAddr, source: IoError },
+    FailedToReceiveResponse { server: SocketAddr, source: IoError },
+    InvalidResponse { server: SocketAddr, source: DeserializeError },
+    Timeout { server: SocketAddr, timeout: Duration },
+}
+
+impl LookupError {
+    /// The upstream server the query was meant for.
+    pub fn server(&self) -> SocketAddr {
+        match self {
+            LookupError::FailedToBindSocket { server, .. }
+            | LookupError::FailedToConnectSocket { server, .. }
+            | LookupError::FailedToSerializeQuery { server, .. }
+            | LookupError::FailedToSendQuery { server, .. }
+            | LookupError::FailedToReceiveResponse { server, .. }
+            | LookupError::InvalidResponse { server, .. }
+            | LookupError::Timeout { server, .. } => *server,
+        }
+    }
 }
 
-impl Error for LookupError {}
+impl Error for LookupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LookupError::FailedToBindSocket { source, .. }
+            | LookupError::FailedToConnectSocket { source, .. }
+            | LookupError::FailedToSendQuery { source, .. }
+            | LookupError::FailedToReceiveResponse { source, .. } => Some(source),
+            LookupError::FailedToSerializeQuery { source, .. } => Some(source),
+            LookupError::InvalidResponse { source, .. } => Some(source),
+            LookupError::Timeout { .. } => None,
+        }
+    }
+}
 
 impl Display for LookupError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            LookupError::FailedToBindSocket(e) => format!("Failed to bind socket: {}", e),
-            LookupError::FailedToConnectSocket { ip, port } => format!("Failed to connect to socket {}:{}", ip, port),
-            LookupError::FailedToSetReadTimeout => String::from("Failed to set read timeout"),
-            LookupError::FailedToSendQuery => String::from("Failed to send query"),
-            LookupError::FailedToReceiveResponse(e) => format!("Failed to receive response: {}", e),
-            LookupError::Timeout => String::from("Timeout, no response received")
-        };
-
-        write!(f, "{}", message)
+        match self {
+            LookupError::FailedToBindSocket { server, .. } => write!(f, "Failed to bind a socket to query {}", server),
+            LookupError::FailedToConnectSocket { server, .. } => write!(f, "Failed to connect to {}", server),
+            LookupError::FailedToSerializeQuery { server, .. } => write!(f, "Failed to serialize query for {}", server),
+            LookupError::FailedToSendQuery { server, .. } => write!(f, "Failed to send query to {}", server),
+            LookupError::FailedToReceiveResponse { server, .. } => write!(f, "Failed to receive response from {}", server),
+            LookupError::InvalidResponse { server, .. } => write!(f, "Received a response from {} that could not be parsed", server),
+            LookupError::Timeout { server, timeout } => write!(f, "Timeout, no response from {} in {:?}", server, timeout),
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum DeserializeError {
-    InvalidHeader,
-    InvalidQuestion,
-    InvalidAnswer,
-    InvalidAuthority,
-    InvalidAdditional,
-    InvalidRecord,
+    InvalidHeader(ParseError),
+    /// The entry at `index` (counting from 0) of `section` couldn't be read.
+    /// The type is missing when the bytes before it were already malformed.
+    InvalidRecord {
+        section: Section,
+        index: usize,
+        record_type: Option<RecordType>,
+        source: ParseError,
+    },
+    TrailingBytes { position: usize, count: usize },
 }
 
-impl Error for DeserializeError {}
+impl Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeserializeError::InvalidHeader(source) | DeserializeError::InvalidRecord { source, .. } => Some(source),
+            DeserializeError::TrailingBytes { .. } => None,
+        }
+    }
+}
 
 impl Display for DeserializeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            DeserializeError::InvalidHeader => "Failed to deserialize header",
-            DeserializeError::InvalidQuestion => "Failed to deserialize question",
-            DeserializeError::InvalidAnswer => "Failed to deserialize answer",
-            DeserializeError::InvalidAuthority => "Failed to deserialize authority",
-            DeserializeError::InvalidAdditional => "Failed to deserialize additional",
-            DeserializeError::InvalidRecord => "Failed to deserialize record"
-        };
-
-        write!(f, "{}", message)
+        match self {
+            DeserializeError::InvalidHeader(_) => write!(f, "Failed to deserialize header"),
+            DeserializeError::InvalidRecord { section, index, record_type: Some(record_type), .. } => {
+                write!(f, "Failed to deserialize {} {} of type {}", section, index, record_type)
+            }
+            DeserializeError::InvalidRecord { section, index, record_type: None, .. } => {
+                write!(f, "Failed to deserialize {} {}", section, index)
+            }
+            DeserializeError::TrailingBytes { position, count } => {
+                write!(f, "{} unexpected bytes after the end of the message at byte {}", count, position)
+            }
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum SerializeError {
-    InvalidHeader,
-    InvalidQuestion,
-    InvalidAnswer,
-    InvalidAuthority,
-    InvalidAdditional,
-    InvalidRecord,
+    InvalidHeader(BufferError),
+    /// The entry at `index` (counting from 0) of `section` couldn't be written.
+    InvalidRecord {
+        section: Section,
+        index: usize,
+        source: BufferError,
+    },
+    InvalidEdns(BufferError),
 }
 
-impl Error for SerializeError {}
+impl Error for SerializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SerializeError::InvalidHeader(source)
+            | SerializeError::InvalidRecord { source, .. }
+            | SerializeError::InvalidEdns(source) => Some(source),
+        }
+    }
+}
 
 impl Display for SerializeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            SerializeError::InvalidHeader => "Failed to serialize header",
-            SerializeError::InvalidQuestion => "Failed to serialize question",
-            SerializeError::InvalidAnswer => "Failed to serialize answer",
-            SerializeError::InvalidAuthority => "Failed to serialize authority",
-            SerializeError::InvalidAdditional => "Failed to serialize additional",
-            SerializeError::InvalidRecord => "Failed to serialize record"
-        };
+        match self {
+            SerializeError::InvalidHeader(_) => write!(f, "Failed to serialize header"),
+            SerializeError::InvalidRecord { section, index, .. } => write!(f, "Failed to serialize {} {}", section, index),
+            SerializeError::InvalidEdns(_) => write!(f, "Failed to serialize OPT record"),
+        }
+    }
+}
 
-        write!(f, "{}", message)
+/// Why a value couldn't be written to a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferError {
+    LimitExceeded { limit: usize },
+    LabelTooLong { length: usize },
+    /// Two dots in a row, or a leading dot.
+    EmptyLabel,
+    NameTooLong { length: usize },
+    CharacterStringTooLong { length: usize },
+    InvalidValue { field: &'static str },
+}
+
+impl Error for BufferError {}
+
+impl Display for BufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BufferError::LimitExceeded { limit } => write!(f, "Message exceeds the {} byte limit", limit),
+            BufferError::LabelTooLong { length } => write!(f, "Label of {} bytes is longer than 63", length),
+            BufferError::EmptyLabel => write!(f, "Name has an empty label"),
+            BufferError::NameTooLong { length } => write!(f, "Name of {} bytes is longer than 255", length),
+            BufferError::CharacterStringTooLong { length } => write!(f, "Character string of {} bytes is longer than 255", length),
+            BufferError::InvalidValue { field } => write!(f, "Invalid {} value", field),
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Why received bytes are not a well formed DNS message. Positions are byte
+/// offsets from the start of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    MessageTooLong { length: usize },
+    Truncated { position: usize },
+    ReservedLabelType { position: usize },
+    ForwardPointer { position: usize, target: usize },
+    PointerLoop { position: usize, target: usize },
+    NameTooLong { position: usize },
+    BadRdLength { position: usize, rdlength: u16 },
+    /// A field whose value breaks the rules of its record type or option.
+    InvalidField { position: usize, field: &'static str },
+}
+
+impl Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MessageTooLong { length } => write!(f, "Message of {} bytes is too long", length),
+            ParseError::Truncated { position } => write!(f, "Message truncated at byte {}", position),
+            ParseError::ReservedLabelType { position } => write!(f, "Reserved label type at byte {}", position),
+            ParseError::ForwardPointer { position, target } => write!(f, "Compression pointer at byte {} points forward to {}", position, target),
+            ParseError::PointerLoop { position, target } => write!(f, "Compression pointer at byte {} to {} forms a loop", position, target),
+            ParseError::NameTooLong { position } => write!(f, "Name at byte {} is longer than 255 bytes", position),
+            ParseError::BadRdLength { position, rdlength } => write!(f, "RDATA at byte {} doesn't match its RDLENGTH of {}", position, rdlength),
+            ParseError::InvalidField { position, field } => write!(f, "Invalid {} at byte {}", field, position),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ZoneError {
+    FailedToReadFile(IoError),
+    Syntax { line: usize, message: String },
+    MissingSoa,
+    OutOfZone { name: String },
+}
+
+impl Error for ZoneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ZoneError::FailedToReadFile(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ZoneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoneError::FailedToReadFile(_) => write!(f, "Failed to read zone file"),
+            ZoneError::Syntax { line, message } => write!(f, "Zone file error on line {}: {}", line, message),
+            ZoneError::MissingSoa => write!(f, "Zone has no SOA record at its apex"),
+            ZoneError::OutOfZone { name } => write!(f, "Record {} is outside of the zone", name),
+        }
+    }
+}
+
+/// Why a response failed DNSSEC validation and is bogus.
+#[derive(Debug)]
+pub enum ValidationError {
+    MissingSignature { name: String, record_type: RecordType },
+    BadSignature { name: String, record_type: RecordType },
+    NoTrustedKey { zone: String },
+    MissingDenial { name: String },
+    /// The inner resolver failed with `source`, or answered with an error
+    /// RCODE when there is none.
+    FailedToFetch { name: String, record_type: RecordType, source: Option<Box<QueryError>> },
+    TooManyQueries,
+}
+
+impl Error for ValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ValidationError::FailedToFetch { source: Some(source), .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::MissingSignature { name, record_type } => write!(f, "No signature for {} {}", name, record_type),
+            ValidationError::BadSignature { name, record_type } => write!(f, "No valid signature for {} {}", name, record_type),
+            ValidationError::NoTrustedKey { zone } => write!(f, "No trusted DNSKEY for zone {}", zone),
+            ValidationError::MissingDenial { name } => write!(f, "Missing proof of non-existence for {}", name),
+            ValidationError::FailedToFetch { name, record_type, .. } => write!(f, "Failed to fetch {} {}", name, record_type),
+            ValidationError::TooManyQueries => write!(f, "Too many queries needed to validate the response"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SigningError {
+    UnsupportedAlgorithm(u8),
+    InvalidKey,
+    /// The RRset couldn't be put in the canonical form that gets signed.
+    InvalidRrset(BufferError),
+    FailedToSign,
+}
+
+impl Error for SigningError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SigningError::InvalidRrset(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SigningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigningError::UnsupportedAlgorithm(algorithm) => write!(f, "Algorithm {} can't be used for signing", algorithm),
+            SigningError::InvalidKey => write!(f, "Invalid private key"),
+            SigningError::InvalidRrset(_) => write!(f, "Failed to encode the records to sign"),
+            SigningError::FailedToSign => write!(f, "Failed to sign records"),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod network;
-mod protocol;
+pub mod protocol;
 pub mod resolver;
 pub mod builder;
-pub mod errors;
\ No newline at end of file
+pub mod dnssec;
+pub mod errors;
+pub mod zone;
@@ -0,0 +1,83 @@
+//! Answers to CHAOS class queries, which identify the server instead of
+//! looking anything up.
+
+use crate::protocol::dns_header::ResponseCode;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+use crate::protocol::domain_name::normalize;
+
+use super::options::ServerIdentity;
+
+/// Whether `query` asks a question of the CHAOS class.
+pub(crate) fn is_chaos(query: &DnsPacket) -> bool {
+    query.questions.first().is_some_and(|question| question.qclass == Class::CH)
+}
+
+/// Answers the `version.bind`, `hostname.bind` and `id.server` TXT queries
+/// from `identity`, and refuses everything else.
+pub(crate) fn answer(query: &DnsPacket, identity: &ServerIdentity) -> DnsPacket {
+    let mut response = DnsPacket::response_to(query);
+    let Some(question) = query.questions.first() else {
+        return response.with_rcode(ResponseCode::FormatError);
+    };
+
+    let name = normalize(&question.name);
+    let value = match name.as_str() {
+        "version.bind" => &identity.version,
+        "hostname.bind" => &identity.hostname,
+        "id.server" => &identity.id,
+        _ => &None,
+    };
+    let Some(value) = value else {
+        return response.with_rcode(ResponseCode::Refused);
+    };
+
+    response.header.authoritative_answer = true;
+    if matches!(question.qtype, RecordType::TXT | RecordType::ANY) {
+        let txt = DnsRecordType::txt(value.as_bytes());
+        response = response.with_answer(DnsRecord::new(name, Class::CH, 0, txt));
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::dns_query::Question;
+
+    fn ask(name: &str, qtype: RecordType, identity: &ServerIdentity) -> DnsPacket {
+        let mut query = DnsPacket::new_query(1, name, qtype);
+        query.questions = vec![Question::new(name, qtype, Class::CH)];
+        assert!(is_chaos(&query));
+
+        answer(&query, identity)
+    }
+
+    #[test]
+    fn answers_configured_names_and_refuses_the_rest() {
+        let identity = ServerIdentity {
+            version: None,
+            hostname: Some("ns1.example.com".to_string()),
+            id: Some("fra-1".to_string()),
+        };
+
+        let response = ask("HOSTNAME.bind.", RecordType::TXT, &identity);
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.answers[0].response_class(), Class::CH);
+        assert_eq!(response.answers[0].rdata(), &DnsRecordType::txt(b"ns1.example.com"));
+        assert_eq!(ask("id.server", RecordType::ANY, &identity).answers[0].rdata(), &DnsRecordType::txt(b"fra-1"));
+
+        assert!(ask("id.server", RecordType::A, &identity).answers.is_empty());
+        assert_eq!(ask("version.bind", RecordType::TXT, &identity).header.rcode, ResponseCode::Refused);
+        assert_eq!(ask("authors.bind", RecordType::TXT, &identity).header.rcode, ResponseCode::Refused);
+    }
+
+    #[test]
+    fn reports_the_version_by_default() {
+        let response = ask("version.bind", RecordType::TXT, &ServerIdentity::default());
+
+        assert_eq!(response.answers[0].rdata().to_string(), format!("\"tiny_dns {}\"", env!("CARGO_PKG_VERSION")));
+    }
+}
@@ -0,0 +1,34 @@
+//! Two byte length prefixed message framing used by DNS over TCP (RFC 1035 4.2.2).
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Reads one framed message, returning `None` when the peer closed the
+/// connection cleanly between messages.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 2];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut message = vec![0u8; u16::from_be_bytes(length) as usize];
+    reader.read_exact(&mut message).await?;
+
+    Ok(Some(message))
+}
+
+/// Writes `message` prefixed with its length in a single write.
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, message: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(message.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "DNS message longer than 65535 bytes"))?;
+
+    let mut frame = Vec::with_capacity(message.len() + 2);
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(message);
+
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
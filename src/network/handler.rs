@@ -0,0 +1,218 @@
+use log::{debug, error};
+
+use crate::errors::{ErrorChain, QueryError};
+use crate::protocol::dns_header::{DnsHeader, ResponseCode, OPCODE_QUERY};
+use crate::protocol::dns_packet::{DnsPacket, UDP_PAYLOAD_LIMIT};
+use crate::protocol::dns_query::Question;
+use crate::protocol::edns::{Edns, BADVERS, DEFAULT_UDP_PAYLOAD_SIZE};
+use crate::protocol::packet_buffer::{PacketBuffer, MAX_MESSAGE_SIZE};
+use crate::resolver::{Protocol, RequestContext, Resolver};
+
+use super::chaos;
+use super::options::{MultiQuestionPolicy, ServerOptions};
+
+/// Parses a raw request, resolves it and returns the serialized response.
+///
+/// Failures are answered with the matching RCODE, keeping the id and
+/// question of the request. An error is only returned when there is nothing
+/// sensible to answer, such as a request too short to hold a header.
+///
+/// Shared by the UDP and TCP listeners.
+pub(crate) async fn handle_query(
+    request: &[u8],
+    resolver: &dyn Resolver,
+    options: &ServerOptions,
+    ctx: &RequestContext,
+) -> Result<Vec<u8>, QueryError> {
+    let query = match DnsPacket::from_bytes_with_policy(request, options.trailing_bytes_policy) {
+        Ok(query) => query,
+        Err(e) => {
+            let error = QueryError::FailedToDeserializeQuery(e);
+            let Some(query) = salvage_query(request) else {
+                return Err(error);
+            };
+            debug!("Malformed query from {}: {}", ctx.client_address, ErrorChain(&error));
+
+            let response = DnsPacket::response_to(&query).with_rcode(error.response_code());
+            return response.to_bytes_with_limit(UDP_PAYLOAD_LIMIT).map_err(QueryError::FailedToSerializeResponse);
+        }
+    };
+
+    let limit = match ctx.protocol {
+        Protocol::Udp => udp_payload_limit(&query),
+        Protocol::Tcp => MAX_MESSAGE_SIZE,
+    };
+
+    let mut response = match answer(&query, resolver, options, ctx).await {
+        Ok(response) => response,
+        Err(e) => error_response(&query, &e, ctx),
+    };
+
+    // The OPT record is hop by hop, so whatever the resolver got from an
+    // upstream is replaced by our own, and only sent to EDNS aware clients.
+    response.edns = query.edns.as_ref().map(|client| Edns {
+        extended_rcode: response.edns.as_ref().map_or(0, |edns| edns.extended_rcode),
+        dnssec_ok: client.dnssec_ok,
+        ..Edns::default()
+    });
+
+    match response.to_bytes_with_limit(limit) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) => {
+            let error = QueryError::FailedToSerializeResponse(e);
+            let mut failure = error_response(&query, &error, ctx);
+            failure.edns = response.edns;
+            failure.to_bytes_with_limit(limit).map_err(|_| error)
+        }
+    }
+}
+
+/// Answers a well formed `query`, unless policy or its opcode rule it out.
+async fn answer(
+    query: &DnsPacket,
+    resolver: &dyn Resolver,
+    options: &ServerOptions,
+    ctx: &RequestContext,
+) -> Result<DnsPacket, QueryError> {
+    let client = ctx.client_address.ip();
+    if !options.allows(client) {
+        return Err(QueryError::Refused { client });
+    }
+
+    if query.header.opcode != OPCODE_QUERY {
+        return Err(QueryError::NotImplemented { opcode: query.header.opcode });
+    }
+
+    // RFC 6891 6.1.3: only version 0 is implemented.
+    if query.edns.as_ref().is_some_and(|edns| edns.version > 0) {
+        return Ok(DnsPacket::response_to(query).with_edns(Edns {
+            extended_rcode: BADVERS,
+            ..Edns::default()
+        }));
+    }
+
+    match chaos::is_chaos(query) {
+        true => Ok(chaos::answer(query, &options.identity)),
+        false => resolve(query, resolver, options.multi_question_policy, ctx).await,
+    }
+}
+
+/// An empty response to `query` carrying the RCODE for `error`.
+fn error_response(query: &DnsPacket, error: &QueryError, ctx: &RequestContext) -> DnsPacket {
+    let rcode = error.response_code();
+    match rcode {
+        ResponseCode::ServerFailure => {
+            error!("Failed to handle query from {}: {}", ctx.client_address, ErrorChain(error))
+        }
+        _ => debug!("Answering {:?} to {}: {}", rcode, ctx.client_address, ErrorChain(error)),
+    }
+
+    DnsPacket::response_to(query).with_rcode(rcode)
+}
+
+/// Recovers the header and question of a request that failed to parse, so
+/// the FORMERR answer can echo them.
+///
+/// Nothing is recovered without a complete header, nor from responses, which
+/// must never be answered or two servers could keep bouncing errors at each
+/// other.
+fn salvage_query(request: &[u8]) -> Option<DnsPacket> {
+    let mut buffer = PacketBuffer::from_bytes(request).ok()?;
+    let header = DnsHeader::deserialize(&mut buffer).ok()?;
+    if header.is_response {
+        return None;
+    }
+
+    let questions = match header.question_count {
+        1 => Question::deserialize(&mut buffer).ok().into_iter().collect(),
+        _ => Vec::new(),
+    };
+
+    Some(DnsPacket {
+        header,
+        questions,
+        answers: Vec::new(),
+        authority: Vec::new(),
+        additional: Vec::new(),
+        edns: None,
+    })
+}
+
+/// Resolves `query`, applying `policy` when it has several questions.
+async fn resolve(
+    query: &DnsPacket,
+    resolver: &dyn Resolver,
+    policy: MultiQuestionPolicy,
+    ctx: &RequestContext,
+) -> Result<DnsPacket, QueryError> {
+    if query.questions.len() <= 1 {
+        return resolver.resolve(query, ctx).await;
+    }
+
+    match policy {
+        MultiQuestionPolicy::FormatError => {
+            Ok(DnsPacket::response_to(query).with_rcode(ResponseCode::FormatError))
+        }
+        MultiQuestionPolicy::FirstOnly => {
+            let mut single = query.clone();
+            single.questions.truncate(1);
+            resolver.resolve(&single, ctx).await
+        }
+        MultiQuestionPolicy::EachQuestion => {
+            let mut merged = DnsPacket::response_to(query);
+            merged.header.authoritative_answer = true;
+            merged.header.authentic_data = true;
+
+            for question in &query.questions {
+                let mut single = query.clone();
+                single.questions = vec![question.clone()];
+                // The first error wins, AA and AD only hold if every answer had them.
+                let response = match resolver.resolve(&single, ctx).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        debug!("Failed to resolve {} {}: {}", question.name, question.qtype, ErrorChain(&e));
+                        if merged.header.rcode == ResponseCode::NoError {
+                            merged.header.rcode = e.response_code();
+                        }
+                        merged.header.authoritative_answer = false;
+                        merged.header.authentic_data = false;
+                        continue;
+                    }
+                };
+                if merged.header.rcode == ResponseCode::NoError {
+                    merged.header.rcode = response.header.rcode;
+                }
+                merged.header.authoritative_answer &= response.header.authoritative_answer;
+                merged.header.authentic_data &= response.header.authentic_data;
+                merged.header.recursion_available |= response.header.recursion_available;
+
+                for record in response.answers {
+                    if !merged.answers.contains(&record) {
+                        merged = merged.with_answer(record);
+                    }
+                }
+                for record in response.authority {
+                    if !merged.authority.contains(&record) {
+                        merged = merged.with_authority(record);
+                    }
+                }
+                for record in response.additional {
+                    if !merged.additional.contains(&record) {
+                        merged = merged.with_additional(record);
+                    }
+                }
+            }
+
+            Ok(merged)
+        }
+    }
+}
+
+/// Largest UDP response the client accepts, capped to what we advertise.
+fn udp_payload_limit(query: &DnsPacket) -> usize {
+    match &query.edns {
+        Some(edns) => (edns.udp_payload_size as usize)
+            .clamp(UDP_PAYLOAD_LIMIT, DEFAULT_UDP_PAYLOAD_SIZE as usize),
+        None => UDP_PAYLOAD_LIMIT,
+    }
+}
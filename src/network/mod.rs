@@ -1,2 +1,8 @@
 pub mod udp_server;
-pub mod peer;
\ No newline at end of file
+pub mod tcp_server;
+pub mod peer;
+pub mod framing;
+pub mod options;
+pub mod shutdown;
+mod handler;
+mod chaos;
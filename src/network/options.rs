@@ -0,0 +1,127 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::protocol::dns_packet::TrailingBytesPolicy;
+
+/// What the server does with requests carrying more than one question.
+///
+/// RFC 1035 allows several questions per message, but no server answers them
+/// in a useful way, so the default is to reject them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiQuestionPolicy {
+    /// Answer with FORMERR.
+    #[default]
+    FormatError,
+    /// Answer the first question only, as if the others weren't there.
+    FirstOnly,
+    /// Resolve every question on its own and merge the results in one response.
+    EachQuestion,
+}
+
+/// Answers to the CHAOS class TXT queries used to identify a server
+/// (RFC 4892). Queries for names left as `None` are refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerIdentity {
+    /// Answer to `version.bind`, the name and version of tiny_dns by default.
+    pub version: Option<String>,
+    /// Answer to `hostname.bind`.
+    pub hostname: Option<String>,
+    /// Answer to `id.server`.
+    pub id: Option<String>,
+}
+
+impl Default for ServerIdentity {
+    fn default() -> Self {
+        ServerIdentity {
+            version: Some(format!("tiny_dns {}", env!("CARGO_PKG_VERSION"))),
+            hostname: None,
+            id: None,
+        }
+    }
+}
+
+/// A block of addresses, such as `192.0.2.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    pub address: IpAddr,
+    pub prefix_length: u8,
+}
+
+impl IpNetwork {
+    pub fn new(address: IpAddr, prefix_length: u8) -> Self {
+        IpNetwork { address, prefix_length }
+    }
+
+    /// Whether `address` is in this network. IPv4 addresses mapped to IPv6,
+    /// as seen on dual stack sockets, are treated as IPv4.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let (network, address, bits) = match (self.address.to_canonical(), address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => (u32::from(network) as u128, u32::from(address) as u128, 32),
+            (IpAddr::V6(network), IpAddr::V6(address)) => (u128::from(network), u128::from(address), 128),
+            _ => return false,
+        };
+
+        let prefix_length = (self.prefix_length as u32).min(bits);
+        prefix_length == 0 || (network ^ address) >> (bits - prefix_length) == 0
+    }
+}
+
+/// How long a shutdown waits for requests in flight when no timeout is configured.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings shared by the UDP and TCP listeners of a server.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub multi_question_policy: MultiQuestionPolicy,
+    /// Answers to CHAOS class queries, which never reach the resolver.
+    pub identity: ServerIdentity,
+    /// Whether requests with bytes after their last section are rejected.
+    pub trailing_bytes_policy: TrailingBytesPolicy,
+    /// Clients allowed to query the server, the others are refused. Everyone
+    /// is allowed when empty.
+    pub allowed_clients: Vec<IpNetwork>,
+    /// How long a shutdown waits for requests in flight before dropping them.
+    pub shutdown_timeout: Duration,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            multi_question_policy: MultiQuestionPolicy::default(),
+            identity: ServerIdentity::default(),
+            trailing_bytes_policy: TrailingBytesPolicy::default(),
+            allowed_clients: Vec::new(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Whether a client at `address` may query the server.
+    pub fn allows(&self, address: IpAddr) -> bool {
+        self.allowed_clients.is_empty() || self.allowed_clients.iter().any(|network| network.contains(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn networks_match_addresses_by_prefix() {
+        let v4 = IpNetwork::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24);
+        assert!(v4.contains(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200))));
+        assert!(!v4.contains(IpAddr::V4(Ipv4Addr::new(192, 0, 3, 1))));
+        assert!(v4.contains(IpAddr::V6(Ipv4Addr::new(192, 0, 2, 7).to_ipv6_mapped())));
+
+        let v6 = IpNetwork::new(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)), 32);
+        assert!(v6.contains(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1))));
+        assert!(!v6.contains(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb9, 0, 0, 0, 0, 0, 1))));
+        assert!(!v6.contains(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+
+        let everything = IpNetwork::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+        assert!(everything.contains(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    }
+}
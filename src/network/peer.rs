@@ -1,60 +1,304 @@
-use std::net::{IpAddr, UdpSocket};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
-use crate::errors::LookupError;
 
+use log::debug;
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::errors::{DeserializeError, LookupError};
 use crate::protocol::dns_packet::DnsPacket;
-use crate::protocol::packet_buffer;
+use crate::protocol::packet_buffer::MAX_MESSAGE_SIZE;
+
+use super::framing::{read_frame, write_frame};
+
+/// How long to wait for an upstream answer when no timeout is configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Sends `query` to the upstream server at `ip:port` and waits at most
+/// `timeout` for the matching answer, one with the same id and question.
+///
+/// Truncated UDP answers are retried over TCP within the same timeout. UDP
+/// datagrams that can't be parsed are skipped; if no answer follows, the
+/// lookup fails with the last of them rather than a timeout. The lookup
+/// never blocks the runtime, and dropping the returned future cancels it and
+/// releases the socket.
+pub async fn nslookup(
+    ip: IpAddr,
+    port: u16,
+    query: &DnsPacket,
+    timeout: Duration,
+) -> Result<DnsPacket, LookupError> {
+    let server = SocketAddr::new(ip, port);
+    let mut unparsed = None;
+    let lookup = async {
+        let response = udp_lookup(server, query, &mut unparsed).await?;
+        if !response.header.truncated_message {
+            return Ok(response);
+        }
 
-pub fn nslookup(ip: IpAddr, port: u16, query: &DnsPacket) -> Result<DnsPacket, LookupError> {
-    let socket = UdpSocket::bind(format!("0.0.0.0:{}", 0));
+        tcp_lookup(server, query).await
+    };
 
-    if let Err(e) = socket {
-        return Err(LookupError::FailedToBindSocket(e));
+    match tokio::time::timeout(timeout, lookup).await {
+        Ok(result) => result,
+        Err(_) => Err(match unparsed {
+            Some(source) => LookupError::InvalidResponse { server, source },
+            None => LookupError::Timeout { server, timeout },
+        }),
     }
+}
+
+/// Sends `query` over TCP only, without trying UDP first.
+pub async fn nslookup_tcp(
+    ip: IpAddr,
+    port: u16,
+    query: &DnsPacket,
+    timeout: Duration,
+) -> Result<DnsPacket, LookupError> {
+    let server = SocketAddr::new(ip, port);
+    tokio::time::timeout(timeout, tcp_lookup(server, query))
+        .await
+        .map_err(|_| LookupError::Timeout { server, timeout })?
+}
+
+/// Waits for the answer to `query`, keeping the error of the last datagram
+/// that couldn't be parsed in `unparsed`.
+async fn udp_lookup(
+    server: SocketAddr,
+    query: &DnsPacket,
+    unparsed: &mut Option<DeserializeError>,
+) -> Result<DnsPacket, LookupError> {
+    let local_address = match server.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let socket = UdpSocket::bind(SocketAddr::new(local_address, 0))
+        .await
+        .map_err(|source| LookupError::FailedToBindSocket { server, source })?;
+
+    socket
+        .connect(server)
+        .await
+        .map_err(|source| LookupError::FailedToConnectSocket { server, source })?;
+
+    let input = query.to_bytes().map_err(|source| LookupError::FailedToSerializeQuery { server, source })?;
+    socket
+        .send(&input)
+        .await
+        .map_err(|source| LookupError::FailedToSendQuery { server, source })?;
+
+    let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+    loop {
+        let len = socket
+            .recv(&mut buffer)
+            .await
+            .map_err(|source| LookupError::FailedToReceiveResponse { server, source })?;
 
-    let socket = socket.unwrap();
+        // Anyone can send to our port, so junk doesn't end the lookup.
+        let response = match DnsPacket::from_bytes(&buffer[..len]) {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("Ignoring a response from {} that could not be parsed", server);
+                *unparsed = Some(e);
+                continue;
+            }
+        };
 
-    match socket.connect(format!("{}:{}", ip, port)) {
-        Err(_) => {
-            return Err(LookupError::FailedToConnectSocket {
-                ip,
-                port,
-            });
+        // Late answers to an earlier query on a reused port are not ours.
+        if answers(&response, query) {
+            return Ok(response);
         }
-        Ok(_) => (),
     }
+}
+
+async fn tcp_lookup(server: SocketAddr, query: &DnsPacket) -> Result<DnsPacket, LookupError> {
+    let mut stream = TcpStream::connect(server)
+        .await
+        .map_err(|source| LookupError::FailedToConnectSocket { server, source })?;
+
+    let input = query.to_bytes().map_err(|source| LookupError::FailedToSerializeQuery { server, source })?;
+    write_frame(&mut stream, &input)
+        .await
+        .map_err(|source| LookupError::FailedToSendQuery { server, source })?;
 
+    loop {
+        let message = read_frame(&mut stream)
+            .await
+            .and_then(|message| message.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof)))
+            .map_err(|source| LookupError::FailedToReceiveResponse { server, source })?;
 
-    match socket.set_read_timeout(Some(Duration::from_secs(5))) {
-        Err(_) => {
-            return Err(LookupError::FailedToSetReadTimeout);
+        let response = DnsPacket::from_bytes(&message)
+            .map_err(|source| LookupError::InvalidResponse { server, source })?;
+
+        if answers(&response, query) {
+            return Ok(response);
         }
-        Ok(_) => ()
     }
+}
 
-    let response: DnsPacket;
-    let input = query.serialize().unwrap();
-    match socket.send(&input.buffer) {
-        Err(_) => {
-            return Err(LookupError::FailedToSendQuery);
-        }
-        Ok(_) => (),
+/// Whether `response` has the id and question of `query`.
+fn answers(response: &DnsPacket, query: &DnsPacket) -> bool {
+    response.header.id == query.header.id
+        && response.questions.len() == query.questions.len()
+        && response.questions.iter().zip(&query.questions).all(|(asked, answered)| {
+            asked.name.eq_ignore_ascii_case(&answered.name) && asked.qtype == answered.qtype && asked.qclass == answered.qclass
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::errors::ErrorChain;
+    use crate::protocol::dns_record::DnsRecord;
+    use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+
+    async fn silent_upstream() -> UdpSocket {
+        UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap()
     }
 
-    let mut buffer = [0u8; 512];
-    match socket.recv_from(&mut buffer) {
-        Err(e) => {
-            return Err(LookupError::FailedToReceiveResponse(e));
-        }
-        Ok(_) => ()
+    async fn answering_upstream() -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+                let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+                let response = DnsPacket::response_to(&query).to_bytes().unwrap();
+                socket.send_to(&response, src).await.unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn silent_upstream_times_out() {
+        let upstream = silent_upstream().await;
+        let address = upstream.local_addr().unwrap();
+        let query = DnsPacket::new_query(1, "example.com", RecordType::A);
+
+        let result = nslookup(address.ip(), address.port(), &query, Duration::from_millis(50)).await;
+
+        assert!(matches!(result, Err(LookupError::Timeout { server, .. }) if server == address));
     }
 
-    let mut packet_buffer = packet_buffer::PacketBuffer::new(buffer);
+    #[tokio::test]
+    async fn malformed_answer_reports_the_server_and_the_cause() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (_, src) = socket.recv_from(&mut buf).await.unwrap();
+            // The right id, then a header cut short.
+            socket.send_to(&[0, 3, 0x81, 0x80, 0, 1], src).await.unwrap();
+        });
+        let query = DnsPacket::new_query(3, "example.com", RecordType::A);
 
-    response = DnsPacket::deserialize(&mut packet_buffer).unwrap();
+        let error = nslookup(address.ip(), address.port(), &query, Duration::from_millis(200)).await.unwrap_err();
 
+        assert_eq!(error.server(), address);
+        assert_eq!(
+            ErrorChain(&error).to_string(),
+            format!(
+                "Received a response from {} that could not be parsed: Failed to deserialize header: Message truncated at byte 6",
+                address
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_upstream_does_not_block_other_lookups() {
+        let silent = silent_upstream().await;
+        let silent_address = silent.local_addr().unwrap();
+        let answering_address = answering_upstream().await;
+
+        let slow = tokio::spawn(async move {
+            let query = DnsPacket::new_query(1, "slow.test", RecordType::A);
+            nslookup(silent_address.ip(), silent_address.port(), &query, Duration::from_secs(2)).await
+        });
 
-    drop(socket);
-    return Ok(response);
+        let started = Instant::now();
+        let query = DnsPacket::new_query(2, "fast.test", RecordType::A);
+        let response = nslookup(answering_address.ip(), answering_address.port(), &query, DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+
+        assert_eq!(response.header.id, 2);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        slow.abort();
+    }
+
+    #[tokio::test]
+    async fn truncated_answer_is_retried_over_tcp() {
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = udp.local_addr().unwrap();
+        let tcp = TcpListener::bind(address).await.unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, src) = udp.recv_from(&mut buf).await.unwrap();
+            let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+            let mut truncated = DnsPacket::response_to(&query);
+            truncated.header.truncated_message = true;
+            udp.send_to(&truncated.to_bytes().unwrap(), src).await.unwrap();
+        });
+
+        tokio::spawn(async move {
+            let (mut stream, _) = tcp.accept().await.unwrap();
+            let request = read_frame(&mut stream).await.unwrap().unwrap();
+            let query = DnsPacket::from_bytes(&request).unwrap();
+            let response = DnsPacket::response_to(&query).with_answer(DnsRecord::new(
+                "big.test".to_string(),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) },
+            ));
+            write_frame(&mut stream, &response.to_bytes().unwrap()).await.unwrap();
+        });
+
+        let query = DnsPacket::new_query(9, "big.test", RecordType::A);
+        let response = nslookup(address.ip(), address.port(), &query, DEFAULT_TIMEOUT)
+            .await
+            .unwrap();
+
+        assert!(!response.header.truncated_message);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_junk_and_answers_to_other_questions() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+            let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+
+            let mut forged = DnsPacket::response_to(&query);
+            forged.questions[0].qtype = RecordType::AAAA;
+            socket.send_to(&[0, 4, 0x81], src).await.unwrap();
+            socket.send_to(&forged.to_bytes().unwrap(), src).await.unwrap();
+            let response = DnsPacket::response_to(&query).with_answer(DnsRecord::new(
+                "example.com".to_string(),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) },
+            ));
+            socket.send_to(&response.to_bytes().unwrap(), src).await.unwrap();
+        });
+        let query = DnsPacket::new_query(4, "Example.COM", RecordType::A);
+
+        let response = nslookup(address.ip(), address.port(), &query, DEFAULT_TIMEOUT).await.unwrap();
+
+        assert_eq!(response.questions[0].qtype, RecordType::A);
+        assert_eq!(response.answers.len(), 1);
+    }
 }
@@ -0,0 +1,143 @@
+//! Stopping a running server: the handle returned when it starts, and what
+//! its listeners use to wind down.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// Request counters of a running server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ServerStats {
+    /// Requests received over UDP and TCP.
+    pub queries: u64,
+    /// Responses sent back, error responses included.
+    pub responses: u64,
+    /// Requests still being answered. Once the server is shut down, those
+    /// cut off by the shutdown timeout.
+    pub in_flight: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    queries: AtomicU64,
+    responses: AtomicU64,
+    in_flight: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn received(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called once a request is done with, unless its task was aborted.
+    pub(crate) fn finished(&self, answered: bool) {
+        if answered {
+            self.responses.fetch_add(1, Ordering::Relaxed);
+        }
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ServerStats {
+        ServerStats {
+            queries: self.queries.load(Ordering::Relaxed),
+            responses: self.responses.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The listeners' side of a [`ServerHandle`].
+#[derive(Debug, Clone)]
+pub(crate) struct Shutdown {
+    receiver: watch::Receiver<bool>,
+    counters: Arc<Counters>,
+}
+
+impl Shutdown {
+    /// Resolves once shutdown is requested. Dropping the handle instead
+    /// leaves the server running.
+    pub(crate) async fn requested(&mut self) {
+        if self.receiver.wait_for(|stop| *stop).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    pub(crate) fn counters(&self) -> &Arc<Counters> {
+        &self.counters
+    }
+}
+
+/// Controls a server started with [`crate::network::udp_server::Server::start`].
+///
+/// Dropping the handle leaves the server running until the runtime stops.
+#[derive(Debug)]
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    stop: watch::Sender<bool>,
+    listeners: Vec<JoinHandle<()>>,
+    counters: Arc<Counters>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(local_addr: SocketAddr) -> (ServerHandle, Shutdown) {
+        let (stop, receiver) = watch::channel(false);
+        let counters = Arc::new(Counters::default());
+        let handle = ServerHandle {
+            local_addr,
+            stop,
+            listeners: Vec::new(),
+            counters: counters.clone(),
+        };
+
+        (handle, Shutdown { receiver, counters })
+    }
+
+    /// Runs `listener` until it is done draining after a shutdown.
+    pub(crate) fn spawn(&mut self, listener: impl Future<Output = ()> + Send + 'static) {
+        self.listeners.push(tokio::spawn(listener));
+    }
+
+    /// The address the server is listening on, useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stats(&self) -> ServerStats {
+        self.counters.snapshot()
+    }
+
+    /// Stops accepting requests, waits for those in flight for at most the
+    /// configured shutdown timeout and returns the final counters.
+    ///
+    /// The sockets are closed once this returns, so the address can be bound
+    /// again right away.
+    pub async fn shutdown(self) -> ServerStats {
+        let _ = self.stop.send(true);
+        for listener in self.listeners {
+            let _ = listener.await;
+        }
+
+        let stats = self.counters.snapshot();
+        if stats.in_flight > 0 {
+            warn!("Gave up on {} requests still in flight", stats.in_flight);
+        }
+        info!("Server stopped after {} queries and {} responses", stats.queries, stats.responses);
+
+        stats
+    }
+}
+
+/// Waits for `tasks` to finish for at most `timeout`, then aborts the rest.
+pub(crate) async fn drain<T: 'static>(tasks: &mut JoinSet<T>, timeout: Duration) {
+    let finished = tokio::time::timeout(timeout, async { while tasks.join_next().await.is_some() {} }).await;
+    if finished.is_err() {
+        tasks.shutdown().await;
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use log::{debug, error, info};
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::errors::{ErrorChain, ServerError};
+use crate::resolver::{Protocol, RequestContext, Resolver};
+
+use super::framing::{read_frame, write_frame};
+use super::handler::handle_query;
+use super::options::ServerOptions;
+use super::shutdown::{drain, ServerHandle, Shutdown};
+
+/// How long an idle connection is kept open waiting for the next query (RFC 7766 6.2.3).
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// DNS over TCP listener.
+///
+/// Every connection may carry several pipelined queries, which are resolved
+/// concurrently and answered in whatever order they complete.
+pub struct TcpServer {
+    listener: TcpListener,
+    local_addr: SocketAddr,
+    resolver: Arc<dyn Resolver>,
+    options: Arc<ServerOptions>,
+}
+
+impl TcpServer {
+    pub async fn new(
+        bind_address: IpAddr,
+        port: u16,
+        resolver: Arc<dyn Resolver>,
+    ) -> Result<TcpServer, ServerError> {
+        TcpServer::with_options(bind_address, port, resolver, ServerOptions::default()).await
+    }
+
+    pub async fn with_options(
+        bind_address: IpAddr,
+        port: u16,
+        resolver: Arc<dyn Resolver>,
+        options: ServerOptions,
+    ) -> Result<TcpServer, ServerError> {
+        let address = SocketAddr::new(bind_address, port);
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|source| ServerError::FailedToBindSocket { address, source })?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|source| ServerError::FailedToBindSocket { address, source })?;
+
+        Ok(TcpServer {
+            listener,
+            local_addr,
+            resolver,
+            options: Arc::new(options),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Starts accepting connections in the background.
+    pub fn start(self) -> ServerHandle {
+        let (mut handle, shutdown) = ServerHandle::new(self.local_addr);
+        handle.spawn(self.serve(shutdown));
+
+        handle
+    }
+
+    /// Accepts connections until shutdown is requested, then waits for the
+    /// open ones to finish their queries.
+    pub(crate) async fn serve(self, mut shutdown: Shutdown) {
+        info!("TCP listener started");
+        let mut connections = JoinSet::new();
+
+        loop {
+            let accepted = tokio::select! {
+                _ = shutdown.requested() => break,
+                accepted = self.listener.accept() => accepted,
+            };
+            while connections.try_join_next().is_some() {}
+
+            match accepted {
+                Ok((stream, src)) => {
+                    let resolver = self.resolver.clone();
+                    let options = self.options.clone();
+                    let shutdown = shutdown.clone();
+                    connections.spawn(async move {
+                        if let Err(e) = serve_connection(stream, src, resolver, options, shutdown).await {
+                            debug!("TCP connection from {} closed: {}", src, e);
+                        }
+                    });
+                }
+                Err(e) => error!("Failed to accept TCP connection: {}", e),
+            }
+        }
+
+        drop(self.listener);
+        drain(&mut connections, self.options.shutdown_timeout).await;
+    }
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    src: SocketAddr,
+    resolver: Arc<dyn Resolver>,
+    options: Arc<ServerOptions>,
+    mut shutdown: Shutdown,
+) -> io::Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    let (responses, mut pending) = mpsc::channel::<Vec<u8>>(16);
+
+    // The writer and every query run in this set, so they are aborted along
+    // with the connection when a shutdown times out.
+    let mut tasks = JoinSet::new();
+    tasks.spawn(async move {
+        while let Some(response) = pending.recv().await {
+            write_frame(&mut writer, &response).await?;
+        }
+        Ok::<(), io::Error>(())
+    });
+
+    let mut result = Ok(());
+    loop {
+        let read = tokio::select! {
+            _ = shutdown.requested() => break,
+            read = tokio::time::timeout(IDLE_TIMEOUT, read_frame(&mut reader)) => read,
+        };
+
+        let request = match read {
+            Ok(Ok(Some(request))) => request,
+            Ok(Ok(None)) | Err(_) => break,
+            Ok(Err(e)) => {
+                result = Err(e);
+                break;
+            }
+        };
+
+        // Only the writer can fail, and then there is no point reading on.
+        if let Err(e) = reap(&mut tasks) {
+            result = Err(e);
+            break;
+        }
+
+        let resolver = resolver.clone();
+        let options = options.clone();
+        let responses = responses.clone();
+        let counters = shutdown.counters().clone();
+        counters.received();
+        tasks.spawn(async move {
+            let ctx = RequestContext::new(src, Protocol::Tcp);
+            let answered = match handle_query(&request, resolver.as_ref(), &options, &ctx).await {
+                Ok(response) => responses.send(response).await.is_ok(),
+                Err(e) => {
+                    debug!("Dropped request from {}: {}", ctx.client_address, ErrorChain(&e));
+                    false
+                }
+            };
+            counters.finished(answered);
+            Ok(())
+        });
+    }
+
+    // Let in-flight queries finish before the write half is closed.
+    drop(responses);
+    while let Some(finished) = tasks.join_next().await {
+        if let Ok(Err(e)) = finished {
+            result = Err(e);
+        }
+    }
+
+    result
+}
+
+/// Collects the tasks that already finished, failing if the writer did.
+fn reap(tasks: &mut JoinSet<io::Result<()>>) -> io::Result<()> {
+    while let Some(finished) = tasks.try_join_next() {
+        if let Ok(Err(e)) = finished {
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::errors::QueryError;
+    use crate::protocol::dns_packet::DnsPacket;
+    use crate::protocol::dns_record::DnsRecord;
+    use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+
+    struct EchoIdResolver;
+
+    #[async_trait]
+    impl Resolver for EchoIdResolver {
+        async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            assert_eq!(ctx.protocol, Protocol::Tcp);
+
+            Ok(DnsPacket::response_to(request).with_answer(DnsRecord::new(
+                request.questions[0].name.clone(),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, request.header.id as u8) },
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_pipelined_queries_on_one_connection() {
+        let server = TcpServer::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, Arc::new(EchoIdResolver))
+            .await
+            .unwrap();
+        let address = server.local_addr();
+        server.start();
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        for id in 1..=3 {
+            let query = DnsPacket::new_query(id, "pipelined.test", RecordType::A);
+            write_frame(&mut stream, &query.to_bytes().unwrap()).await.unwrap();
+        }
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let response = read_frame(&mut stream).await.unwrap().unwrap();
+            let response = DnsPacket::from_bytes(&response).unwrap();
+            assert_eq!(
+                response.answers[0].rdata(),
+                &DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, response.header.id as u8) }
+            );
+            ids.push(response.header.id);
+        }
+
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    /// Answers after a pause, long enough for a shutdown to start meanwhile.
+    struct SlowResolver;
+
+    #[async_trait]
+    impl Resolver for SlowResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(DnsPacket::response_to(request))
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_answers_pending_queries_then_closes_connections() {
+        let server = TcpServer::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, Arc::new(SlowResolver))
+            .await
+            .unwrap()
+            .start();
+
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let query = DnsPacket::new_query(5, "slow.test", RecordType::A);
+        write_frame(&mut stream, &query.to_bytes().unwrap()).await.unwrap();
+        while server.stats().in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let stats = server.shutdown().await;
+
+        assert_eq!((stats.queries, stats.responses, stats.in_flight), (1, 1, 0));
+        let response = read_frame(&mut stream).await.unwrap().unwrap();
+        assert_eq!(DnsPacket::from_bytes(&response).unwrap().header.id, 5);
+        assert!(read_frame(&mut stream).await.unwrap().is_none());
+    }
+}
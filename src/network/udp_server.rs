@@ -1,94 +1,428 @@
-use std::{net::IpAddr, sync::Arc};
-use log::{error, info};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use log::{debug, error, info};
 
 use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
 
-use crate::{
-    protocol::{dns_packet::DnsPacket, packet_buffer::PacketBuffer},
-    resolver::ResolverType,
-};
-use crate::errors::{QueryError, ServerError};
+use crate::protocol::packet_buffer::MAX_MESSAGE_SIZE;
+use crate::resolver::{Protocol, RequestContext, Resolver};
+use crate::errors::{ErrorChain, ServerError};
 
+use super::handler::handle_query;
+use super::options::ServerOptions;
+use super::shutdown::{drain, ServerHandle, Shutdown};
+use super::tcp_server::TcpServer;
+
+/// DNS server answering on both UDP and TCP on the same address and port.
 pub struct Server {
     socket: Arc<UdpSocket>,
-    resolver: Arc<ResolverType>,
+    local_addr: SocketAddr,
+    tcp: TcpServer,
+    resolver: Arc<dyn Resolver>,
+    options: Arc<ServerOptions>,
 }
 
 impl Server {
     pub async fn new(
         bind_address: IpAddr,
         port: u16,
-        resolver: ResolverType,
+        resolver: Arc<dyn Resolver>,
     ) -> Result<Server, ServerError> {
-        let socket = UdpSocket::bind((bind_address, port)).await;
+        Server::with_options(bind_address, port, resolver, ServerOptions::default()).await
+    }
 
-        if let Err(_) = socket {
-            return Err(ServerError::FailedToBindSocket);
-        }
-        let socket = socket.unwrap();
+    pub async fn with_options(
+        bind_address: IpAddr,
+        port: u16,
+        resolver: Arc<dyn Resolver>,
+        options: ServerOptions,
+    ) -> Result<Server, ServerError> {
+        let address = SocketAddr::new(bind_address, port);
+        let socket = UdpSocket::bind(address)
+            .await
+            .map_err(|source| ServerError::FailedToBindSocket { address, source })?;
+
+        // Use the port UDP actually got so binding to port 0 works for both.
+        let local_addr = socket
+            .local_addr()
+            .map_err(|source| ServerError::FailedToBindSocket { address, source })?;
+        let tcp = TcpServer::with_options(bind_address, local_addr.port(), resolver.clone(), options.clone()).await?;
 
-        return Ok(Server {
+        Ok(Server {
             socket: Arc::new(socket),
-            resolver: Arc::new(resolver),
-        });
+            local_addr,
+            tcp,
+            resolver,
+            options: Arc::new(options),
+        })
     }
 
-    pub async fn start(&self) -> Result<(), ServerError> {
-        let mut buf = [0u8; 512];
+    /// The address the server is listening on, useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Starts answering on UDP and TCP in the background.
+    ///
+    /// The returned handle stops the server again. Dropping it leaves the
+    /// server running.
+    pub fn start(self) -> ServerHandle {
+        let (mut handle, shutdown) = ServerHandle::new(self.local_addr);
+        handle.spawn(self.tcp.serve(shutdown.clone()));
+        handle.spawn(serve_udp(self.socket, self.resolver, self.options, shutdown));
+        info!("Server started on {}", self.local_addr);
 
-        info!("Server started");
-        loop {
-            let socket = self.socket.clone();
+        handle
+    }
+}
 
-            if let Ok((_, _src)) = socket.clone().recv_from(&mut buf).await {
-                let resolver = self.resolver.clone();
+/// Answers UDP requests until shutdown is requested, then waits for those
+/// still being resolved.
+async fn serve_udp(
+    socket: Arc<UdpSocket>,
+    resolver: Arc<dyn Resolver>,
+    options: Arc<ServerOptions>,
+    mut shutdown: Shutdown,
+) {
+    let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+    let mut in_flight = JoinSet::new();
 
-                tokio::task::spawn(async move {
-                    let response = match handle_query(buf, resolver.as_ref()) {
-                        Ok(response) => response,
-                        Err(e) => {
-                            error!("Failed to handle query: {}", e);
-                            return;
-                        }
-                    };
+    loop {
+        let received = tokio::select! {
+            _ = shutdown.requested() => break,
+            received = socket.recv_from(&mut buf) => received,
+        };
+        while in_flight.try_join_next().is_some() {}
 
-                    let send_result = socket
-                        .send_to(&response, &_src)
-                        .await;
+        if let Ok((len, src)) = received {
+            let socket = socket.clone();
+            let resolver = resolver.clone();
+            let options = options.clone();
+            let counters = shutdown.counters().clone();
+            let request = buf[..len].to_vec();
+            counters.received();
 
-                    if let Err(e) = send_result {
-                        error!("Failed to send response: {}", e);
+            in_flight.spawn(async move {
+                let ctx = RequestContext::new(src, Protocol::Udp);
+                let response = match handle_query(&request, resolver.as_ref(), &options, &ctx).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        debug!("Dropped request from {}: {}", ctx.client_address, ErrorChain(&e));
+                        counters.finished(false);
+                        return;
                     }
-                });
-            }
+                };
+
+                let send_result = socket
+                    .send_to(&response, &src)
+                    .await;
+
+                if let Err(e) = &send_result {
+                    error!("Failed to send response: {}", e);
+                }
+                counters.finished(send_result.is_ok());
+            });
         }
     }
+
+    drain(&mut in_flight, options.shutdown_timeout).await;
 }
 
-fn handle_query<'a>(buf: [u8; 512], resolver: &ResolverType) -> Result<Vec<u8>, QueryError> {
-    let mut packet_buffer = PacketBuffer::new(buf);
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::errors::QueryError;
+    use crate::protocol::dns_packet::DnsPacket;
+    use crate::protocol::dns_record::DnsRecord;
+    use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+    use crate::protocol::dns_header::ResponseCode;
+    use crate::protocol::dns_query::Question;
+    use crate::protocol::edns::Edns;
+    use crate::network::options::{IpNetwork, MultiQuestionPolicy};
+    use crate::network::shutdown::ServerStats;
+
+    struct StaticResolver {
+        answers: u8,
+    }
+
+    #[async_trait]
+    impl Resolver for StaticResolver {
+        async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            assert_eq!(ctx.protocol, Protocol::Udp);
+
+            let mut response = DnsPacket::response_to(request);
+            for i in 0..self.answers {
+                response = response.with_answer(DnsRecord::new(
+                    request.questions[0].name.clone(),
+                    Class::IN,
+                    60,
+                    DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, i + 1) },
+                ));
+            }
+            Ok(response)
+        }
+    }
+
+    async fn start_server(answers: u8) -> SocketAddr {
+        start_server_with_options(answers, ServerOptions::default()).await
+    }
+
+    async fn start_server_with_options(answers: u8, options: ServerOptions) -> SocketAddr {
+        start_server_with_resolver(Arc::new(StaticResolver { answers }), options).await
+    }
+
+    async fn start_server_with_resolver(resolver: Arc<dyn Resolver>, options: ServerOptions) -> SocketAddr {
+        let server = Server::with_options(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, resolver, options)
+            .await
+            .unwrap();
+        let address = server.local_addr();
+        server.start();
+
+        address
+    }
+
+    async fn exchange(server_address: SocketAddr, query: &DnsPacket) -> (usize, DnsPacket) {
+        exchange_bytes(server_address, &query.to_bytes().unwrap()).await
+    }
+
+    async fn exchange_bytes(server_address: SocketAddr, request: &[u8]) -> (usize, DnsPacket) {
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        client.send_to(request, server_address).await.unwrap();
+
+        let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+        let (len, _) = client.recv_from(&mut buf).await.unwrap();
 
-    let query = DnsPacket::deserialize(&mut packet_buffer);
-    if let Err(e) = query {
-        error!("{}", e);
-        return Err(QueryError::FailedToDeserializeResponse);
+        (len, DnsPacket::from_bytes(&buf[..len]).unwrap())
     }
-    let query = query.unwrap();
 
-    let response = resolver.resolve(query);
-    if let Err(e) = response {
-        error!("{}", e);
-        return Err(QueryError::FailetToResolveQuery);
+    #[tokio::test]
+    async fn serves_answers_from_custom_resolver() {
+        let server_address = start_server(1).await;
+
+        let (_, response) = exchange(server_address, &DnsPacket::new_query(7, "custom.test", RecordType::A)).await;
+
+        assert_eq!(response.header.id, 7);
+        assert_eq!(
+            response.answers[0].rdata(),
+            &DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, 1) }
+        );
+        assert!(response.edns.is_none());
     }
-    let response = response.unwrap();
-    
-    let packet_buffer = response.serialize();
-    
-    if let Err(e) = packet_buffer {
-        error!("{}", e);
-        return Err(QueryError::FailedToSerializeResponse);
+
+    #[tokio::test]
+    async fn honours_client_udp_payload_size() {
+        let server_address = start_server(40).await;
+
+        let plain = DnsPacket::new_query(1, "big.test", RecordType::A);
+        let (len, response) = exchange(server_address, &plain).await;
+        assert!(len <= 512);
+        assert!(response.header.truncated_message);
+
+        let with_edns = DnsPacket::new_query(2, "big.test", RecordType::A).with_edns(Edns::new(4096));
+        let (len, response) = exchange(server_address, &with_edns).await;
+        assert!(len > 512);
+        assert!(!response.header.truncated_message);
+        assert_eq!(response.answers.len(), 40);
+        assert_eq!(response.edns.unwrap().udp_payload_size, 1232);
     }
-    let packet_buffer = packet_buffer.unwrap();
 
-    return Ok(packet_buffer.buffer[..packet_buffer.pos].to_vec());
+    #[tokio::test]
+    async fn answers_unknown_edns_version_with_badvers() {
+        let server_address = start_server(1).await;
+
+        let query = DnsPacket::new_query(3, "custom.test", RecordType::A).with_edns(Edns {
+            version: 1,
+            ..Edns::default()
+        });
+        let (_, response) = exchange(server_address, &query).await;
+
+        assert!(response.answers.is_empty());
+        assert_eq!(response.edns.unwrap().extended_rcode, 1);
+    }
+
+    fn two_questions(id: u16) -> DnsPacket {
+        let mut query = DnsPacket::new_query(id, "first.test", RecordType::A);
+        query.questions.push(Question::new("second.test", RecordType::A, Class::IN));
+        query
+    }
+
+    async fn exchange_with_policy(policy: MultiQuestionPolicy) -> DnsPacket {
+        let options = ServerOptions { multi_question_policy: policy, ..ServerOptions::default() };
+        let server_address = start_server_with_options(1, options).await;
+
+        exchange(server_address, &two_questions(4)).await.1
+    }
+
+    #[tokio::test]
+    async fn applies_multi_question_policy() {
+        let response = exchange_with_policy(MultiQuestionPolicy::FormatError).await;
+        assert_eq!(response.header.rcode, ResponseCode::FormatError);
+        assert_eq!(response.questions.len(), 2);
+
+        let response = exchange_with_policy(MultiQuestionPolicy::FirstOnly).await;
+        assert_eq!(response.questions.len(), 1);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].record(), "first.test");
+
+        let response = exchange_with_policy(MultiQuestionPolicy::EachQuestion).await;
+        assert_eq!(response.questions.len(), 2);
+        let owners: Vec<&str> = response.answers.iter().map(|record| record.record()).collect();
+        assert_eq!(owners, vec!["first.test", "second.test"]);
+    }
+
+    struct FailingResolver;
+
+    #[async_trait]
+    impl Resolver for FailingResolver {
+        async fn resolve(&self, _request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            Err(QueryError::FailetToResolveQuery)
+        }
+    }
+
+    /// Fails for first.test and answers everything else.
+    struct FirstFailingResolver;
+
+    #[async_trait]
+    impl Resolver for FirstFailingResolver {
+        async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            match request.questions[0].name.as_str() {
+                "first.test" => Err(QueryError::FailetToResolveQuery),
+                _ => StaticResolver { answers: 1 }.resolve(request, ctx).await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_questions_keep_the_other_answers() {
+        let options = ServerOptions { multi_question_policy: MultiQuestionPolicy::EachQuestion, ..ServerOptions::default() };
+        let server_address = start_server_with_resolver(Arc::new(FirstFailingResolver), options).await;
+
+        let (_, response) = exchange(server_address, &two_questions(15)).await;
+
+        assert_eq!(response.header.rcode, ResponseCode::ServerFailure);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].record(), "second.test");
+    }
+
+    fn assert_error_response(response: &DnsPacket, id: u16, rcode: ResponseCode) {
+        assert_eq!(response.header.id, id);
+        assert!(response.header.is_response);
+        assert_eq!(response.header.rcode, rcode);
+        assert_eq!(response.questions, vec![Question::new("custom.test", RecordType::A, Class::IN)]);
+        assert!(response.answers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn answers_failures_with_an_error_rcode() {
+        let server_address = start_server(1).await;
+
+        // Claims an answer record that isn't there.
+        let mut malformed = DnsPacket::new_query(11, "custom.test", RecordType::A).to_bytes().unwrap();
+        malformed[7] = 1;
+        assert_error_response(&exchange_bytes(server_address, &malformed).await.1, 11, ResponseCode::FormatError);
+
+        let mut notify = DnsPacket::new_query(12, "custom.test", RecordType::A);
+        notify.header.opcode = 4;
+        let (_, response) = exchange(server_address, &notify).await;
+        assert_error_response(&response, 12, ResponseCode::NotImplemented);
+        assert_eq!(response.header.opcode, 4);
+
+        let failing = start_server_with_resolver(Arc::new(FailingResolver), ServerOptions::default()).await;
+        let (_, response) = exchange(failing, &DnsPacket::new_query(13, "custom.test", RecordType::A)).await;
+        assert_error_response(&response, 13, ResponseCode::ServerFailure);
+    }
+
+    #[tokio::test]
+    async fn refuses_clients_outside_the_allowed_networks() {
+        let options = ServerOptions {
+            allowed_clients: vec![IpNetwork::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24)],
+            ..ServerOptions::default()
+        };
+        let server_address = start_server_with_options(1, options).await;
+
+        let query = DnsPacket::new_query(14, "custom.test", RecordType::A).with_edns(Edns::new(4096));
+        let (_, response) = exchange(server_address, &query).await;
+
+        assert_error_response(&response, 14, ResponseCode::Refused);
+        assert!(response.edns.is_some());
+    }
+
+    /// Answers after `delay`, whatever the protocol.
+    struct SlowResolver {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Resolver for SlowResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            tokio::time::sleep(self.delay).await;
+            Ok(DnsPacket::response_to(request))
+        }
+    }
+
+    async fn start_slow_server(delay: Duration, shutdown_timeout: Duration) -> ServerHandle {
+        let options = ServerOptions { shutdown_timeout, ..ServerOptions::default() };
+        Server::with_options(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, Arc::new(SlowResolver { delay }), options)
+            .await
+            .unwrap()
+            .start()
+    }
+
+    #[tokio::test]
+    async fn starts_and_stops_repeatedly_on_ephemeral_ports() {
+        for id in 0..20 {
+            let server = Server::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, Arc::new(StaticResolver { answers: 1 }))
+                .await
+                .unwrap()
+                .start();
+            let address = server.local_addr();
+
+            assert_eq!(exchange(address, &DnsPacket::new_query(id, "custom.test", RecordType::A)).await.1.header.id, id);
+            assert_eq!(server.shutdown().await, ServerStats { queries: 1, responses: 1, in_flight: 0 });
+
+            // Both sockets are closed, so the address is free again.
+            UdpSocket::bind(address).await.unwrap();
+            tokio::net::TcpListener::bind(address).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_queries_in_flight() {
+        let server = start_slow_server(Duration::from_millis(200), Duration::from_secs(5)).await;
+        let address = server.local_addr();
+
+        let query = DnsPacket::new_query(21, "slow.test", RecordType::A);
+        let client = tokio::spawn(async move { exchange(address, &query).await });
+        while server.stats().in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(server.shutdown().await, ServerStats { queries: 1, responses: 1, in_flight: 0 });
+        assert_eq!(client.await.unwrap().1.header.id, 21);
+    }
+
+    #[tokio::test]
+    async fn shutdown_gives_up_on_queries_after_its_timeout() {
+        let server = start_slow_server(Duration::from_secs(3600), Duration::from_millis(100)).await;
+        let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let query = DnsPacket::new_query(22, "stuck.test", RecordType::A);
+        client.send_to(&query.to_bytes().unwrap(), server.local_addr()).await.unwrap();
+        while server.stats().in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let started = std::time::Instant::now();
+        let stats = server.shutdown().await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(stats, ServerStats { queries: 1, responses: 0, in_flight: 1 });
+    }
 }
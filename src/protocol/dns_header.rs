@@ -1,7 +1,11 @@
-use super::Result;
+use crate::errors::{BufferError, ParseError};
 
 use super::packet_buffer::PacketBuffer;
 
+/// Opcode of a standard query, the only kind of request answered (RFC 1035 4.1.1).
+pub const OPCODE_QUERY: u8 = 0;
+
+/// The fixed 12 byte header at the start of every DNS message (RFC 1035 4.1.1).
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct DnsHeader {
@@ -14,7 +18,11 @@ pub struct DnsHeader {
     pub recursion_desired: bool,    // 1  bit
     pub recursion_available: bool,  // 1  bit
 
-    pub z: u8,             // 3  bit
+    pub z: u8,                      // 1  bit
+    /// AD, set when every record in the response was validated (RFC 4035 3.2.3).
+    pub authentic_data: bool,       // 1  bit
+    /// CD, set by clients that want responses even if they fail validation.
+    pub checking_disabled: bool,    // 1  bit
     pub rcode: ResponseCode, // 4  bits
 
     pub question_count: u16, // 16 bits
@@ -24,7 +32,28 @@ pub struct DnsHeader {
 }
 
 impl DnsHeader {
-    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self> {
+    /// Creates an empty query header with the given id and every flag cleared.
+    pub fn new(id: u16) -> Self {
+        DnsHeader {
+            id,
+            is_response: false,
+            opcode: 0,
+            authoritative_answer: false,
+            truncated_message: false,
+            recursion_desired: false,
+            recursion_available: false,
+            z: 0,
+            authentic_data: false,
+            checking_disabled: false,
+            rcode: ResponseCode::NoError,
+            question_count: 0,
+            answer_count: 0,
+            nscount: 0,
+            arcount: 0,
+        }
+    }
+
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
         let id = packet_buffer.read_u16()?;
         let flags = packet_buffer.read_u16()?;
 
@@ -34,8 +63,10 @@ impl DnsHeader {
         let truncated_message = (flags >> 9 & 1) > 0;
         let recursion_desired = (flags >> 8 & 1) > 0;
         let recursion_available = (flags >> 7 & 1) > 0;
-        let z = (flags >> 4 & 0x07) as u8;
-        let response_code = ResponseCode::from_u8((flags & 0x0F) as u8).unwrap();
+        let z = (flags >> 6 & 1) as u8;
+        let authentic_data = (flags >> 5 & 1) > 0;
+        let checking_disabled = (flags >> 4 & 1) > 0;
+        let response_code = ResponseCode::from((flags & 0x0F) as u8);
 
         let question_count = packet_buffer.read_u16()?;
         let answer_count = packet_buffer.read_u16()?;
@@ -51,6 +82,8 @@ impl DnsHeader {
             recursion_desired,
             recursion_available,
             z,
+            authentic_data,
+            checking_disabled,
             rcode: response_code,
             question_count,
             answer_count,
@@ -58,11 +91,11 @@ impl DnsHeader {
             arcount,
         };
 
-        return Ok(header);
+        Ok(header)
     }
 
-    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<()> {
-        packet_buffer.write_u16(self.id);
+    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        packet_buffer.write_u16(self.id)?;
 
         let mut flags: u16;
 
@@ -74,40 +107,74 @@ impl DnsHeader {
         flags = (flags << 1) | (if self.recursion_desired { 1 } else { 0 } & 0b1);
         flags = (flags << 1) | (if self.recursion_available { 1 } else { 0 } & 0b1);
 
-        flags = (flags << 3) | (self.z as u16 & 0x7);
-        flags = (flags << 4) | (self.rcode as u16 & 0xF);
+        flags = (flags << 1) | (self.z as u16 & 0b1);
+        flags = (flags << 1) | (if self.authentic_data { 1 } else { 0 } & 0b1);
+        flags = (flags << 1) | (if self.checking_disabled { 1 } else { 0 } & 0b1);
+        flags = (flags << 4) | (u8::from(self.rcode) as u16 & 0xF);
 
-        packet_buffer.write_u16(flags);
+        packet_buffer.write_u16(flags)?;
 
-        packet_buffer.write_u16(self.question_count);
-        packet_buffer.write_u16(self.answer_count);
-        packet_buffer.write_u16(self.nscount);
-        packet_buffer.write_u16(self.arcount);
+        packet_buffer.write_u16(self.question_count)?;
+        packet_buffer.write_u16(self.answer_count)?;
+        packet_buffer.write_u16(self.nscount)?;
+        packet_buffer.write_u16(self.arcount)?;
 
-        return Ok(());
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The RCODE of a message. Unassigned codes are kept as `Unknown` so the
+/// message can still be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResponseCode {
-    NoError = 0,
-    FormatError = 1,
-    ServerFailure = 2,
-    NXDomain = 3,
-    NotImplemented = 4,
-    Refused = 5,
+    NoError,
+    FormatError,
+    ServerFailure,
+    NXDomain,
+    NotImplemented,
+    Refused,
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    Unknown(u8),
 }
 
-impl ResponseCode {
-    pub fn from_u8(value: u8) -> Result<Self> {
+impl From<u8> for ResponseCode {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(ResponseCode::NoError),
-            1 => Ok(ResponseCode::FormatError),
-            2 => Ok(ResponseCode::ServerFailure),
-            3 => Ok(ResponseCode::NXDomain),
-            4 => Ok(ResponseCode::NotImplemented),
-            5 => Ok(ResponseCode::Refused),
-            _ => Err("Invalid ResponseCode".into()),
+            0 => ResponseCode::NoError,
+            1 => ResponseCode::FormatError,
+            2 => ResponseCode::ServerFailure,
+            3 => ResponseCode::NXDomain,
+            4 => ResponseCode::NotImplemented,
+            5 => ResponseCode::Refused,
+            6 => ResponseCode::YXDomain,
+            7 => ResponseCode::YXRRSet,
+            8 => ResponseCode::NXRRSet,
+            9 => ResponseCode::NotAuth,
+            10 => ResponseCode::NotZone,
+            _ => ResponseCode::Unknown(value),
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<ResponseCode> for u8 {
+    fn from(rcode: ResponseCode) -> u8 {
+        match rcode {
+            ResponseCode::NoError => 0,
+            ResponseCode::FormatError => 1,
+            ResponseCode::ServerFailure => 2,
+            ResponseCode::NXDomain => 3,
+            ResponseCode::NotImplemented => 4,
+            ResponseCode::Refused => 5,
+            ResponseCode::YXDomain => 6,
+            ResponseCode::YXRRSet => 7,
+            ResponseCode::NXRRSet => 8,
+            ResponseCode::NotAuth => 9,
+            ResponseCode::NotZone => 10,
+            ResponseCode::Unknown(value) => value,
+        }
+    }
+}
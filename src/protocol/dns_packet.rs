@@ -1,90 +1,574 @@
-use crate::errors::{DeserializeError, SerializeError};
+use std::fmt;
+
+use log::debug;
+
+use crate::errors::{BufferError, DeserializeError, ParseError, SerializeError};
 
 use super::{
-    dns_record::DnsRecord, dns_header::DnsHeader, dns_query::DnsQuery, packet_buffer::PacketBuffer,
+    dns_header::{DnsHeader, ResponseCode},
+    dns_query::Question,
+    dns_record::DnsRecord,
+    dns_record_type::{Class, RecordType},
+    edns::{Edns, OPT_TYPE},
+    packet_buffer::{PacketBuffer, MAX_MESSAGE_SIZE},
 };
 
-#[derive(Debug)]
+/// Largest UDP response for clients that don't advertise a bigger size (RFC 1035 4.2.1).
+pub const UDP_PAYLOAD_LIMIT: usize = 512;
+
+/// The sections of a message, in the order they appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Section::Question => "question",
+            Section::Answer => "answer",
+            Section::Authority => "authority record",
+            Section::Additional => "additional record",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// What to do with bytes left after the last section of a received message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingBytesPolicy {
+    /// Log them and parse the message anyway, as most servers do.
+    #[default]
+    Lenient,
+    /// Reject the message.
+    Strict,
+}
+
+/// A complete DNS message.
+///
+/// Queries and responses can be built with [`DnsPacket::new_query`] and
+/// [`DnsPacket::response_to`] plus the `with_*` helpers, which keep the
+/// section counts in the header in sync.
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct DnsPacket {
     pub header: DnsHeader,
-    pub questions: DnsQuery,
+    pub questions: Vec<Question>,
     pub answers: Vec<DnsRecord>,
     pub authority: Vec<DnsRecord>,
     pub additional: Vec<DnsRecord>,
+    /// EDNS(0) parameters from the OPT record, written after the additional section.
+    pub edns: Option<Edns>,
 }
 
 impl DnsPacket {
-    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, DeserializeError> {
-        let header = DnsHeader::deserialize(packet_buffer);
-        if let Err(_) = header {
-            return Err(DeserializeError::InvalidHeader)
-        }
-        let header = header.unwrap();
+    /// Creates a recursive query for `domain_name` in the IN class.
+    pub fn new_query(id: u16, domain_name: &str, qtype: RecordType) -> Self {
+        let mut header = DnsHeader::new(id);
+        header.recursion_desired = true;
+        header.question_count = 1;
 
-        let questions = DnsQuery::deserialize(packet_buffer, header.question_count);
-        if let Err(_) = questions {
-            return Err(DeserializeError::InvalidHeader);
+        DnsPacket {
+            header,
+            questions: vec![Question::new(domain_name, qtype, Class::IN)],
+            answers: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+            edns: None,
         }
-        let questions = questions.unwrap();
+    }
 
-        let mut answers = Vec::new();
-        for _ in 0..header.answer_count {
-            let answer = DnsRecord::deserialize(packet_buffer);
-            if let Err(_) = answer {
-                return Err(DeserializeError::InvalidRecord);
-            }
-            let answer = answer.unwrap();
-            answers.push(answer);
+    /// Creates an empty response that echoes the id, opcode, recursion desired
+    /// and checking disabled flags and questions of `query`.
+    pub fn response_to(query: &DnsPacket) -> Self {
+        let mut header = DnsHeader::new(query.header.id);
+        header.is_response = true;
+        header.opcode = query.header.opcode;
+        header.recursion_desired = query.header.recursion_desired;
+        header.checking_disabled = query.header.checking_disabled;
+        header.question_count = query.questions.len() as u16;
+
+        DnsPacket {
+            header,
+            questions: query.questions.clone(),
+            answers: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+            edns: None,
         }
+    }
 
-        let mut authority = Vec::new();
-        for _ in 0..header.nscount {
-            let name_server = DnsRecord::deserialize(packet_buffer);
-            if let Err(_) = name_server {
-                return Err(DeserializeError::InvalidRecord);
-            }
-            let name_server = name_server.unwrap();
-            authority.push(name_server);
+    pub fn with_rcode(mut self, rcode: ResponseCode) -> Self {
+        self.header.rcode = rcode;
+        self
+    }
+
+    pub fn with_answer(mut self, record: DnsRecord) -> Self {
+        self.answers.push(record);
+        self.header.answer_count = self.answers.len() as u16;
+        self
+    }
+
+    pub fn with_authority(mut self, record: DnsRecord) -> Self {
+        self.authority.push(record);
+        self.header.nscount = self.authority.len() as u16;
+        self
+    }
+
+    pub fn with_additional(mut self, record: DnsRecord) -> Self {
+        self.additional.push(record);
+        self.header.arcount = self.additional.len() as u16;
+        self
+    }
+
+    pub fn with_edns(mut self, edns: Edns) -> Self {
+        self.edns = Some(edns);
+        self
+    }
+
+    /// Reads a message from the current position of `packet_buffer`, leaving
+    /// it right after the last section.
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, DeserializeError> {
+        let header = DnsHeader::deserialize(packet_buffer)
+            .map_err(DeserializeError::InvalidHeader)?;
+
+        let mut questions = Vec::new();
+        for index in 0..header.question_count as usize {
+            let start = packet_buffer.pos;
+            let question = Question::deserialize(packet_buffer)
+                .map_err(|e| invalid_record(packet_buffer, start, Section::Question, index, e))?;
+            questions.push(question);
         }
 
+        let answers = read_section(packet_buffer, Section::Answer, header.answer_count)?;
+        let authority = read_section(packet_buffer, Section::Authority, header.nscount)?;
+
         let mut additional = Vec::new();
-        for _ in 0..header.arcount {
-            let record = DnsRecord::deserialize(packet_buffer);
-            if let Err(_) = record {
-                return Err(DeserializeError::InvalidRecord);
+        let mut edns = None;
+        for index in 0..header.arcount as usize {
+            let start = packet_buffer.pos;
+            let is_opt = packet_buffer.read_qname()
+                .and_then(|_| packet_buffer.read_u16())
+                .map_err(|e| invalid_record(packet_buffer, start, Section::Additional, index, e))? == OPT_TYPE;
+
+            if !is_opt {
+                packet_buffer.seek(start);
+                let record = DnsRecord::deserialize(packet_buffer)
+                    .map_err(|e| invalid_record(packet_buffer, start, Section::Additional, index, e))?;
+                additional.push(record);
+                continue;
+            }
+
+            // RFC 6891 6.1.1: more than one OPT record is a format error.
+            if edns.is_some() {
+                let e = ParseError::InvalidField { position: start, field: "second OPT record" };
+                return Err(invalid_record(packet_buffer, start, Section::Additional, index, e));
             }
-            let record = record.unwrap();
-            additional.push(record);
+            edns = Some(Edns::deserialize(packet_buffer)
+                .map_err(|e| invalid_record(packet_buffer, start, Section::Additional, index, e))?);
         }
 
-        return Ok(DnsPacket {
+        Ok(DnsPacket {
             header,
             questions,
             answers,
             authority,
             additional,
-        });
+            edns,
+        })
     }
 
     pub fn serialize(&self) -> Result<PacketBuffer, SerializeError> {
-        let mut packet_buffer = PacketBuffer::new([0u8; 512]);
+        self.serialize_with_limit(MAX_MESSAGE_SIZE)
+    }
+
+    /// Serializes the packet into at most `limit` bytes.
+    ///
+    /// Records that don't fit are dropped a whole RRset at a time, starting
+    /// from the end of the message. The TC bit is set when answer or
+    /// authority records had to be left out; trimming only additional records
+    /// doesn't set it (RFC 2181 9). Room for the OPT record is always kept,
+    /// so truncated responses still carry it (RFC 6891 7).
+    pub fn serialize_with_limit(&self, limit: usize) -> Result<PacketBuffer, SerializeError> {
+        let opt_length = self.edns.as_ref().map_or(0, Edns::wire_length);
+        let mut packet_buffer = PacketBuffer::with_limit(limit.saturating_sub(opt_length));
+        let mut header = self.header.clone();
+        header.question_count = self.questions.len() as u16;
+
+        header.serialize(&mut packet_buffer)
+            .map_err(SerializeError::InvalidHeader)?;
+        for (index, question) in self.questions.iter().enumerate() {
+            question.serialize(&mut packet_buffer)
+                .map_err(|source| SerializeError::InvalidRecord { section: Section::Question, index, source })?;
+        }
 
-        self.header.serialize(&mut packet_buffer)
-            .map_err(|_| SerializeError::InvalidHeader)?;
-        self.questions.serialize(&mut packet_buffer)
-            .map_err(|_| SerializeError::InvalidQuestion)?;
+        let (answer_count, answers_complete) = write_section(&mut packet_buffer, Section::Answer, &self.answers)?;
+        let (nscount, authority_complete) = match answers_complete {
+            true => write_section(&mut packet_buffer, Section::Authority, &self.authority)?,
+            false => (0, false),
+        };
+        let arcount = match authority_complete {
+            true => write_section(&mut packet_buffer, Section::Additional, &self.additional)?.0,
+            false => 0,
+        };
 
-        for answer in self.answers.iter() {
-            answer.serialize(&mut packet_buffer)
-                .map_err(|_| SerializeError::InvalidRecord)?;
+        if let Some(edns) = &self.edns {
+            packet_buffer.set_limit(limit);
+            edns.serialize(&mut packet_buffer)
+                .map_err(SerializeError::InvalidEdns)?;
         }
 
-        for name_server in self.authority.iter() {
-            name_server.serialize(&mut packet_buffer)
-                .map_err(|_| SerializeError::InvalidRecord)?;
+        header.answer_count = answer_count;
+        header.nscount = nscount;
+        header.arcount = arcount + self.edns.is_some() as u16;
+        header.truncated_message |= !authority_complete;
+
+        let end = packet_buffer.pos;
+        packet_buffer.seek(0);
+        header.serialize(&mut packet_buffer)
+            .map_err(SerializeError::InvalidHeader)?;
+        packet_buffer.seek(end);
+
+        Ok(packet_buffer)
+    }
+
+    /// Parses a packet from the bytes received on the wire.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        DnsPacket::from_bytes_with_policy(bytes, TrailingBytesPolicy::default())
+    }
+
+    /// Like [`DnsPacket::from_bytes`], handling bytes after the last section
+    /// according to `policy`.
+    pub fn from_bytes_with_policy(bytes: &[u8], policy: TrailingBytesPolicy) -> Result<Self, DeserializeError> {
+        let mut packet_buffer = PacketBuffer::from_bytes(bytes)
+            .map_err(DeserializeError::InvalidHeader)?;
+        let packet = DnsPacket::deserialize(&mut packet_buffer)?;
+
+        let position = packet_buffer.pos;
+        if position < bytes.len() {
+            let count = bytes.len() - position;
+            match policy {
+                TrailingBytesPolicy::Lenient => debug!("Ignoring {} bytes after the end of message {}", count, packet.header.id),
+                TrailingBytesPolicy::Strict => return Err(DeserializeError::TrailingBytes { position, count }),
+            }
         }
 
-        return Ok(packet_buffer);
+        Ok(packet)
+    }
+
+    /// Serializes the packet into the bytes that go on the wire.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
+        Ok(self.serialize()?.as_bytes().to_vec())
+    }
+
+    /// Like [`DnsPacket::to_bytes`], truncating the message to `limit` bytes.
+    pub fn to_bytes_with_limit(&self, limit: usize) -> Result<Vec<u8>, SerializeError> {
+        Ok(self.serialize_with_limit(limit)?.as_bytes().to_vec())
+    }
+}
+
+/// Reads the `count` records of `section`.
+fn read_section(packet_buffer: &mut PacketBuffer, section: Section, count: u16) -> Result<Vec<DnsRecord>, DeserializeError> {
+    let mut records = Vec::new();
+    for index in 0..count as usize {
+        let start = packet_buffer.pos;
+        let record = DnsRecord::deserialize(packet_buffer)
+            .map_err(|e| invalid_record(packet_buffer, start, section, index, e))?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Describes why the entry starting at `start` couldn't be read, with its type
+/// when the name and type in front of it are readable.
+fn invalid_record(
+    packet_buffer: &mut PacketBuffer,
+    start: usize,
+    section: Section,
+    index: usize,
+    source: ParseError,
+) -> DeserializeError {
+    packet_buffer.seek(start);
+    let record_type = packet_buffer.read_qname()
+        .and_then(|_| packet_buffer.read_u16())
+        .map(RecordType::from)
+        .ok();
+
+    DeserializeError::InvalidRecord { section, index, record_type, source }
+}
+
+/// Writes as many whole RRsets of `records` as fit, returning how many
+/// records were written and whether that was all of them.
+fn write_section(packet_buffer: &mut PacketBuffer, section: Section, records: &[DnsRecord]) -> Result<(u16, bool), SerializeError> {
+    let mut rrset_start = (0, packet_buffer.pos);
+
+    for (i, record) in records.iter().enumerate() {
+        if i == 0 || !same_rrset(&records[i - 1], record) {
+            rrset_start = (i, packet_buffer.pos);
+        }
+
+        match record.serialize(packet_buffer) {
+            Ok(()) => {}
+            Err(BufferError::LimitExceeded { .. }) => {
+                packet_buffer.truncate(rrset_start.1);
+                return Ok((rrset_start.0 as u16, false));
+            }
+            Err(source) => return Err(SerializeError::InvalidRecord { section, index: i, source }),
+        }
+    }
+
+    Ok((records.len() as u16, true))
+}
+
+fn same_rrset(a: &DnsRecord, b: &DnsRecord) -> bool {
+    a.rdata().get_type() == b.rdata().get_type() && a.record().eq_ignore_ascii_case(b.record())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::protocol::dns_record_type::DnsRecordType;
+
+    #[test]
+    fn build_and_parse_response() {
+        let query = DnsPacket::new_query(0x1234, "example.com", RecordType::A);
+        let response = DnsPacket::response_to(&query)
+            .with_answer(DnsRecord::new(
+                "example.com".to_string(),
+                Class::IN,
+                300,
+                DnsRecordType::A { address: Ipv4Addr::new(93, 184, 216, 34) },
+            ));
+
+        let mut buffer = response.serialize().unwrap();
+        buffer.seek(0);
+        let parsed = DnsPacket::deserialize(&mut buffer).unwrap();
+
+        assert_eq!(parsed.header.id, 0x1234);
+        assert!(parsed.header.is_response);
+        assert!(parsed.header.recursion_desired);
+        assert_eq!(parsed.questions, vec![Question::new("example.com", RecordType::A, Class::IN)]);
+        assert_eq!(parsed.answers.len(), 1);
+        assert_eq!(parsed.answers[0].record(), "example.com");
+        assert_eq!(parsed.answers[0].ttl(), 300);
+        assert_eq!(
+            parsed.answers[0].rdata(),
+            &DnsRecordType::A { address: Ipv4Addr::new(93, 184, 216, 34) }
+        );
+    }
+
+    #[test]
+    fn round_trips_several_questions() {
+        let mut query = DnsPacket::new_query(7, "example.com", RecordType::A);
+        query.questions.push(Question::new("example.org", RecordType::AAAA, Class::IN));
+        query.questions.push(Question::new("version.bind", RecordType::TXT, Class::CH));
+        query.questions.push(Question::new("example.net", RecordType::ANY, Class::ANY));
+        query.questions.push(Question::new("example.net", RecordType::A, Class::NONE));
+        query.questions.push(Question::new("example.net", RecordType::A, Class::Unknown(4242)));
+
+        let parsed = DnsPacket::from_bytes(&query.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.header.question_count, 6);
+        assert_eq!(parsed.questions, query.questions);
+    }
+
+    #[test]
+    fn unknown_records_round_trip_byte_for_byte() {
+        let query = DnsPacket::new_query(9, "example.com", RecordType::Unknown(65280));
+        let response = DnsPacket::response_to(&query).with_answer(DnsRecord::new(
+            "example.com".to_string(),
+            Class::IN,
+            300,
+            DnsRecordType::Unknown { type_code: 65280, rdata: vec![0, 1, 0, 0, 1, 0, 3, 2, 104, 50] },
+        ));
+        let bytes = response.to_bytes().unwrap();
+
+        let parsed = DnsPacket::from_bytes(&bytes).unwrap();
+
+        assert_eq!(parsed.questions[0].qtype, RecordType::Unknown(65280));
+        assert_eq!(parsed.answers, response.answers);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+
+    fn large_response() -> DnsPacket {
+        let query = DnsPacket::new_query(1, "many.example.com", RecordType::A);
+        let mut response = DnsPacket::response_to(&query);
+        for i in 0..10 {
+            response = response.with_answer(DnsRecord::new(
+                "many.example.com".to_string(),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, i) },
+            ));
+        }
+        for i in 0..40 {
+            response = response.with_additional(DnsRecord::new(
+                format!("ns{}.example.com", i),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(10, 0, 1, i) },
+            ));
+        }
+        response
+    }
+
+    #[test]
+    fn serializes_responses_over_512_bytes() {
+        let bytes = large_response().to_bytes().unwrap();
+        assert!(bytes.len() > UDP_PAYLOAD_LIMIT);
+
+        let parsed = DnsPacket::from_bytes(&bytes).unwrap();
+        assert!(!parsed.header.truncated_message);
+        assert_eq!(parsed.answers.len(), 10);
+        assert_eq!(parsed.additional.len(), 40);
+    }
+
+    #[test]
+    fn trims_additional_records_without_truncation() {
+        let bytes = large_response().to_bytes_with_limit(UDP_PAYLOAD_LIMIT).unwrap();
+        assert!(bytes.len() <= UDP_PAYLOAD_LIMIT);
+
+        let parsed = DnsPacket::from_bytes(&bytes).unwrap();
+        assert!(!parsed.header.truncated_message);
+        assert_eq!(parsed.answers.len(), 10);
+        assert!(parsed.additional.len() < 40);
+        assert_eq!(parsed.header.arcount as usize, parsed.additional.len());
+    }
+
+    #[test]
+    fn keeps_opt_record_when_truncating() {
+        let response = large_response().with_edns(Edns::new(1232));
+        let bytes = response.to_bytes_with_limit(100).unwrap();
+        assert!(bytes.len() <= 100);
+
+        let parsed = DnsPacket::from_bytes(&bytes).unwrap();
+        assert!(parsed.header.truncated_message);
+        assert_eq!(parsed.header.arcount, 1);
+        assert!(parsed.additional.is_empty());
+        assert_eq!(parsed.edns, Some(Edns::new(1232)));
+    }
+
+    #[test]
+    fn sets_tc_when_answers_do_not_fit() {
+        let bytes = large_response().to_bytes_with_limit(100).unwrap();
+        assert!(bytes.len() <= 100);
+
+        let parsed = DnsPacket::from_bytes(&bytes).unwrap();
+        assert!(parsed.header.truncated_message);
+        assert!(parsed.answers.is_empty());
+        assert!(parsed.additional.is_empty());
+    }
+
+    /// A header with the RD flag and the given question and answer counts.
+    fn header(question_count: u8, answer_count: u8) -> Vec<u8> {
+        vec![0, 1, 1, 0, 0, question_count, 0, answer_count, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let mut long_name = header(1, 0);
+        long_name.extend([[63].as_slice(), &[b'a'; 63]].concat().repeat(5));
+        long_name.extend([0, 0, 1, 0, 1]);
+
+        let corpus: Vec<(Vec<u8>, ParseError)> = vec![
+            (Vec::new(), ParseError::Truncated { position: 0 }),
+            (header(0, 0)[..11].to_vec(), ParseError::Truncated { position: 11 }),
+            ([header(1, 0), vec![3, b'w', b'w']].concat(), ParseError::Truncated { position: 15 }),
+            ([header(1, 0), vec![0xC0, 12, 0, 1, 0, 1]].concat(), ParseError::ForwardPointer { position: 12, target: 12 }),
+            ([header(1, 0), vec![0xC0, 14, 0, 0, 1, 0, 1]].concat(), ParseError::ForwardPointer { position: 12, target: 14 }),
+            ([header(1, 0), vec![1, b'a', 0xC0, 12, 0, 1, 0, 1]].concat(), ParseError::PointerLoop { position: 14, target: 12 }),
+            ([header(1, 0), vec![0x40, 0, 0, 1, 0, 1]].concat(), ParseError::ReservedLabelType { position: 12 }),
+            (long_name, ParseError::NameTooLong { position: 12 }),
+            (
+                [header(0, 1), vec![0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 10, 1, 2, 3, 4]].concat(),
+                ParseError::BadRdLength { position: 23, rdlength: 10 },
+            ),
+            (
+                [header(0, 1), vec![0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 2, 1, 2, 3, 4]].concat(),
+                ParseError::BadRdLength { position: 23, rdlength: 2 },
+            ),
+            (
+                [header(0, 1), vec![0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 6, 1, 2, 3, 4, 5, 6]].concat(),
+                ParseError::BadRdLength { position: 23, rdlength: 6 },
+            ),
+            (
+                [header(0, 1), vec![0, 0, 16, 0, 1, 0, 0, 0, 60, 0, 4, 9, b'a', b'b', b'c']].concat(),
+                ParseError::BadRdLength { position: 23, rdlength: 4 },
+            ),
+            (
+                [header(0, 255), vec![0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4]].concat(),
+                ParseError::Truncated { position: 27 },
+            ),
+        ];
+
+        for (index, (bytes, expected)) in corpus.iter().enumerate() {
+            let error = match DnsPacket::from_bytes(bytes) {
+                Ok(_) => panic!("packet {} parsed", index),
+                Err(DeserializeError::InvalidHeader(source)) | Err(DeserializeError::InvalidRecord { source, .. }) => source,
+                Err(e) => panic!("packet {} failed with {}", index, e),
+            };
+            assert_eq!(&error, expected, "packet {}", index);
+        }
+    }
+
+    #[test]
+    fn parses_response_codes_from_dynamic_updates() {
+        let bytes = [0, 1, 0x81, 0x09, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(DnsPacket::from_bytes(&bytes).unwrap().header.rcode, ResponseCode::NotAuth);
+    }
+
+    #[test]
+    fn keeps_unassigned_response_codes() {
+        let bytes = [0, 1, 0x81, 0x0F, 0, 0, 0, 0, 0, 0, 0, 0];
+        let packet = DnsPacket::from_bytes(&bytes).unwrap();
+
+        assert_eq!(packet.header.rcode, ResponseCode::Unknown(15));
+        assert_eq!(packet.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn names_the_record_that_failed() {
+        let a_record = vec![0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4];
+        // An MX record cut short by its RDLENGTH, followed by a record it must not read into.
+        let short_mx = vec![0, 0, 15, 0, 1, 0, 0, 0, 60, 0, 2, 0, 10];
+        let bytes = [header(0, 3), a_record.clone(), short_mx, a_record].concat();
+
+        match DnsPacket::from_bytes(&bytes).unwrap_err() {
+            DeserializeError::InvalidRecord { section, index, record_type, source } => {
+                assert_eq!((section, index, record_type), (Section::Answer, 1, Some(RecordType::MX)));
+                assert_eq!(source, ParseError::BadRdLength { position: 38, rdlength: 2 });
+            }
+            e => panic!("unexpected error {}", e),
+        }
+
+        let bytes = [header(1, 0), vec![0xC0, 12, 0, 1, 0, 1]].concat();
+        match DnsPacket::from_bytes(&bytes).unwrap_err() {
+            DeserializeError::InvalidRecord { section, index, record_type, .. } => {
+                assert_eq!((section, index, record_type), (Section::Question, 0, None));
+            }
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_depend_on_the_policy() {
+        let mut bytes = DnsPacket::new_query(7, "example.com", RecordType::A).to_bytes().unwrap();
+        bytes.extend([0xAB, 0xCD]);
+
+        let parsed = DnsPacket::from_bytes_with_policy(&bytes, TrailingBytesPolicy::Lenient).unwrap();
+        assert_eq!(parsed.questions[0].name, "example.com");
+
+        match DnsPacket::from_bytes_with_policy(&bytes, TrailingBytesPolicy::Strict) {
+            Err(DeserializeError::TrailingBytes { position, count }) => assert_eq!((position, count), (bytes.len() - 2, 2)),
+            result => panic!("unexpected result {:?}", result),
+        }
     }
 }
@@ -1,86 +1,67 @@
-use super::Result;
+use crate::errors::{BufferError, ParseError};
 
-use super::{dns_record_type::Class, packet_buffer::PacketBuffer};
+use super::{dns_record_type::{Class, RecordType}, packet_buffer::PacketBuffer};
 
-#[derive(Debug, Clone)]
-pub struct DnsQuery {
-    pub domain_names: Vec<String>,
-    pub qtype: u16,
+/// One entry of the question section of a DNS message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: RecordType,
     pub qclass: Class,
 }
 
-impl DnsQuery {
-    pub fn deserialize(packet_buffer: &mut PacketBuffer, query_count: u16) -> Result<Self> {
-        let mut domain_names = Vec::new();
-
-        for _ in 0..query_count {
-            let domain_name = packet_buffer.read_qname()?;
-            domain_names.push(domain_name);
+impl Question {
+    pub fn new(name: &str, qtype: RecordType, qclass: Class) -> Self {
+        Question {
+            name: name.to_string(),
+            qtype,
+            qclass,
         }
+    }
 
-
-        let qtype = packet_buffer.read_u16()?;
-
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
+        let name = packet_buffer.read_qname()?;
+        let qtype = RecordType::from(packet_buffer.read_u16()?);
         let qclass = Class::deserialize(packet_buffer)?;
 
-        return Ok(DnsQuery {
-            domain_names,
-            qtype,
-            qclass,
-        });
+        Ok(Question { name, qtype, qclass })
     }
 
-    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<()> {
-        for name in &self.domain_names {
-            packet_buffer.write_qname(name);
-        }
-
-        packet_buffer.write_u16(self.qtype);
-        packet_buffer.write_u16(self.qclass.into());
+    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        packet_buffer.write_qname(&self.name)?;
+        packet_buffer.write_u16(self.qtype.into())?;
+        packet_buffer.write_u16(self.qclass.into())?;
 
-        return Ok(());
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::protocol::dns_record_type::Class;
-
+    use super::Question;
+    use crate::protocol::dns_record_type::{Class, RecordType};
+    use crate::protocol::packet_buffer::PacketBuffer;
 
     #[test]
-    fn serialize_and_deserialize_dns_query() {
-        use super::DnsQuery;
-        use crate::protocol::packet_buffer::PacketBuffer;
+    fn serialize_and_deserialize_questions() {
+        let mut packet_buffer = PacketBuffer::new();
 
-        let mut packet_buffer = PacketBuffer::new([0; 512]);
-
-        let domain_names = vec![
-            "goole.com".to_string(),
-            "images.google.com".to_string(),
-            "www.images.google.com".to_string(),
+        let questions = vec![
+            Question::new("goole.com", RecordType::A, Class::IN),
+            Question::new("images.google.com", RecordType::AAAA, Class::IN),
+            Question::new("www.images.google.com", RecordType::Unknown(65280), Class::CH),
         ];
 
-        let dns_query = DnsQuery {
-            domain_names,
-            qtype: 1,
-            qclass: Class::IN,
-        };
-
-        dns_query.serialize(&mut packet_buffer).unwrap();
+        for question in &questions {
+            question.serialize(&mut packet_buffer).unwrap();
+        }
 
         packet_buffer.pos = 0;
 
-        let dns_query = DnsQuery::deserialize(&mut packet_buffer, 3).unwrap();
+        let parsed: Vec<Question> = (0..3)
+            .map(|_| Question::deserialize(&mut packet_buffer).unwrap())
+            .collect();
 
-        assert_eq!(
-            dns_query.domain_names,
-            vec![
-                "goole.com".to_string(),
-                "images.google.com".to_string(),
-                "www.images.google.com".to_string()
-            ]
-        );
-        assert_eq!(dns_query.qtype, 1);
-        assert_eq!(dns_query.qclass, Class::IN);
+        assert_eq!(parsed, questions);
     }
 }
@@ -1,12 +1,15 @@
-use super::Result;
+use std::fmt;
+
+use crate::errors::{BufferError, ParseError};
 
 use super::{
-    dns_record_type::{Class, DnsRecordType},
+    dns_record_type::{Class, DnsRecordType, RecordType},
+    domain_name::to_fqdn,
     packet_buffer::PacketBuffer,
 };
 
-#[derive(Debug)]
-#[allow(dead_code)]
+/// A resource record as found in the answer, authority and additional sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsRecord {
     record: String,
     response_class: Class,
@@ -23,30 +26,70 @@ impl DnsRecord {
             rdata,
         }
     }
-    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self> {
+
+    /// The owner name of the record.
+    pub fn record(&self) -> &str {
+        &self.record
+    }
+
+    pub fn response_class(&self) -> Class {
+        self.response_class
+    }
+
+    /// Time to live in seconds.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    pub fn rdata(&self) -> &DnsRecordType {
+        &self.rdata
+    }
+
+    /// Returns the same record with a different TTL.
+    pub fn with_ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
         let domain_name = packet_buffer.read_qname()?;
 
-        let type_id = packet_buffer.read_u16()?;
+        let record_type = RecordType::from(packet_buffer.read_u16()?);
         let response_class = Class::deserialize(packet_buffer)?;
         let ttl = packet_buffer.read_u32()?;
         let rdlength = packet_buffer.read_u16()?;
 
         let rdata: DnsRecordType =
-            DnsRecordType::deserialize(packet_buffer, type_id, rdlength)?;
+            DnsRecordType::deserialize(packet_buffer, record_type, rdlength)?;
 
-        return Ok(DnsRecord::new(domain_name, response_class, ttl, rdata));
+        Ok(DnsRecord::new(domain_name, response_class, ttl, rdata))
     }
 
-    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<()> {
-        packet_buffer.write_qname(&self.record);
+    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        packet_buffer.write_qname(&self.record)?;
 
-        packet_buffer.write_u16(self.rdata.get_type());
+        packet_buffer.write_u16(self.rdata.get_type().into())?;
 
-        packet_buffer.write_u16(self.response_class.into());
-        packet_buffer.write_u32(self.ttl);
+        packet_buffer.write_u16(self.response_class.into())?;
+        packet_buffer.write_u32(self.ttl)?;
 
         self.rdata.serialize(packet_buffer)?;
 
-        return Ok(());
+        Ok(())
+    }
+}
+
+/// The record as a zone file line: owner, TTL, class, type and RDATA.
+impl fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            to_fqdn(&self.record),
+            self.ttl,
+            self.response_class,
+            self.rdata.get_type(),
+            self.rdata
+        )
     }
 }
@@ -1,10 +1,203 @@
+use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
-use super::Result;
+use crate::errors::{BufferError, ParseError};
 
+use super::domain_name::to_fqdn;
+use super::encoding::{to_base32hex, to_base64, to_hex, to_timestamp};
 use super::packet_buffer::PacketBuffer;
+use super::svcb::SvcParam;
+use super::type_bitmap;
 
+/// The TYPE code of a resource record or QTYPE of a question.
+///
+/// Codes without a variant of their own are kept as `Unknown` so they can be
+/// passed along untouched (RFC 3597).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum RecordType {
+    A,
+    NS,
+    CNAME,
+    SOA,
+    PTR,
+    MX,
+    TXT,
+    AAAA,
+    HINFO,
+    SRV,
+    NAPTR,
+    OPT,
+    DS,
+    SSHFP,
+    RRSIG,
+    NSEC,
+    DNSKEY,
+    NSEC3,
+    NSEC3PARAM,
+    TLSA,
+    CDS,
+    CDNSKEY,
+    SVCB,
+    HTTPS,
+    ANY,
+    CAA,
+    Unknown(u16),
+}
+
+impl From<u16> for RecordType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => RecordType::A,
+            2 => RecordType::NS,
+            5 => RecordType::CNAME,
+            6 => RecordType::SOA,
+            12 => RecordType::PTR,
+            15 => RecordType::MX,
+            16 => RecordType::TXT,
+            13 => RecordType::HINFO,
+            28 => RecordType::AAAA,
+            33 => RecordType::SRV,
+            35 => RecordType::NAPTR,
+            41 => RecordType::OPT,
+            43 => RecordType::DS,
+            44 => RecordType::SSHFP,
+            46 => RecordType::RRSIG,
+            47 => RecordType::NSEC,
+            48 => RecordType::DNSKEY,
+            50 => RecordType::NSEC3,
+            51 => RecordType::NSEC3PARAM,
+            52 => RecordType::TLSA,
+            59 => RecordType::CDS,
+            60 => RecordType::CDNSKEY,
+            64 => RecordType::SVCB,
+            65 => RecordType::HTTPS,
+            255 => RecordType::ANY,
+            257 => RecordType::CAA,
+            _ => RecordType::Unknown(value),
+        }
+    }
+}
+
+impl From<RecordType> for u16 {
+    fn from(record_type: RecordType) -> u16 {
+        match record_type {
+            RecordType::A => 1,
+            RecordType::NS => 2,
+            RecordType::CNAME => 5,
+            RecordType::SOA => 6,
+            RecordType::PTR => 12,
+            RecordType::MX => 15,
+            RecordType::TXT => 16,
+            RecordType::HINFO => 13,
+            RecordType::AAAA => 28,
+            RecordType::SRV => 33,
+            RecordType::NAPTR => 35,
+            RecordType::OPT => 41,
+            RecordType::DS => 43,
+            RecordType::SSHFP => 44,
+            RecordType::RRSIG => 46,
+            RecordType::NSEC => 47,
+            RecordType::DNSKEY => 48,
+            RecordType::NSEC3 => 50,
+            RecordType::NSEC3PARAM => 51,
+            RecordType::TLSA => 52,
+            RecordType::CDS => 59,
+            RecordType::CDNSKEY => 60,
+            RecordType::SVCB => 64,
+            RecordType::HTTPS => 65,
+            RecordType::ANY => 255,
+            RecordType::CAA => 257,
+            RecordType::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordType::A => write!(f, "A"),
+            RecordType::NS => write!(f, "NS"),
+            RecordType::CNAME => write!(f, "CNAME"),
+            RecordType::SOA => write!(f, "SOA"),
+            RecordType::PTR => write!(f, "PTR"),
+            RecordType::MX => write!(f, "MX"),
+            RecordType::TXT => write!(f, "TXT"),
+            RecordType::HINFO => write!(f, "HINFO"),
+            RecordType::AAAA => write!(f, "AAAA"),
+            RecordType::SRV => write!(f, "SRV"),
+            RecordType::NAPTR => write!(f, "NAPTR"),
+            RecordType::OPT => write!(f, "OPT"),
+            RecordType::DS => write!(f, "DS"),
+            RecordType::SSHFP => write!(f, "SSHFP"),
+            RecordType::RRSIG => write!(f, "RRSIG"),
+            RecordType::NSEC => write!(f, "NSEC"),
+            RecordType::DNSKEY => write!(f, "DNSKEY"),
+            RecordType::NSEC3 => write!(f, "NSEC3"),
+            RecordType::NSEC3PARAM => write!(f, "NSEC3PARAM"),
+            RecordType::TLSA => write!(f, "TLSA"),
+            RecordType::CDS => write!(f, "CDS"),
+            RecordType::CDNSKEY => write!(f, "CDNSKEY"),
+            RecordType::SVCB => write!(f, "SVCB"),
+            RecordType::HTTPS => write!(f, "HTTPS"),
+            RecordType::ANY => write!(f, "ANY"),
+            RecordType::CAA => write!(f, "CAA"),
+            RecordType::Unknown(value) => write!(f, "TYPE{}", value),
+        }
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = String;
+
+    /// Parses a mnemonic like `AAAA` or the generic `TYPE28` form, ignoring case.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let upper = text.to_ascii_uppercase();
+        if let Some(code) = upper.strip_prefix("TYPE") {
+            return code
+                .parse::<u16>()
+                .map(RecordType::from)
+                .map_err(|_| format!("Invalid record type {}", text));
+        }
+
+        let record_type = match upper.as_str() {
+            "A" => RecordType::A,
+            "NS" => RecordType::NS,
+            "CNAME" => RecordType::CNAME,
+            "SOA" => RecordType::SOA,
+            "PTR" => RecordType::PTR,
+            "MX" => RecordType::MX,
+            "TXT" => RecordType::TXT,
+            "HINFO" => RecordType::HINFO,
+            "AAAA" => RecordType::AAAA,
+            "SRV" => RecordType::SRV,
+            "NAPTR" => RecordType::NAPTR,
+            "OPT" => RecordType::OPT,
+            "DS" => RecordType::DS,
+            "SSHFP" => RecordType::SSHFP,
+            "RRSIG" => RecordType::RRSIG,
+            "NSEC" => RecordType::NSEC,
+            "DNSKEY" => RecordType::DNSKEY,
+            "NSEC3" => RecordType::NSEC3,
+            "NSEC3PARAM" => RecordType::NSEC3PARAM,
+            "TLSA" => RecordType::TLSA,
+            "CDS" => RecordType::CDS,
+            "CDNSKEY" => RecordType::CDNSKEY,
+            "SVCB" => RecordType::SVCB,
+            "HTTPS" => RecordType::HTTPS,
+            "ANY" => RecordType::ANY,
+            "CAA" => RecordType::CAA,
+            _ => return Err(format!("Unknown record type {}", text)),
+        };
+
+        Ok(record_type)
+    }
+}
+
+/// The typed RDATA of a resource record.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum DnsRecordType {
     A {
         address: Ipv4Addr,
@@ -31,22 +224,175 @@ pub enum DnsRecordType {
         priority: u16,
         exchange: String,
     },
+    /// One or more <character-string>s, which may hold arbitrary bytes.
     TXT {
-        text: String,
+        strings: Vec<Vec<u8>>,
     },
     AAAA {
         address: Ipv6Addr,
     },
+    HINFO {
+        cpu: Vec<u8>,
+        os: Vec<u8>,
+    },
+    /// Service location (RFC 2782).
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// Naming authority pointer (RFC 3403).
+    NAPTR {
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: String,
+    },
+    /// SSH host key fingerprint (RFC 4255).
+    SSHFP {
+        algorithm: u8,
+        fingerprint_type: u8,
+        fingerprint: Vec<u8>,
+    },
+    /// Delegation signer, the digest of a child zone's DNSKEY (RFC 4034 5).
+    DS {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+    },
+    /// Signature over the RRset of `type_covered` at the owner name (RFC 4034 3).
+    RRSIG {
+        type_covered: RecordType,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        /// Seconds since the epoch, in serial number arithmetic.
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: String,
+        signature: Vec<u8>,
+    },
+    /// Authenticated denial of existence: the next owner name in the zone and
+    /// the types present at this one (RFC 4034 4).
+    NSEC {
+        next_domain_name: String,
+        types: Vec<RecordType>,
+    },
+    /// Public key of a zone (RFC 4034 2).
+    DNSKEY {
+        flags: u16,
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>,
+    },
+    /// NSEC with hashed owner names (RFC 5155 3).
+    NSEC3 {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed_owner: Vec<u8>,
+        types: Vec<RecordType>,
+    },
+    /// The hash parameters a zone uses for its NSEC3 records (RFC 5155 4).
+    NSEC3PARAM {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+    },
+    /// A DS record the child zone wants in its parent (RFC 7344 3.1).
+    CDS {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+    },
+    /// A DNSKEY the child zone wants its parent to make a DS from (RFC 7344 3.2).
+    CDNSKEY {
+        flags: u16,
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>,
+    },
+    /// DANE certificate association (RFC 6698).
+    TLSA {
+        certificate_usage: u8,
+        selector: u8,
+        matching_type: u8,
+        certificate_data: Vec<u8>,
+    },
+    /// Service binding (RFC 9460). A priority of 0 makes it an alias to `target`.
+    SVCB {
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+    },
+    /// SVCB for HTTPS origins, with the same RDATA.
+    HTTPS {
+        priority: u16,
+        target: String,
+        params: Vec<SvcParam>,
+    },
+    /// Certification authority authorization (RFC 8659).
+    CAA {
+        flags: u8,
+        tag: String,
+        value: Vec<u8>,
+    },
+    /// RDATA of a type this crate doesn't interpret, kept as raw bytes.
+    Unknown {
+        type_code: u16,
+        rdata: Vec<u8>,
+    },
 }
 
 impl DnsRecordType {
+    /// A TXT record holding `value`, split in as many 255 byte strings as needed.
+    pub fn txt(value: &[u8]) -> Self {
+        DnsRecordType::TXT { strings: split_character_strings(value) }
+    }
+
+    /// Reads `rdlength` bytes of RDATA, which have to hold exactly one value
+    /// of `record_type`. Nothing past the RDATA is read, even if the fields
+    /// don't fit in it.
     pub fn deserialize(
         packet_buffer: &mut PacketBuffer,
-        type_id: u16,
+        record_type: RecordType,
         rdlength: u16,
-    ) -> Result<Self> {
-        match type_id {
-            1 => {
+    ) -> Result<Self, ParseError> {
+        let position = packet_buffer.pos;
+        let end = position + rdlength as usize;
+        let bad_rdlength = ParseError::BadRdLength { position, rdlength };
+        if end > packet_buffer.buffer.len() {
+            return Err(bad_rdlength);
+        }
+
+        let rdata = packet_buffer
+            .read_within(end, |packet_buffer| DnsRecordType::read_rdata(packet_buffer, record_type, end))
+            .map_err(|e| match e {
+                ParseError::Truncated { position } if position >= end => bad_rdlength,
+                _ => e,
+            })?;
+        if packet_buffer.pos != end {
+            return Err(bad_rdlength);
+        }
+
+        Ok(rdata)
+    }
+
+    /// Reads the fields of `record_type` from RDATA that ends at `end`.
+    fn read_rdata(packet_buffer: &mut PacketBuffer, record_type: RecordType, end: usize) -> Result<Self, ParseError> {
+        // Bytes of RDATA left after the fixed size fields have been read.
+        let remaining = |packet_buffer: &PacketBuffer| end - packet_buffer.pos;
+
+        match record_type {
+            RecordType::A => {
                 let address = Ipv4Addr::new(
                     packet_buffer.read()?,
                     packet_buffer.read()?,
@@ -55,15 +401,15 @@ impl DnsRecordType {
                 );
                 Ok(DnsRecordType::A { address })
             }
-            2 => {
+            RecordType::NS => {
                 let name_server = packet_buffer.read_qname()?;
                 Ok(DnsRecordType::NS { name_server })
             }
-            5 => {
+            RecordType::CNAME => {
                 let canonical_name = packet_buffer.read_qname()?;
                 Ok(DnsRecordType::CNAME { canonical_name })
             }
-            6 => {
+            RecordType::SOA => {
                 let mname = packet_buffer.read_qname()?;
                 let rname = packet_buffer.read_qname()?;
                 let serial = packet_buffer.read_u32()?;
@@ -72,7 +418,7 @@ impl DnsRecordType {
                 let expire = packet_buffer.read_u32()?;
                 let minimum = packet_buffer.read_u32()?;
 
-                return Ok(Self::SOA {
+                Ok(Self::SOA {
                     mname,
                     rname,
                     serial,
@@ -80,24 +426,25 @@ impl DnsRecordType {
                     retry,
                     expire,
                     minimum,
-                });
+                })
             },
-            12 => {
+            RecordType::PTR => {
                 let domain_name = packet_buffer.read_qname()?;
                 Ok(DnsRecordType::PTR { domain_name })
             }
-            15 => {
+            RecordType::MX => {
                 let priority = packet_buffer.read_u16()?;
                 let exchange = packet_buffer.read_qname()?;
                 Ok(DnsRecordType::MX { priority, exchange })
             }
-            16 => {
-                let text = packet_buffer.read_bytes(rdlength as usize)?;
-                Ok(DnsRecordType::TXT {
-                    text: String::from_utf8(text)?,
-                })
+            RecordType::TXT => {
+                let mut strings = Vec::new();
+                while remaining(packet_buffer) > 0 {
+                    strings.push(packet_buffer.read_character_string()?);
+                }
+                Ok(DnsRecordType::TXT { strings })
             }
-            28 => {
+            RecordType::AAAA => {
                 let address = Ipv6Addr::new(
                     packet_buffer.read_u16()?,
                     packet_buffer.read_u16()?,
@@ -110,40 +457,175 @@ impl DnsRecordType {
                 );
                 Ok(DnsRecordType::AAAA { address })
             }
-            _ => Err("Unknown type".to_string().into()),
+            RecordType::HINFO => {
+                let cpu = packet_buffer.read_character_string()?;
+                let os = packet_buffer.read_character_string()?;
+                Ok(DnsRecordType::HINFO { cpu, os })
+            }
+            RecordType::SRV => {
+                let priority = packet_buffer.read_u16()?;
+                let weight = packet_buffer.read_u16()?;
+                let port = packet_buffer.read_u16()?;
+                let target = packet_buffer.read_qname()?;
+                Ok(DnsRecordType::SRV { priority, weight, port, target })
+            }
+            RecordType::NAPTR => {
+                let order = packet_buffer.read_u16()?;
+                let preference = packet_buffer.read_u16()?;
+                let flags = packet_buffer.read_character_string()?;
+                let services = packet_buffer.read_character_string()?;
+                let regexp = packet_buffer.read_character_string()?;
+                let replacement = packet_buffer.read_qname()?;
+                Ok(DnsRecordType::NAPTR { order, preference, flags, services, regexp, replacement })
+            }
+            RecordType::SSHFP => {
+                let algorithm = packet_buffer.read()?;
+                let fingerprint_type = packet_buffer.read()?;
+                let fingerprint = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                Ok(DnsRecordType::SSHFP { algorithm, fingerprint_type, fingerprint })
+            }
+            RecordType::DS | RecordType::CDS => {
+                let key_tag = packet_buffer.read_u16()?;
+                let algorithm = packet_buffer.read()?;
+                let digest_type = packet_buffer.read()?;
+                let digest = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                match record_type {
+                    RecordType::DS => Ok(DnsRecordType::DS { key_tag, algorithm, digest_type, digest }),
+                    _ => Ok(DnsRecordType::CDS { key_tag, algorithm, digest_type, digest }),
+                }
+            }
+            RecordType::RRSIG => {
+                let type_covered = RecordType::from(packet_buffer.read_u16()?);
+                let algorithm = packet_buffer.read()?;
+                let labels = packet_buffer.read()?;
+                let original_ttl = packet_buffer.read_u32()?;
+                let expiration = packet_buffer.read_u32()?;
+                let inception = packet_buffer.read_u32()?;
+                let key_tag = packet_buffer.read_u16()?;
+                let signer_name = packet_buffer.read_qname()?;
+                let signature = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                Ok(DnsRecordType::RRSIG {
+                    type_covered,
+                    algorithm,
+                    labels,
+                    original_ttl,
+                    expiration,
+                    inception,
+                    key_tag,
+                    signer_name,
+                    signature,
+                })
+            }
+            RecordType::NSEC => {
+                let next_domain_name = packet_buffer.read_qname()?;
+                let types = type_bitmap::deserialize(packet_buffer, end)?;
+                Ok(DnsRecordType::NSEC { next_domain_name, types })
+            }
+            RecordType::DNSKEY | RecordType::CDNSKEY => {
+                let flags = packet_buffer.read_u16()?;
+                let protocol = packet_buffer.read()?;
+                let algorithm = packet_buffer.read()?;
+                let public_key = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                match record_type {
+                    RecordType::DNSKEY => Ok(DnsRecordType::DNSKEY { flags, protocol, algorithm, public_key }),
+                    _ => Ok(DnsRecordType::CDNSKEY { flags, protocol, algorithm, public_key }),
+                }
+            }
+            RecordType::NSEC3 => {
+                let hash_algorithm = packet_buffer.read()?;
+                let flags = packet_buffer.read()?;
+                let iterations = packet_buffer.read_u16()?;
+                let salt = packet_buffer.read_character_string()?;
+                let next_hashed_owner = packet_buffer.read_character_string()?;
+                let types = type_bitmap::deserialize(packet_buffer, end)?;
+                Ok(DnsRecordType::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types })
+            }
+            RecordType::NSEC3PARAM => {
+                let hash_algorithm = packet_buffer.read()?;
+                let flags = packet_buffer.read()?;
+                let iterations = packet_buffer.read_u16()?;
+                let salt = packet_buffer.read_character_string()?;
+                Ok(DnsRecordType::NSEC3PARAM { hash_algorithm, flags, iterations, salt })
+            }
+            RecordType::TLSA => {
+                let certificate_usage = packet_buffer.read()?;
+                let selector = packet_buffer.read()?;
+                let matching_type = packet_buffer.read()?;
+                let certificate_data = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                Ok(DnsRecordType::TLSA { certificate_usage, selector, matching_type, certificate_data })
+            }
+            RecordType::SVCB | RecordType::HTTPS => {
+                let priority = packet_buffer.read_u16()?;
+                let target = packet_buffer.read_qname()?;
+                let params = SvcParam::deserialize_all(packet_buffer, end)?;
+                match record_type {
+                    RecordType::SVCB => Ok(DnsRecordType::SVCB { priority, target, params }),
+                    _ => Ok(DnsRecordType::HTTPS { priority, target, params }),
+                }
+            }
+            RecordType::CAA => {
+                let flags = packet_buffer.read()?;
+                let position = packet_buffer.pos;
+                let tag = String::from_utf8(packet_buffer.read_character_string()?)
+                    .map_err(|_| ParseError::InvalidField { position, field: "CAA tag" })?;
+                let value = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                Ok(DnsRecordType::CAA { flags, tag, value })
+            }
+            _ => {
+                let rdata = packet_buffer.read_bytes(remaining(packet_buffer))?;
+                Ok(DnsRecordType::Unknown { type_code: record_type.into(), rdata })
+            }
         }
     }
 
-    pub fn get_type(&self) -> u16 {
+    /// The TYPE of this record.
+    pub fn get_type(&self) -> RecordType {
         match self {
-            DnsRecordType::A { .. } => 1,
-            DnsRecordType::NS { .. } => 2,
-            DnsRecordType::CNAME { .. } => 5,
-            DnsRecordType::SOA { .. } => 6,
-            DnsRecordType::PTR { .. } => 12,
-            DnsRecordType::MX { .. } => 15,
-            DnsRecordType::TXT { .. } => 16,
-            DnsRecordType::AAAA { .. } => 28,
+            DnsRecordType::A { .. } => RecordType::A,
+            DnsRecordType::NS { .. } => RecordType::NS,
+            DnsRecordType::CNAME { .. } => RecordType::CNAME,
+            DnsRecordType::SOA { .. } => RecordType::SOA,
+            DnsRecordType::PTR { .. } => RecordType::PTR,
+            DnsRecordType::MX { .. } => RecordType::MX,
+            DnsRecordType::TXT { .. } => RecordType::TXT,
+            DnsRecordType::AAAA { .. } => RecordType::AAAA,
+            DnsRecordType::HINFO { .. } => RecordType::HINFO,
+            DnsRecordType::SRV { .. } => RecordType::SRV,
+            DnsRecordType::NAPTR { .. } => RecordType::NAPTR,
+            DnsRecordType::DS { .. } => RecordType::DS,
+            DnsRecordType::SSHFP { .. } => RecordType::SSHFP,
+            DnsRecordType::RRSIG { .. } => RecordType::RRSIG,
+            DnsRecordType::NSEC { .. } => RecordType::NSEC,
+            DnsRecordType::DNSKEY { .. } => RecordType::DNSKEY,
+            DnsRecordType::NSEC3 { .. } => RecordType::NSEC3,
+            DnsRecordType::NSEC3PARAM { .. } => RecordType::NSEC3PARAM,
+            DnsRecordType::TLSA { .. } => RecordType::TLSA,
+            DnsRecordType::CDS { .. } => RecordType::CDS,
+            DnsRecordType::CDNSKEY { .. } => RecordType::CDNSKEY,
+            DnsRecordType::SVCB { .. } => RecordType::SVCB,
+            DnsRecordType::HTTPS { .. } => RecordType::HTTPS,
+            DnsRecordType::CAA { .. } => RecordType::CAA,
+            DnsRecordType::Unknown { type_code, .. } => RecordType::from(*type_code),
         }
     }
 
-    pub(crate) fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<()> {
+    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
 
         let start_pos = packet_buffer.pos;
-        packet_buffer.write_u16(0); // Placeholder for length
+        packet_buffer.write_u16(0)?; // Placeholder for length
 
         match self {
             DnsRecordType::A { address } => {
-                packet_buffer.write(address.octets()[0]);
-                packet_buffer.write(address.octets()[1]);
-                packet_buffer.write(address.octets()[2]);
-                packet_buffer.write(address.octets()[3]);
+                packet_buffer.write(address.octets()[0])?;
+                packet_buffer.write(address.octets()[1])?;
+                packet_buffer.write(address.octets()[2])?;
+                packet_buffer.write(address.octets()[3])?;
             }
             DnsRecordType::NS { name_server } => {
-                packet_buffer.write_qname(name_server);
+                packet_buffer.write_qname(name_server)?;
             }
             DnsRecordType::CNAME { canonical_name } => {
-                packet_buffer.write_qname(canonical_name);
+                packet_buffer.write_qname(canonical_name)?;
             }
             DnsRecordType::SOA {
                 mname,
@@ -154,33 +636,136 @@ impl DnsRecordType {
                 expire,
                 minimum,
             } => {
-                packet_buffer.write_qname(mname);
-                packet_buffer.write_qname(rname);
-                packet_buffer.write_u32(*serial);
-                packet_buffer.write_u32(*refresh);
-                packet_buffer.write_u32(*retry);
-                packet_buffer.write_u32(*expire);
-                packet_buffer.write_u32(*minimum);
+                packet_buffer.write_qname(mname)?;
+                packet_buffer.write_qname(rname)?;
+                packet_buffer.write_u32(*serial)?;
+                packet_buffer.write_u32(*refresh)?;
+                packet_buffer.write_u32(*retry)?;
+                packet_buffer.write_u32(*expire)?;
+                packet_buffer.write_u32(*minimum)?;
             },
             DnsRecordType::PTR { domain_name } => {
-                packet_buffer.write_qname(domain_name);
+                packet_buffer.write_qname(domain_name)?;
             }
             DnsRecordType::MX { priority, exchange } => {
-                packet_buffer.write_u16(*priority);
-                packet_buffer.write_qname(exchange);
+                packet_buffer.write_u16(*priority)?;
+                packet_buffer.write_qname(exchange)?;
             }
-            DnsRecordType::TXT { text } => {
-                packet_buffer.write_bytes(text.as_bytes().to_vec());
+            DnsRecordType::TXT { strings } => {
+                // The RDATA can't be empty, so no strings is sent as one empty string.
+                if strings.is_empty() {
+                    packet_buffer.write_character_string(&[])?;
+                }
+                for string in strings.iter().flat_map(|string| split_character_strings(string)) {
+                    packet_buffer.write_character_string(&string)?;
+                }
             }
             DnsRecordType::AAAA { address } => {
-                packet_buffer.write_u16(address.segments()[0]);
-                packet_buffer.write_u16(address.segments()[1]);
-                packet_buffer.write_u16(address.segments()[2]);
-                packet_buffer.write_u16(address.segments()[3]);
-                packet_buffer.write_u16(address.segments()[4]);
-                packet_buffer.write_u16(address.segments()[5]);
-                packet_buffer.write_u16(address.segments()[6]);
-                packet_buffer.write_u16(address.segments()[7]);
+                packet_buffer.write_u16(address.segments()[0])?;
+                packet_buffer.write_u16(address.segments()[1])?;
+                packet_buffer.write_u16(address.segments()[2])?;
+                packet_buffer.write_u16(address.segments()[3])?;
+                packet_buffer.write_u16(address.segments()[4])?;
+                packet_buffer.write_u16(address.segments()[5])?;
+                packet_buffer.write_u16(address.segments()[6])?;
+                packet_buffer.write_u16(address.segments()[7])?;
+            }
+            DnsRecordType::HINFO { cpu, os } => {
+                packet_buffer.write_character_string(cpu)?;
+                packet_buffer.write_character_string(os)?;
+            }
+            // RFC 2782 and RFC 3403 forbid compressing the target names.
+            DnsRecordType::SRV { priority, weight, port, target } => {
+                packet_buffer.write_u16(*priority)?;
+                packet_buffer.write_u16(*weight)?;
+                packet_buffer.write_u16(*port)?;
+                packet_buffer.write_qname_uncompressed(target)?;
+            }
+            DnsRecordType::NAPTR { order, preference, flags, services, regexp, replacement } => {
+                packet_buffer.write_u16(*order)?;
+                packet_buffer.write_u16(*preference)?;
+                packet_buffer.write_character_string(flags)?;
+                packet_buffer.write_character_string(services)?;
+                packet_buffer.write_character_string(regexp)?;
+                packet_buffer.write_qname_uncompressed(replacement)?;
+            }
+            DnsRecordType::SSHFP { algorithm, fingerprint_type, fingerprint } => {
+                packet_buffer.write(*algorithm)?;
+                packet_buffer.write(*fingerprint_type)?;
+                packet_buffer.write_bytes(fingerprint)?;
+            }
+            DnsRecordType::DS { key_tag, algorithm, digest_type, digest }
+            | DnsRecordType::CDS { key_tag, algorithm, digest_type, digest } => {
+                packet_buffer.write_u16(*key_tag)?;
+                packet_buffer.write(*algorithm)?;
+                packet_buffer.write(*digest_type)?;
+                packet_buffer.write_bytes(digest)?;
+            }
+            // RFC 4034 and RFC 6840 5.1 forbid compressing names in DNSSEC RDATA.
+            DnsRecordType::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+            } => {
+                packet_buffer.write_u16((*type_covered).into())?;
+                packet_buffer.write(*algorithm)?;
+                packet_buffer.write(*labels)?;
+                packet_buffer.write_u32(*original_ttl)?;
+                packet_buffer.write_u32(*expiration)?;
+                packet_buffer.write_u32(*inception)?;
+                packet_buffer.write_u16(*key_tag)?;
+                packet_buffer.write_qname_uncompressed(signer_name)?;
+                packet_buffer.write_bytes(signature)?;
+            }
+            DnsRecordType::NSEC { next_domain_name, types } => {
+                packet_buffer.write_qname_uncompressed(next_domain_name)?;
+                type_bitmap::serialize(types, packet_buffer)?;
+            }
+            DnsRecordType::DNSKEY { flags, protocol, algorithm, public_key }
+            | DnsRecordType::CDNSKEY { flags, protocol, algorithm, public_key } => {
+                packet_buffer.write_u16(*flags)?;
+                packet_buffer.write(*protocol)?;
+                packet_buffer.write(*algorithm)?;
+                packet_buffer.write_bytes(public_key)?;
+            }
+            DnsRecordType::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                packet_buffer.write(*hash_algorithm)?;
+                packet_buffer.write(*flags)?;
+                packet_buffer.write_u16(*iterations)?;
+                packet_buffer.write_character_string(salt)?;
+                packet_buffer.write_character_string(next_hashed_owner)?;
+                type_bitmap::serialize(types, packet_buffer)?;
+            }
+            DnsRecordType::NSEC3PARAM { hash_algorithm, flags, iterations, salt } => {
+                packet_buffer.write(*hash_algorithm)?;
+                packet_buffer.write(*flags)?;
+                packet_buffer.write_u16(*iterations)?;
+                packet_buffer.write_character_string(salt)?;
+            }
+            DnsRecordType::TLSA { certificate_usage, selector, matching_type, certificate_data } => {
+                packet_buffer.write(*certificate_usage)?;
+                packet_buffer.write(*selector)?;
+                packet_buffer.write(*matching_type)?;
+                packet_buffer.write_bytes(certificate_data)?;
+            }
+            DnsRecordType::SVCB { priority, target, params } | DnsRecordType::HTTPS { priority, target, params } => {
+                packet_buffer.write_u16(*priority)?;
+                packet_buffer.write_qname_uncompressed(target)?;
+                SvcParam::serialize_all(params, packet_buffer)?;
+            }
+            DnsRecordType::CAA { flags, tag, value } => {
+                packet_buffer.write(*flags)?;
+                packet_buffer.write_character_string(tag.as_bytes())?;
+                packet_buffer.write_bytes(value)?;
+            }
+            DnsRecordType::Unknown { rdata, .. } => {
+                packet_buffer.write_bytes(rdata)?;
             }
         }
 
@@ -188,47 +773,460 @@ impl DnsRecordType {
         let length = end_pos - start_pos - 2; // <--- A piti le da toc :D 
 
         packet_buffer.seek(start_pos);
-        packet_buffer.write_u16(length as u16);
+        packet_buffer.write_u16(length as u16)?;
         packet_buffer.seek(end_pos);
-        
-        return Ok(());
+
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Presentation format of the RDATA, as used in zone files.
+impl fmt::Display for DnsRecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnsRecordType::A { address } => write!(f, "{}", address),
+            DnsRecordType::NS { name_server } => write!(f, "{}", to_fqdn(name_server)),
+            DnsRecordType::CNAME { canonical_name } => write!(f, "{}", to_fqdn(canonical_name)),
+            DnsRecordType::SOA { mname, rname, serial, refresh, retry, expire, minimum } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                to_fqdn(mname),
+                to_fqdn(rname),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum
+            ),
+            DnsRecordType::PTR { domain_name } => write!(f, "{}", to_fqdn(domain_name)),
+            DnsRecordType::MX { priority, exchange } => write!(f, "{} {}", priority, to_fqdn(exchange)),
+            DnsRecordType::TXT { strings } => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write_quoted(f, string)?;
+                }
+                Ok(())
+            }
+            DnsRecordType::AAAA { address } => write!(f, "{}", address),
+            DnsRecordType::HINFO { cpu, os } => {
+                write_quoted(f, cpu)?;
+                write!(f, " ")?;
+                write_quoted(f, os)
+            }
+            DnsRecordType::SRV { priority, weight, port, target } => {
+                write!(f, "{} {} {} {}", priority, weight, port, to_fqdn(target))
+            }
+            DnsRecordType::NAPTR { order, preference, flags, services, regexp, replacement } => {
+                write!(f, "{} {} ", order, preference)?;
+                for text in [flags, services, regexp] {
+                    write_quoted(f, text)?;
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", to_fqdn(replacement))
+            }
+            DnsRecordType::SSHFP { algorithm, fingerprint_type, fingerprint } => {
+                write!(f, "{} {} {}", algorithm, fingerprint_type, to_hex(fingerprint))
+            }
+            DnsRecordType::DS { key_tag, algorithm, digest_type, digest }
+            | DnsRecordType::CDS { key_tag, algorithm, digest_type, digest } => {
+                write!(f, "{} {} {} {}", key_tag, algorithm, digest_type, to_hex(digest))
+            }
+            DnsRecordType::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {} {} {}",
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                to_timestamp(*expiration),
+                to_timestamp(*inception),
+                key_tag,
+                to_fqdn(signer_name),
+                to_base64(signature)
+            ),
+            DnsRecordType::NSEC { next_domain_name, types } => {
+                write!(f, "{}", to_fqdn(next_domain_name))?;
+                write_types(f, types)
+            }
+            DnsRecordType::DNSKEY { flags, protocol, algorithm, public_key }
+            | DnsRecordType::CDNSKEY { flags, protocol, algorithm, public_key } => {
+                write!(f, "{} {} {} {}", flags, protocol, algorithm, to_base64(public_key))
+            }
+            DnsRecordType::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                write_salt(f, salt)?;
+                write!(f, " {}", to_base32hex(next_hashed_owner))?;
+                write_types(f, types)
+            }
+            DnsRecordType::NSEC3PARAM { hash_algorithm, flags, iterations, salt } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                write_salt(f, salt)
+            }
+            DnsRecordType::TLSA { certificate_usage, selector, matching_type, certificate_data } => write!(
+                f,
+                "{} {} {} {}",
+                certificate_usage,
+                selector,
+                matching_type,
+                to_hex(certificate_data)
+            ),
+            DnsRecordType::SVCB { priority, target, params } | DnsRecordType::HTTPS { priority, target, params } => {
+                write!(f, "{} {}", priority, to_fqdn(target))?;
+                for param in params {
+                    write!(f, " {}", param)?;
+                }
+                Ok(())
+            }
+            DnsRecordType::CAA { flags, tag, value } => {
+                write!(f, "{} {} ", flags, tag)?;
+                write_quoted(f, value)
+            }
+            // RFC 3597 5: \# followed by the length and the RDATA in hex.
+            DnsRecordType::Unknown { rdata, .. } => {
+                write!(f, "\\# {}", rdata.len())?;
+                if !rdata.is_empty() {
+                    write!(f, " {}", to_hex(rdata))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes the types of an NSEC or NSEC3 type bitmap, each preceded by a space.
+fn write_types(f: &mut fmt::Formatter, types: &[RecordType]) -> fmt::Result {
+    for record_type in types {
+        write!(f, " {}", record_type)?;
+    }
+    Ok(())
+}
+
+/// Writes an NSEC3 salt in hex, or `-` if there is none (RFC 5155 3.3).
+fn write_salt(f: &mut fmt::Formatter, salt: &[u8]) -> fmt::Result {
+    match salt.is_empty() {
+        true => write!(f, "-"),
+        false => write!(f, "{}", to_hex(salt)),
+    }
+}
+
+/// Cuts `value` into chunks that fit in a <character-string>, keeping at
+/// least one (possibly empty) string.
+pub(crate) fn split_character_strings(value: &[u8]) -> Vec<Vec<u8>> {
+    if value.is_empty() {
+        return vec![Vec::new()];
+    }
+
+    value.chunks(255).map(<[u8]>::to_vec).collect()
+}
+
+/// Writes a character-string in quotes, escaping what a zone file can't hold as is.
+fn write_quoted(f: &mut fmt::Formatter, text: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+    for &byte in text {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+            0x20..=0x7E => write!(f, "{}", byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// The CLASS of a question or resource record.
+///
+/// Like [`RecordType`], classes without a variant are kept as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Class {
-    IN = 1,
-    CS = 2,
-    CH = 3,
-    HS = 4,
+    IN,
+    CS,
+    CH,
+    HS,
+    /// Only used in dynamic updates (RFC 2136 2.4).
+    NONE,
+    /// Only used in questions, matching every class.
+    ANY,
+    Unknown(u16),
 }
 
-impl Class {
-    pub fn from_u16(value: u16) -> Result<Self> {
+impl From<u16> for Class {
+    fn from(value: u16) -> Self {
         match value {
-            1 => Ok(Class::IN),
-            2 => Ok(Class::CS),
-            3 => Ok(Class::CH),
-            4 => Ok(Class::HS),
-            _ => Err("Unknown class".to_string().into()),
+            1 => Class::IN,
+            2 => Class::CS,
+            3 => Class::CH,
+            4 => Class::HS,
+            254 => Class::NONE,
+            255 => Class::ANY,
+            _ => Class::Unknown(value),
         }
     }
 }
 
-impl Into<u16> for Class {
-    fn into(self) -> u16 {
-        match self {
+impl From<Class> for u16 {
+    fn from(class: Class) -> u16 {
+        match class {
             Class::IN => 1,
             Class::CS => 2,
             Class::CH => 3,
             Class::HS => 4,
+            Class::NONE => 254,
+            Class::ANY => 255,
+            Class::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Class::IN => write!(f, "IN"),
+            Class::CS => write!(f, "CS"),
+            Class::CH => write!(f, "CH"),
+            Class::HS => write!(f, "HS"),
+            Class::NONE => write!(f, "NONE"),
+            Class::ANY => write!(f, "ANY"),
+            Class::Unknown(value) => write!(f, "CLASS{}", value),
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = String;
+
+    /// Parses a mnemonic like `IN` or the generic `CLASS1` form, ignoring case.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let upper = text.to_ascii_uppercase();
+        if let Some(code) = upper.strip_prefix("CLASS") {
+            return code
+                .parse::<u16>()
+                .map(Class::from)
+                .map_err(|_| format!("Invalid class {}", text));
+        }
+
+        match upper.as_str() {
+            "IN" => Ok(Class::IN),
+            "CS" => Ok(Class::CS),
+            "CH" => Ok(Class::CH),
+            "HS" => Ok(Class::HS),
+            "NONE" => Ok(Class::NONE),
+            "ANY" => Ok(Class::ANY),
+            _ => Err(format!("Invalid class {}", text)),
         }
     }
 }
 
 impl Class {
-    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self> {
-        let class = packet_buffer.read_u16()?;
-        Ok(Class::from_u16(class)?)
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
+        Ok(Class::from(packet_buffer.read_u16()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::dns_record::DnsRecord;
+
+    #[test]
+    fn record_types_convert_both_ways() {
+        assert_eq!(RecordType::from(28), RecordType::AAAA);
+        assert_eq!(u16::from(RecordType::Unknown(65)), 65);
+        assert_eq!("aaaa".parse::<RecordType>(), Ok(RecordType::AAAA));
+        assert_eq!("TYPE1".parse::<RecordType>(), Ok(RecordType::A));
+        assert_eq!(RecordType::Unknown(65534).to_string(), "TYPE65534");
+    }
+
+    #[test]
+    fn classes_convert_both_ways() {
+        assert_eq!(Class::from(255), Class::ANY);
+        assert_eq!(Class::from(254), Class::NONE);
+        assert_eq!(u16::from(Class::Unknown(300)), 300);
+        assert_eq!(Class::Unknown(300).to_string(), "CLASS300");
+        assert_eq!("ch".parse::<Class>(), Ok(Class::CH));
+        assert_eq!("CLASS1".parse::<Class>(), Ok(Class::IN));
+        assert!("CLASSX".parse::<Class>().is_err());
+    }
+
+    /// Writes `rdata` in a record owned by `owner` and reads it back.
+    fn round_trip(owner: &str, rdata: DnsRecordType) -> PacketBuffer {
+        let record = DnsRecord::new(owner.to_string(), Class::IN, 60, rdata);
+        let mut buffer = PacketBuffer::new();
+        record.serialize(&mut buffer).unwrap();
+
+        buffer.seek(0);
+        assert_eq!(DnsRecord::deserialize(&mut buffer).unwrap(), record);
+        assert_eq!(buffer.pos, buffer.as_bytes().len());
+
+        buffer
+    }
+
+    #[test]
+    fn service_and_security_records_round_trip() {
+        round_trip("example.com", DnsRecordType::HINFO { cpu: b"x86_64".to_vec(), os: b"Linux".to_vec() });
+        round_trip("example.com", DnsRecordType::SSHFP { algorithm: 4, fingerprint_type: 2, fingerprint: vec![0xAB; 32] });
+        round_trip(
+            "_443._tcp.example.com",
+            DnsRecordType::TLSA { certificate_usage: 3, selector: 1, matching_type: 1, certificate_data: vec![0xCD; 32] },
+        );
+        round_trip("example.com", DnsRecordType::CAA { flags: 128, tag: "issue".to_string(), value: b"ca.example.net".to_vec() });
+    }
+
+    #[test]
+    fn svcb_and_https_round_trip() {
+        let params = vec![
+            SvcParam::Mandatory(vec![1]),
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+            SvcParam::Port(8443),
+            SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+            SvcParam::Ech(vec![0, 69, 254, 13]),
+            SvcParam::Ipv6Hint(vec!["2001:db8::1".parse().unwrap()]),
+            SvcParam::Unknown { key: 65333, value: b"ex".to_vec() },
+        ];
+        let https = DnsRecordType::HTTPS { priority: 1, target: String::new(), params: params.clone() };
+
+        round_trip("example.com", https.clone());
+        round_trip("_8443._foo.example.com", DnsRecordType::SVCB { priority: 0, target: "svc.example.net".to_string(), params: Vec::new() });
+        assert_eq!(
+            https.to_string(),
+            "1 . mandatory=alpn alpn=h2,h3 port=8443 ipv4hint=192.0.2.1 ech=AEX+DQ== ipv6hint=2001:db8::1 key65333=ex"
+        );
+    }
+
+    #[test]
+    fn dnssec_records_round_trip() {
+        let dnskey = DnsRecordType::DNSKEY { flags: 257, protocol: 3, algorithm: 13, public_key: vec![0x5A; 64] };
+        let rrsig = DnsRecordType::RRSIG {
+            type_covered: RecordType::A,
+            algorithm: 13,
+            labels: 2,
+            original_ttl: 3600,
+            expiration: 1_709_251_199,
+            inception: 1_706_745_600,
+            key_tag: 12345,
+            signer_name: "example.com".to_string(),
+            signature: vec![0xA5; 64],
+        };
+        let nsec3 = DnsRecordType::NSEC3 {
+            hash_algorithm: 1,
+            flags: 1,
+            iterations: 0,
+            salt: Vec::new(),
+            next_hashed_owner: vec![0x01; 20],
+            types: vec![RecordType::A, RecordType::RRSIG],
+        };
+
+        round_trip("example.com", dnskey.clone());
+        round_trip("example.com", DnsRecordType::CDNSKEY { flags: 257, protocol: 3, algorithm: 15, public_key: vec![0x5A; 32] });
+        round_trip("example.com", DnsRecordType::CDS { key_tag: 0, algorithm: 0, digest_type: 0, digest: vec![0] });
+        round_trip("example.com", DnsRecordType::DS { key_tag: 20326, algorithm: 8, digest_type: 2, digest: vec![0xE0; 32] });
+        round_trip("www.example.com", rrsig.clone());
+        round_trip(
+            "example.com",
+            DnsRecordType::NSEC { next_domain_name: "www.example.com".to_string(), types: vec![RecordType::NS, RecordType::SOA] },
+        );
+        round_trip("a.example.com", DnsRecordType::NSEC { next_domain_name: "example.com".to_string(), types: Vec::new() });
+        round_trip("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.com", nsec3.clone());
+        round_trip("example.com", DnsRecordType::NSEC3PARAM { hash_algorithm: 1, flags: 0, iterations: 10, salt: vec![0xAA, 0xBB] });
+
+        assert!(dnskey.to_string().starts_with("257 3 13 WlpaWlpa"));
+        assert!(rrsig.to_string().starts_with("A 13 2 3600 20240229235959 20240201000000 12345 example.com. paWl"));
+        assert_eq!(nsec3.to_string(), "1 1 0 - 040G2081040G2081040G2081040G2081 A RRSIG");
+    }
+
+    #[test]
+    fn svc_params_must_be_in_key_order() {
+        // HTTPS 1 . with port (3) written before alpn (1).
+        let rdata = [0, 1, 0, 0, 3, 0, 2, 1, 187, 0, 1, 0, 3, 2, 104, 50];
+        let mut buffer = PacketBuffer::from_bytes(&rdata).unwrap();
+
+        assert!(DnsRecordType::deserialize(&mut buffer, RecordType::HTTPS, rdata.len() as u16).is_err());
+    }
+
+    #[test]
+    fn srv_and_naptr_targets_are_not_compressed() {
+        let target = b"\x03sip\x07example\x03com\x00";
+
+        let buffer = round_trip(
+            "sip.example.com",
+            DnsRecordType::SRV { priority: 10, weight: 60, port: 5060, target: "sip.example.com".to_string() },
+        );
+        assert!(buffer.as_bytes().ends_with(target));
+
+        let buffer = round_trip(
+            "sip.example.com",
+            DnsRecordType::NAPTR {
+                order: 100,
+                preference: 10,
+                flags: b"S".to_vec(),
+                services: b"SIP+D2T".to_vec(),
+                regexp: Vec::new(),
+                replacement: "sip.example.com".to_string(),
+            },
+        );
+        assert!(buffer.as_bytes().ends_with(target));
+    }
+
+    #[test]
+    fn txt_strings_round_trip_losslessly() {
+        let dkim = vec![b'k'; 300];
+        let txt = DnsRecordType::txt(&dkim);
+        assert_eq!(txt, DnsRecordType::TXT { strings: vec![vec![b'k'; 255], vec![b'k'; 45]] });
+
+        round_trip("example.com", txt);
+        round_trip("example.com", DnsRecordType::TXT { strings: vec![b"v=spf1 -all".to_vec(), vec![0xFF, 0x00, b'"']] });
+
+        let buffer = round_trip("example.com", DnsRecordType::TXT { strings: vec![Vec::new()] });
+        assert!(buffer.as_bytes().ends_with(&[0, 1, 0]));
+    }
+
+    #[test]
+    fn long_txt_strings_are_split_when_serialized() {
+        let record = DnsRecord::new("example.com".to_string(), Class::IN, 60, DnsRecordType::TXT { strings: vec![vec![b'a'; 256]] });
+        let mut buffer = PacketBuffer::new();
+        record.serialize(&mut buffer).unwrap();
+
+        buffer.seek(0);
+        let parsed = DnsRecord::deserialize(&mut buffer).unwrap();
+        assert_eq!(parsed.rdata(), &DnsRecordType::TXT { strings: vec![vec![b'a'; 255], vec![b'a']] });
+        assert_eq!(parsed.rdata().to_string(), format!("\"{}\" \"a\"", "a".repeat(255)));
+    }
+
+    #[test]
+    fn txt_presentation_escapes_quotes_and_binary() {
+        let txt = DnsRecordType::TXT { strings: vec![b"say \"hi\"".to_vec(), vec![0x07, b'\\']] };
+        assert_eq!(txt.to_string(), r#""say \"hi\"" "\007\\""#);
+    }
+
+    #[test]
+    fn unknown_rdata_uses_generic_presentation() {
+        let rdata = DnsRecordType::Unknown { type_code: 65534, rdata: vec![0x0A, 0x00, 0xFF] };
+        assert_eq!(rdata.to_string(), "\\# 3 0A00FF");
+
+        let empty = DnsRecordType::Unknown { type_code: 65534, rdata: Vec::new() };
+        assert_eq!(empty.to_string(), "\\# 0");
+    }
+
+    #[test]
+    fn rdata_must_fill_its_rdlength_exactly() {
+        let bad_rdlength = |rdlength: u16, rdata: &[u8]| {
+            let mut buffer = PacketBuffer::from_bytes(rdata).unwrap();
+            DnsRecordType::deserialize(&mut buffer, RecordType::A, rdlength).unwrap_err()
+        };
+
+        assert_eq!(bad_rdlength(2, &[1, 2, 3, 4]), ParseError::BadRdLength { position: 0, rdlength: 2 });
+        assert_eq!(bad_rdlength(5, &[1, 2, 3, 4, 5]), ParseError::BadRdLength { position: 0, rdlength: 5 });
+        assert_eq!(bad_rdlength(8, &[1, 2, 3, 4]), ParseError::BadRdLength { position: 0, rdlength: 8 });
     }
 }
@@ -0,0 +1,96 @@
+//! Helpers for comparing domain names in their presentation form.
+//!
+//! Names are kept the way [`crate::protocol::PacketBuffer::read_qname`]
+//! returns them: labels separated by dots, without the trailing root dot,
+//! with the root itself being the empty string.
+
+use std::cmp::Ordering;
+
+/// Lowercases `name` and strips a trailing dot so names can be compared.
+pub fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `name` is `parent` or lies below it, ignoring case.
+pub fn is_subdomain_of(name: &str, parent: &str) -> bool {
+    let name = name.trim_end_matches('.');
+    let parent = parent.trim_end_matches('.');
+
+    if parent.is_empty() || name.eq_ignore_ascii_case(parent) {
+        return true;
+    }
+
+    // Bytes rather than str slices, the offset may not be a char boundary.
+    let (name, parent) = (name.as_bytes(), parent.as_bytes());
+    name.len() > parent.len()
+        && name[name.len() - parent.len()..].eq_ignore_ascii_case(parent)
+        && name[name.len() - parent.len() - 1] == b'.'
+}
+
+/// The name with its leftmost label removed, or `None` for the root.
+pub fn parent(name: &str) -> Option<&str> {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(name.split_once('.').map_or("", |(_, parent)| parent))
+}
+
+/// The name in absolute form, with its trailing dot.
+pub fn to_fqdn(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+pub fn label_count(name: &str) -> usize {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() { 0 } else { name.split('.').count() }
+}
+
+/// Orders names the way DNSSEC does (RFC 4034 6.1): label by label from the
+/// root, comparing lowercased labels as bytes.
+pub fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    let labels = |name: &str| -> Vec<Vec<u8>> {
+        let name = name.trim_end_matches('.');
+        if name.is_empty() {
+            return Vec::new();
+        }
+        name.rsplit('.').map(|label| label.to_ascii_lowercase().into_bytes()).collect()
+    };
+
+    labels(a).cmp(&labels(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdomains_match_on_label_boundaries() {
+        assert!(is_subdomain_of("www.Example.com", "example.COM"));
+        assert!(is_subdomain_of("example.com", "example.com."));
+        assert!(is_subdomain_of("example.com", ""));
+        assert!(!is_subdomain_of("badexample.com", "example.com"));
+        assert!(!is_subdomain_of("com", "example.com"));
+        assert!(!is_subdomain_of("\u{e9}onsul", "consul"));
+        assert!(is_subdomain_of("\u{e9}.consul", "consul"));
+    }
+
+    #[test]
+    fn parents_walk_up_to_the_root() {
+        assert_eq!(parent("www.example.com"), Some("example.com"));
+        assert_eq!(parent("com"), Some(""));
+        assert_eq!(parent(""), None);
+        assert_eq!(label_count("www.example.com."), 3);
+    }
+
+    #[test]
+    fn canonical_order_matches_rfc_4034() {
+        let ordered = ["example", "a.example", "yljkjljk.a.example", "Z.a.example", "zABC.a.EXAMPLE", "z.example", "*.z.example"];
+
+        for pair in ordered.windows(2) {
+            assert_eq!(canonical_cmp(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(canonical_cmp("A.example", "a.EXAMPLE."), Ordering::Equal);
+    }
+}
@@ -0,0 +1,305 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::errors::{BufferError, ParseError};
+
+use super::packet_buffer::PacketBuffer;
+
+/// TYPE code of the OPT pseudo-record.
+pub const OPT_TYPE: u16 = 41;
+
+/// Payload size tiny_dns advertises and accepts over UDP, as recommended by
+/// DNS flag day 2020 to avoid IP fragmentation.
+pub const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// Upper 8 bits of the BADVERS extended RCODE (16), sent back to clients
+/// using an EDNS version we don't implement.
+pub const BADVERS: u8 = 1;
+
+/// EDNS(0) parameters carried in the OPT pseudo-record (RFC 6891).
+///
+/// An OPT record is stored here instead of in the additional section of
+/// [`crate::protocol::DnsPacket`], since its CLASS and TTL fields don't have
+/// their usual meaning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    pub udp_payload_size: u16,
+    /// Upper 8 bits of the 12 bit RCODE.
+    pub extended_rcode: u8,
+    pub version: u8,
+    /// The DO bit, set by clients that want DNSSEC records (RFC 3225).
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+/// A single option from the RDATA of an OPT record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdnsOption {
+    /// Name server identifier (RFC 5001), empty in requests.
+    Nsid(Vec<u8>),
+    /// Client subnet (RFC 7871).
+    ClientSubnet {
+        source_prefix_length: u8,
+        scope_prefix_length: u8,
+        address: IpAddr,
+    },
+    /// DNS cookie (RFC 7873), the server part is absent in requests.
+    Cookie {
+        client: [u8; 8],
+        server: Option<Vec<u8>>,
+    },
+    /// Idle timeout in units of 100 milliseconds (RFC 7828).
+    TcpKeepalive(Option<u16>),
+    /// Zero filled padding of the given length (RFC 7830).
+    Padding(u16),
+    Unknown {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Edns::new(DEFAULT_UDP_PAYLOAD_SIZE)
+    }
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Self {
+        Edns {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+
+    /// Parses the rest of an OPT record whose owner name and TYPE were
+    /// already read.
+    pub fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
+        let udp_payload_size = packet_buffer.read_u16()?;
+        let ttl = packet_buffer.read_u32()?;
+        let rdlength = packet_buffer.read_u16()? as usize;
+
+        let end = packet_buffer.pos + rdlength;
+        let options = packet_buffer.read_within(end, |packet_buffer| {
+            let mut options = Vec::new();
+            while packet_buffer.pos < end {
+                options.push(EdnsOption::deserialize(packet_buffer)?);
+            }
+
+            Ok(options)
+        })?;
+
+        Ok(Edns {
+            udp_payload_size,
+            extended_rcode: (ttl >> 24) as u8,
+            version: (ttl >> 16) as u8,
+            dnssec_ok: ttl & 0x8000 != 0,
+            options,
+        })
+    }
+
+    /// Writes the whole OPT record, owner name included.
+    pub fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        packet_buffer.write(0)?;
+        packet_buffer.write_u16(OPT_TYPE)?;
+        packet_buffer.write_u16(self.udp_payload_size)?;
+
+        let ttl = (self.extended_rcode as u32) << 24
+            | (self.version as u32) << 16
+            | if self.dnssec_ok { 0x8000 } else { 0 };
+        packet_buffer.write_u32(ttl)?;
+
+        let start_pos = packet_buffer.pos;
+        packet_buffer.write_u16(0)?; // Placeholder for length
+
+        for option in &self.options {
+            option.serialize(packet_buffer)?;
+        }
+
+        let end_pos = packet_buffer.pos;
+        packet_buffer.seek(start_pos);
+        packet_buffer.write_u16((end_pos - start_pos - 2) as u16)?;
+        packet_buffer.seek(end_pos);
+
+        Ok(())
+    }
+
+    /// Size of the serialized OPT record in bytes.
+    pub fn wire_length(&self) -> usize {
+        11 + self.options.iter().map(|option| 4 + option.data_length()).sum::<usize>()
+    }
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => 3,
+            EdnsOption::ClientSubnet { .. } => 8,
+            EdnsOption::Cookie { .. } => 10,
+            EdnsOption::TcpKeepalive(_) => 11,
+            EdnsOption::Padding(_) => 12,
+            EdnsOption::Unknown { code, .. } => *code,
+        }
+    }
+
+    fn deserialize(packet_buffer: &mut PacketBuffer) -> Result<Self, ParseError> {
+        let position = packet_buffer.pos;
+        let code = packet_buffer.read_u16()?;
+        let length = packet_buffer.read_u16()? as usize;
+        let data = packet_buffer.read_bytes(length)?;
+
+        let option = match code {
+            3 => EdnsOption::Nsid(data),
+            8 => {
+                if length < 4 {
+                    return Err(ParseError::InvalidField { position, field: "client subnet option" });
+                }
+                let family = u16::from_be_bytes([data[0], data[1]]);
+                let address = &data[4..];
+                let address = match family {
+                    1 if address.len() <= 4 => {
+                        let mut octets = [0u8; 4];
+                        octets[..address.len()].copy_from_slice(address);
+                        IpAddr::V4(Ipv4Addr::from(octets))
+                    }
+                    2 if address.len() <= 16 => {
+                        let mut octets = [0u8; 16];
+                        octets[..address.len()].copy_from_slice(address);
+                        IpAddr::V6(Ipv6Addr::from(octets))
+                    }
+                    _ => return Err(ParseError::InvalidField { position, field: "client subnet address" }),
+                };
+
+                EdnsOption::ClientSubnet {
+                    source_prefix_length: data[2],
+                    scope_prefix_length: data[3],
+                    address,
+                }
+            }
+            10 => {
+                if length != 8 && !(16..=40).contains(&length) {
+                    return Err(ParseError::InvalidField { position, field: "cookie option" });
+                }
+                let mut client = [0u8; 8];
+                client.copy_from_slice(&data[..8]);
+                let server = if length > 8 { Some(data[8..].to_vec()) } else { None };
+
+                EdnsOption::Cookie { client, server }
+            }
+            11 => match length {
+                0 => EdnsOption::TcpKeepalive(None),
+                2 => EdnsOption::TcpKeepalive(Some(u16::from_be_bytes([data[0], data[1]]))),
+                _ => return Err(ParseError::InvalidField { position, field: "TCP keepalive option" }),
+            },
+            12 => EdnsOption::Padding(length as u16),
+            _ => EdnsOption::Unknown { code, data },
+        };
+
+        Ok(option)
+    }
+
+    fn serialize(&self, packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        packet_buffer.write_u16(self.code())?;
+        packet_buffer.write_u16(self.data_length() as u16)?;
+
+        match self {
+            EdnsOption::Nsid(data) => packet_buffer.write_bytes(data)?,
+            EdnsOption::ClientSubnet { source_prefix_length, scope_prefix_length, address } => {
+                let (family, octets) = match address {
+                    IpAddr::V4(address) => (1, address.octets().to_vec()),
+                    IpAddr::V6(address) => (2, address.octets().to_vec()),
+                };
+                packet_buffer.write_u16(family)?;
+                packet_buffer.write(*source_prefix_length)?;
+                packet_buffer.write(*scope_prefix_length)?;
+                packet_buffer.write_bytes(&octets[..prefix_bytes(*source_prefix_length, octets.len())])?;
+            }
+            EdnsOption::Cookie { client, server } => {
+                packet_buffer.write_bytes(client)?;
+                if let Some(server) = server {
+                    packet_buffer.write_bytes(server)?;
+                }
+            }
+            EdnsOption::TcpKeepalive(timeout) => {
+                if let Some(timeout) = timeout {
+                    packet_buffer.write_u16(*timeout)?;
+                }
+            }
+            EdnsOption::Padding(length) => packet_buffer.write_bytes(&vec![0; *length as usize])?,
+            EdnsOption::Unknown { data, .. } => packet_buffer.write_bytes(data)?,
+        }
+
+        Ok(())
+    }
+
+    fn data_length(&self) -> usize {
+        match self {
+            EdnsOption::Nsid(data) => data.len(),
+            EdnsOption::ClientSubnet { source_prefix_length, address, .. } => {
+                let address_length = if address.is_ipv4() { 4 } else { 16 };
+                4 + prefix_bytes(*source_prefix_length, address_length)
+            }
+            EdnsOption::Cookie { server, .. } => 8 + server.as_ref().map_or(0, Vec::len),
+            EdnsOption::TcpKeepalive(timeout) => if timeout.is_some() { 2 } else { 0 },
+            EdnsOption::Padding(length) => *length as usize,
+            EdnsOption::Unknown { data, .. } => data.len(),
+        }
+    }
+}
+
+/// Number of address bytes needed to hold a prefix of `prefix_length` bits.
+fn prefix_bytes(prefix_length: u8, address_length: usize) -> usize {
+    (prefix_length as usize).div_ceil(8).min(address_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_deserialize_opt_record() {
+        let edns = Edns {
+            udp_payload_size: 4096,
+            extended_rcode: BADVERS,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![
+                EdnsOption::Nsid(Vec::new()),
+                EdnsOption::ClientSubnet {
+                    source_prefix_length: 24,
+                    scope_prefix_length: 0,
+                    address: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)),
+                },
+                EdnsOption::Cookie { client: [1, 2, 3, 4, 5, 6, 7, 8], server: None },
+                EdnsOption::TcpKeepalive(Some(100)),
+                EdnsOption::Padding(5),
+                EdnsOption::Unknown { code: 65001, data: vec![0xDE, 0xAD] },
+            ],
+        };
+
+        let mut packet_buffer = PacketBuffer::new();
+        edns.serialize(&mut packet_buffer).unwrap();
+        assert_eq!(packet_buffer.pos, edns.wire_length());
+
+        packet_buffer.seek(0);
+        assert_eq!(packet_buffer.read_qname().unwrap(), "");
+        assert_eq!(packet_buffer.read_u16().unwrap(), OPT_TYPE);
+        assert_eq!(Edns::deserialize(&mut packet_buffer).unwrap(), edns);
+    }
+
+    #[test]
+    fn client_subnet_only_sends_prefix_bytes() {
+        let option = EdnsOption::ClientSubnet {
+            source_prefix_length: 20,
+            scope_prefix_length: 0,
+            address: IpAddr::V4(Ipv4Addr::new(198, 51, 96, 0)),
+        };
+
+        let mut packet_buffer = PacketBuffer::new();
+        option.serialize(&mut packet_buffer).unwrap();
+
+        assert_eq!(packet_buffer.as_bytes(), &[0, 8, 0, 7, 0, 1, 20, 0, 198, 51, 96]);
+    }
+}
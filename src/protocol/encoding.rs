@@ -0,0 +1,225 @@
+//! Text encodings of binary RDATA used in presentation format.
+
+/// Encodes `bytes` as uppercase hex, the way zone files usually show them.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Decodes hex digits of either case, or returns `None` if `text` isn't valid hex.
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding (RFC 4648 4).
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+/// Decodes padded base64, ignoring whitespace, or returns `None` if `text` isn't valid.
+pub fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let text: Vec<u8> = text.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 != text.len() / 4) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for byte in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET.iter().position(|c| c == byte)? as u32;
+            group = group << 6 | value;
+        }
+        group <<= 6 * padding as u32;
+
+        bytes.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+
+    Some(bytes)
+}
+
+const BASE32HEX_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// Base32 with the extended hex alphabet and no padding, as NSEC3 uses for
+/// hashed owner names (RFC 5155 3.3).
+pub fn to_base32hex(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = buffer << 8 | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            text.push(BASE32HEX_ALPHABET[(buffer >> bits) as usize & 0x1F] as char);
+        }
+    }
+    if bits > 0 {
+        text.push(BASE32HEX_ALPHABET[(buffer << (5 - bits)) as usize & 0x1F] as char);
+    }
+
+    text
+}
+
+/// Decodes base32hex of either case, with or without padding.
+pub fn from_base32hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for c in text.bytes() {
+        let value = BASE32HEX_ALPHABET.iter().position(|a| *a == c.to_ascii_uppercase())? as u16;
+        buffer = buffer << 5 | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    // Whatever is left must be the zero bits completing the last character.
+    if bits >= 5 || buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+
+    Some(bytes)
+}
+
+/// Formats seconds since the epoch as `YYYYMMDDHHmmSS` in UTC, the way
+/// RRSIG validity periods are written (RFC 4034 3.2).
+pub fn to_timestamp(seconds: u32) -> String {
+    let days = seconds as i64 / 86400;
+    let time = seconds % 86400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parses a `YYYYMMDDHHmmSS` timestamp, or plain seconds since the epoch.
+pub fn from_timestamp(text: &str) -> Option<u32> {
+    if text.len() != 14 {
+        return text.parse().ok();
+    }
+    if !text.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    u32::try_from(seconds).ok()
+}
+
+// Conversions between days since 1970-01-01 and proleptic Gregorian dates,
+// from Howard Hinnant's "chrono-Compatible Low-Level Date Algorithms".
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+
+    (year_of_era + era * 400 + (month <= 2) as i64, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(to_hex(&[0x00, 0xAB, 0x0F]), "00AB0F");
+        assert_eq!(from_hex("00ab0F"), Some(vec![0x00, 0xAB, 0x0F]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")];
+
+        for (plain, encoded) in vectors {
+            assert_eq!(to_base64(plain.as_bytes()), encoded);
+            assert_eq!(from_base64(encoded), Some(plain.as_bytes().to_vec()));
+        }
+        assert_eq!(from_base64("Zm9v YmFy"), Some(b"foobar".to_vec()));
+        assert_eq!(from_base64("Zg="), None);
+        assert_eq!(from_base64("Zg==Zm8="), None);
+    }
+
+    #[test]
+    fn base32hex_matches_rfc_4648_vectors() {
+        let vectors = [("", ""), ("f", "CO"), ("fo", "CPNG"), ("foo", "CPNMU"), ("foobar", "CPNMUOJ1E8")];
+
+        for (plain, encoded) in vectors {
+            assert_eq!(to_base32hex(plain.as_bytes()), encoded);
+            assert_eq!(from_base32hex(&encoded.to_lowercase()), Some(plain.as_bytes().to_vec()));
+        }
+        assert_eq!(from_base32hex("CPNMUOG="), Some(b"foob".to_vec()));
+        assert_eq!(from_base32hex("CP"), None);
+        assert_eq!(from_base32hex("W0"), None);
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        assert_eq!(to_timestamp(0), "19700101000000");
+        assert_eq!(to_timestamp(1_709_251_199), "20240229235959");
+        assert_eq!(from_timestamp("20240229235959"), Some(1_709_251_199));
+        assert_eq!(from_timestamp("1709251199"), Some(1_709_251_199));
+        assert_eq!(from_timestamp("20241301000000"), None);
+    }
+}
@@ -1,8 +1,25 @@
+//! DNS wire format types.
+//!
+//! Everything needed to build, parse and serialize DNS messages lives here, so
+//! resolvers and tools built on top of tiny_dns never have to deal with raw
+//! bytes themselves.
+
 pub mod dns_record;
 pub mod dns_header;
 pub mod dns_packet;
 pub mod dns_query;
 pub mod packet_buffer;
 pub mod dns_record_type;
+pub mod domain_name;
+pub mod edns;
+pub mod encoding;
+pub mod svcb;
+pub mod type_bitmap;
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub use dns_header::{DnsHeader, ResponseCode};
+pub use dns_packet::DnsPacket;
+pub use dns_query::Question;
+pub use dns_record::DnsRecord;
+pub use dns_record_type::{Class, DnsRecordType, RecordType};
+pub use edns::{Edns, EdnsOption};
+pub use packet_buffer::PacketBuffer;
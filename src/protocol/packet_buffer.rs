@@ -1,156 +1,376 @@
 use std::collections::HashMap;
 
-use super::Result;
+use crate::errors::{BufferError, ParseError};
 
+/// Largest message that fits in the 16 bit length of a TCP frame.
+pub const MAX_MESSAGE_SIZE: usize = 65535;
+
+/// Longest name on the wire, length octets included (RFC 1035 2.3.4).
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Offsets past this point can't be the target of a compression pointer.
+const MAX_POINTER_TARGET: usize = 0x3FFF;
+
+/// A cursor over the bytes of a DNS message.
+///
+/// The buffer grows as data is written, up to a limit that defaults to
+/// [`MAX_MESSAGE_SIZE`]. Reads and writes past the end return errors instead
+/// of panicking. Keeps track of the names already written so later
+/// occurrences can be compressed into pointers, unless created with
+/// [`PacketBuffer::without_compression`].
 #[derive(Debug)]
 pub struct PacketBuffer {
-    pub buffer: [u8; 512],
+    pub buffer: Vec<u8>,
     pub pos: usize,
     pub domains: HashMap<String, usize>,
+    limit: usize,
+    /// Reads at or past this offset fail, see [`PacketBuffer::read_within`].
+    read_end: usize,
+    compress: bool,
+}
+
+impl Default for PacketBuffer {
+    fn default() -> Self {
+        PacketBuffer::new()
+    }
 }
 
 impl PacketBuffer {
-    pub fn new(buffer: [u8; 512]) -> Self {
+    pub fn new() -> Self {
+        PacketBuffer::with_limit(MAX_MESSAGE_SIZE)
+    }
+
+    /// Creates an empty buffer that refuses to grow past `limit` bytes.
+    pub fn with_limit(limit: usize) -> Self {
         PacketBuffer {
-            buffer,
+            buffer: Vec::new(),
             pos: 0,
             domains: HashMap::new(),
+            limit: limit.min(MAX_MESSAGE_SIZE),
+            read_end: MAX_MESSAGE_SIZE,
+            compress: true,
         }
     }
 
+    /// Creates an empty buffer that writes every name in full, as needed for
+    /// the canonical form of records (RFC 4034 6.2).
+    pub fn without_compression() -> Self {
+        PacketBuffer {
+            compress: false,
+            ..PacketBuffer::new()
+        }
+    }
+
+    /// Copies a received message into a new buffer positioned at its start.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        if bytes.len() > MAX_MESSAGE_SIZE {
+            return Err(ParseError::MessageTooLong { length: bytes.len() });
+        }
+
+        Ok(PacketBuffer {
+            buffer: bytes.to_vec(),
+            ..PacketBuffer::new()
+        })
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.min(MAX_MESSAGE_SIZE);
+    }
+
+    /// The bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.pos]
+    }
+
+    /// Drops everything after `pos`, including compression targets that
+    /// pointed into the removed bytes.
+    pub fn truncate(&mut self, pos: usize) {
+        self.buffer.truncate(pos);
+        self.domains.retain(|_, target| *target < pos);
+        self.pos = pos;
+    }
+
     pub fn seek(&mut self, pos: usize) {
         self.pos = pos;
     }
 
-    fn peek(&self) -> Result<u8> {
-        return Ok(self.buffer[self.pos]);
+    /// Runs `read` as if the message ended at `end`, so a field with a known
+    /// length can't consume the bytes that follow it. Compression pointers
+    /// only go backwards, so names inside the field still resolve.
+    pub fn read_within<T>(&mut self, end: usize, read: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        let outer_end = self.read_end;
+        self.read_end = end.min(outer_end);
+        let result = read(self);
+        self.read_end = outer_end;
+
+        result
+    }
+
+    fn peek(&self) -> Result<u8, ParseError> {
+        match self.buffer.get(self.pos).filter(|_| self.pos < self.read_end) {
+            Some(num) => Ok(*num),
+            None => Err(ParseError::Truncated { position: self.pos }),
+        }
     }
 
-    pub fn read(&mut self) -> Result<u8> {
-        let num = self.buffer[self.pos];
+    pub fn read(&mut self) -> Result<u8, ParseError> {
+        let num = self.peek()?;
         self.pos += 1;
 
-        return Ok(num);
+        Ok(num)
     }
 
-    pub fn read_u16(&mut self) -> Result<u16> {
+    pub fn read_u16(&mut self) -> Result<u16, ParseError> {
         let num = ((self.read()? as u16) << 8) | self.read()? as u16;
 
-        return Ok(num);
+        Ok(num)
     }
 
-    pub fn read_u32(&mut self) -> Result<u32> {
+    pub fn read_u32(&mut self) -> Result<u32, ParseError> {
         let num = ((self.read()? as u32) << 24)
             | ((self.read()? as u32) << 16)
             | ((self.read()? as u32) << 8)
             | self.read()? as u32;
 
-        return Ok(num);
+        Ok(num)
     }
 
-    pub fn read_qname(&mut self) -> Result<String> {
-        let mut higger_pos = self.pos;
+    /// Reads a possibly compressed name (RFC 1035 4.1.4).
+    ///
+    /// Every compression pointer has to point before the start of the part of
+    /// the name read so far, which rules out forward pointers and loops.
+    /// Label bytes other than printable ASCII come back as `\DDD` escapes,
+    /// which [`PacketBuffer::write_qname`] turns back into the same bytes.
+    pub fn read_qname(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        // Lowest offset this name has been read from, pointers must go below it.
+        let mut lowest = self.pos;
+        // Where reading continues once the name is done, set by the first pointer.
+        let mut resume = None;
+        let mut wire_length = 1;
 
         let mut actual_domain = String::new();
 
         loop {
-            if self.peek()? == 0 {
-                self.pos += 1;
-                actual_domain.pop();
-                break;
+            let position = self.pos;
+            let length = self.read()?;
+
+            match length & 0xC0 {
+                0x00 if length == 0 => break,
+                0x00 => {
+                    wire_length += length as usize + 1;
+                    if wire_length > MAX_NAME_LENGTH {
+                        return Err(ParseError::NameTooLong { position: start });
+                    }
+
+                    for byte in self.read_bytes(length as usize)? {
+                        push_label_byte(&mut actual_domain, byte);
+                    }
+                    actual_domain.push('.');
+                }
+                0xC0 => {
+                    let target = ((length as usize & 0x3F) << 8) | self.read()? as usize;
+                    if target >= position {
+                        return Err(ParseError::ForwardPointer { position, target });
+                    }
+                    if target >= lowest {
+                        return Err(ParseError::PointerLoop { position, target });
+                    }
+
+                    resume.get_or_insert(self.pos);
+                    lowest = target;
+                    self.seek(target);
+                }
+                _ => return Err(ParseError::ReservedLabelType { position }),
             }
+        }
 
-            if self.peek()? == 0xC0 {
-                // Read the pointer and remove the first two bits that are always 1
-                let pointer = self.read_u16()? & 0x3FFF;
+        actual_domain.pop();
+        if let Some(resume) = resume {
+            self.seek(resume);
+        }
 
-                if pointer as usize >= self.buffer.len() {
-                    return Err("Pointer out of bounds".to_string().into());
-                }
+        Ok(actual_domain)
+    }
 
-                if higger_pos < self.pos {
-                    higger_pos = self.pos;
-                }
+    pub fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, ParseError> {
+        let end = self.pos + length;
+        let available = self.buffer.len().min(self.read_end);
+        if end > available {
+            return Err(ParseError::Truncated { position: available.max(self.pos) });
+        }
 
-                self.seek(pointer as usize);
-                continue;
-            }
+        let bytes = self.buffer[self.pos..end].to_vec();
+        self.pos = end;
 
-            let label_length = self.read()? as usize;
+        Ok(bytes)
+    }
+
+    pub fn write(&mut self, num: u8) -> Result<(), BufferError> {
+        if self.pos >= self.limit {
+            return Err(BufferError::LimitExceeded { limit: self.limit });
+        }
 
-            if label_length > 63 {
-                return Err("Label length too long".to_string().into());
+        if self.pos < self.buffer.len() {
+            self.buffer[self.pos] = num;
+        } else {
+            self.buffer.resize(self.pos, 0);
+            self.buffer.push(num);
+        }
+        self.pos += 1;
+
+        Ok(())
+    }
+
+    pub fn write_u16(&mut self, num: u16) -> Result<(), BufferError> {
+        self.write_bytes(&num.to_be_bytes())
+    }
+
+    pub fn write_u32(&mut self, num: u32) -> Result<(), BufferError> {
+        self.write_bytes(&num.to_be_bytes())
+    }
+
+    pub fn write_qname(&mut self, domain: &str) -> Result<(), BufferError> {
+        let labels = encode_labels(domain)?;
+
+        for i in 0..labels.len() {
+            let suffix = labels[i..].iter().map(|(text, _)| *text).collect::<Vec<&str>>().join(".");
+
+            if let Some(pos) = self.domains.get(&suffix).filter(|_| self.compress) {
+                let pointer = 0xC000 | pos;
+                return self.write_u16(pointer as u16);
             }
 
-            for _ in 0..label_length {
-                actual_domain.push(self.read()? as char);
+            if self.compress && self.pos <= MAX_POINTER_TARGET {
+                self.domains.insert(suffix, self.pos);
             }
 
-            actual_domain.push('.');
+            let label = &labels[i].1;
+            self.write(label.len() as u8)?;
+            self.write_bytes(label)?;
         }
 
-        if higger_pos > self.pos {
-            self.seek(higger_pos);
+        self.write(0)
+    }
+
+    /// Writes `domain` in full, for RDATA fields where compression is not
+    /// allowed (RFC 3597 4). The name isn't offered as a compression target either.
+    pub fn write_qname_uncompressed(&mut self, domain: &str) -> Result<(), BufferError> {
+        for (_, label) in encode_labels(domain)? {
+            self.write(label.len() as u8)?;
+            self.write_bytes(&label)?;
         }
 
-        return Ok(actual_domain);
+        self.write(0)
     }
 
-    pub fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>> {
-        let mut bytes = Vec::new();
+    /// Reads a length prefixed <character-string> (RFC 1035 3.3).
+    pub fn read_character_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let length = self.read()?;
+        self.read_bytes(length as usize)
+    }
 
-        for _ in 0..length {
-            bytes.push(self.read()?);
+    pub fn write_character_string(&mut self, text: &[u8]) -> Result<(), BufferError> {
+        if text.len() > 255 {
+            return Err(BufferError::CharacterStringTooLong { length: text.len() });
         }
 
-        return Ok(bytes);
+        self.write(text.len() as u8)?;
+        self.write_bytes(text)
     }
 
-    pub fn write(&mut self, num: u8) {
-        self.buffer[self.pos] = num;
-        self.pos += 1;
-    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), BufferError> {
+        if self.pos + bytes.len() > self.limit {
+            return Err(BufferError::LimitExceeded { limit: self.limit });
+        }
 
-    pub fn write_u16(&mut self, num: u16) {
-        self.write((num >> 8) as u8);
-        self.write(num as u8);
-    }
+        for byte in bytes {
+            self.write(*byte)?;
+        }
 
-    pub fn write_u32(&mut self, num: u32) {
-        self.write((num >> 24) as u8);
-        self.write((num >> 16) as u8);
-        self.write((num >> 8) as u8);
-        self.write(num as u8);
+        Ok(())
     }
+}
 
-    pub fn write_qname(&mut self, domain: &str) {
-        let domains_split = domain.split(".").collect::<Vec<&str>>();
+/// Appends a label byte to a name in presentation form. Anything but
+/// printable ASCII, and the dot and backslash, is escaped as `\DDD` so the
+/// name converts back to the same bytes.
+fn push_label_byte(name: &mut String, byte: u8) {
+    if byte.is_ascii_graphic() && byte != b'.' && byte != b'\\' {
+        name.push(byte as char);
+    } else {
+        name.push_str(&format!("\\{:03}", byte));
+    }
+}
 
-        for (i, label) in domains_split.iter().enumerate() {
-            let all_labes_from_this_point = domains_split.get(i..).unwrap().join(".");
+/// Splits a name in presentation form into its labels, each as written and
+/// as wire bytes with `\DDD` and `\X` escapes decoded. Fails on empty or
+/// oversized labels and names longer than 255 bytes on the wire.
+fn encode_labels(domain: &str) -> Result<Vec<(&str, Vec<u8>)>, BufferError> {
+    let mut labels = Vec::new();
+    if domain.is_empty() || domain == "." {
+        return Ok(labels);
+    }
 
-            if let Some(pos) = self.domains.get(&all_labes_from_this_point) {
-                let pointer = 0xC000 | pos;
-                self.write_u16(pointer as u16);
-                return;
+    let bytes = domain.as_bytes();
+    let mut wire_length = 1;
+    let mut label = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    loop {
+        match bytes.get(i) {
+            Some(b'\\') => {
+                let digits = bytes.get(i + 1..i + 4).filter(|digits| digits.iter().all(u8::is_ascii_digit));
+                match (digits, bytes.get(i + 1)) {
+                    (Some(digits), _) => {
+                        let value = digits.iter().fold(0u16, |value, digit| value * 10 + (digit - b'0') as u16);
+                        label.push(u8::try_from(value).map_err(|_| BufferError::InvalidValue { field: "name escape" })?);
+                        i += 4;
+                    }
+                    (None, Some(&byte)) => {
+                        label.push(byte);
+                        i += 2;
+                    }
+                    (None, None) => return Err(BufferError::InvalidValue { field: "name escape" }),
+                }
             }
+            Some(b'.') | None => {
+                // Only the trailing dot of an absolute name may end an empty label.
+                if label.is_empty() {
+                    if i == bytes.len() {
+                        break;
+                    }
+                    return Err(BufferError::EmptyLabel);
+                }
+                if label.len() > 63 {
+                    return Err(BufferError::LabelTooLong { length: label.len() });
+                }
 
-            self.domains.insert(all_labes_from_this_point, self.pos);
-
-            self.write(label.len() as u8);
-            for byte in label.bytes() {
-                self.write(byte);
+                wire_length += label.len() + 1;
+                labels.push((&domain[start..i], std::mem::take(&mut label)));
+                if i == bytes.len() {
+                    break;
+                }
+                i += 1;
+                start = i;
+            }
+            Some(&byte) => {
+                label.push(byte);
+                i += 1;
             }
         }
-        self.write(0);
-
     }
 
-    pub fn write_bytes(&mut self, bytes: Vec<u8>) {
-        for byte in bytes {
-            self.write(byte);
-        }
+    if wire_length > MAX_NAME_LENGTH {
+        return Err(BufferError::NameTooLong { length: wire_length });
     }
+
+    Ok(labels)
 }
 
 #[cfg(test)]
@@ -159,52 +379,50 @@ mod tests {
 
     #[test]
     fn test_packet_buffer() {
-        let mut buffer = PacketBuffer::new([0; 512]);
-        buffer.buffer[0] = 0b00000001;
-        buffer.buffer[1] = 0b00000010;
+        let mut buffer = PacketBuffer::from_bytes(&[0b00000001, 0b00000010]).unwrap();
         assert_eq!(buffer.read_u16().unwrap(), 258);
+        assert!(buffer.read().is_err());
+    }
+
+    #[test]
+    fn grows_past_512_bytes() {
+        let mut buffer = PacketBuffer::new();
+        buffer.write_bytes(&[0xAB; 600]).unwrap();
+        buffer.write_u16(0x0102).unwrap();
+
+        assert_eq!(buffer.as_bytes().len(), 602);
+        assert_eq!(buffer.as_bytes()[601], 0x02);
+    }
+
+    #[test]
+    fn writes_past_limit_fail() {
+        let mut buffer = PacketBuffer::with_limit(3);
+        buffer.write_u16(1).unwrap();
+
+        assert!(buffer.write_u16(2).is_err());
+        assert_eq!(buffer.pos, 2);
     }
 
     #[test]
     fn test_qname() {
-        let mut buffer = PacketBuffer::new([0; 512]);
-        let mut tmp_vec: Vec<u8> = Vec::new();
+        let mut tmp_vec: Vec<u8> = vec![3];
 
-        tmp_vec.push(3);
-        tmp_vec.push('w' as u8);
-        tmp_vec.push('w' as u8);
-        tmp_vec.push('w' as u8);
+        tmp_vec.extend_from_slice(b"www");
         tmp_vec.push(6);
-        tmp_vec.push('g' as u8);
-        tmp_vec.push('o' as u8);
-        tmp_vec.push('o' as u8);
-        tmp_vec.push('g' as u8);
-        tmp_vec.push('l' as u8);
-        tmp_vec.push('e' as u8);
+        tmp_vec.extend_from_slice(b"google");
         tmp_vec.push(3);
-        tmp_vec.push('c' as u8);
-        tmp_vec.push('o' as u8);
-        tmp_vec.push('m' as u8);
+        tmp_vec.extend_from_slice(b"com");
         tmp_vec.push(0);
         tmp_vec.push(6);
-        tmp_vec.push('i' as u8);
-        tmp_vec.push('m' as u8);
-        tmp_vec.push('a' as u8);
-        tmp_vec.push('g' as u8);
-        tmp_vec.push('e' as u8);
-        tmp_vec.push('s' as u8);
+        tmp_vec.extend_from_slice(b"images");
         tmp_vec.push(0xC0);
         tmp_vec.push(0x04);
         tmp_vec.push(3);
-        tmp_vec.push('w' as u8);
-        tmp_vec.push('w' as u8);
-        tmp_vec.push('w' as u8);
+        tmp_vec.extend_from_slice(b"www");
         tmp_vec.push(0xC0);
         tmp_vec.push(16);
 
-        for i in 0..tmp_vec.len() {
-            buffer.buffer[i] = tmp_vec[i];
-        }
+        let mut buffer = PacketBuffer::from_bytes(&tmp_vec).unwrap();
 
         assert_eq!(buffer.read_qname().unwrap(), "www.google.com".to_string());
         assert_eq!(
@@ -216,4 +434,91 @@ mod tests {
             "www.images.google.com".to_string()
         );
     }
+
+    /// Reads a name from `bytes`, starting at `start`.
+    fn qname_at(bytes: &[u8], start: usize) -> Result<String, ParseError> {
+        let mut buffer = PacketBuffer::from_bytes(bytes).unwrap();
+        buffer.seek(start);
+        buffer.read_qname()
+    }
+
+    #[test]
+    fn rejects_malformed_names() {
+        assert_eq!(qname_at(&[3, b'w', b'w'], 0).unwrap_err(), ParseError::Truncated { position: 3 });
+        assert_eq!(qname_at(&[0xC0], 0).unwrap_err(), ParseError::Truncated { position: 1 });
+        assert_eq!(qname_at(&[0x40, 0], 0).unwrap_err(), ParseError::ReservedLabelType { position: 0 });
+        assert_eq!(qname_at(&[0x80, 0], 0).unwrap_err(), ParseError::ReservedLabelType { position: 0 });
+
+        let mut long = Vec::new();
+        for _ in 0..4 {
+            long.push(63);
+            long.extend_from_slice(&[b'a'; 63]);
+        }
+        long.push(0);
+        assert_eq!(qname_at(&long, 0).unwrap_err(), ParseError::NameTooLong { position: 0 });
+
+        // 3 labels of 63 and one of 61 add up to exactly 255 bytes.
+        long[192] = 61;
+        long.drain(254..256);
+        assert_eq!(qname_at(&long, 0).unwrap().len(), 253);
+    }
+
+    #[test]
+    fn rejects_pointer_loops_and_forward_pointers() {
+        assert_eq!(qname_at(&[0xC0, 0], 0).unwrap_err(), ParseError::ForwardPointer { position: 0, target: 0 });
+        assert_eq!(qname_at(&[0, 0xC0, 3, 0], 1).unwrap_err(), ParseError::ForwardPointer { position: 1, target: 3 });
+        assert_eq!(qname_at(&[1, b'a', 0xC0, 0], 0).unwrap_err(), ParseError::PointerLoop { position: 2, target: 0 });
+
+        // Two names pointing at each other need a forward hop somewhere.
+        let bytes = [1, b'a', 0xC0, 4, 1, b'b', 0xC0, 0];
+        assert_eq!(qname_at(&bytes, 4).unwrap_err(), ParseError::ForwardPointer { position: 2, target: 4 });
+
+        // Chains of pointers are fine as long as every hop goes further back.
+        let bytes = [1, b'a', 0, 1, b'b', 0xC0, 0, 0xC0, 3, 0xFF];
+        let mut buffer = PacketBuffer::from_bytes(&bytes).unwrap();
+        buffer.seek(7);
+        assert_eq!(buffer.read_qname().unwrap(), "b.a");
+        assert_eq!(buffer.pos, 9);
+    }
+
+    #[test]
+    fn names_round_trip_byte_for_byte() {
+        let wire = [4, 0xE9, b'.', b'\\', b' ', 3, b'c', b'o', b'm', 0];
+        let name = qname_at(&wire, 0).unwrap();
+        assert_eq!(name, "\\233\\046\\092\\032.com");
+
+        let mut buffer = PacketBuffer::without_compression();
+        buffer.write_qname(&name).unwrap();
+        assert_eq!(buffer.as_bytes(), wire);
+
+        let mut buffer = PacketBuffer::without_compression();
+        buffer.write_qname("a\\.b.c.").unwrap();
+        assert_eq!(buffer.as_bytes(), [3, b'a', b'.', b'b', 1, b'c', 0]);
+    }
+
+    #[test]
+    fn rejects_names_that_cannot_be_written() {
+        let write = |name: &str| PacketBuffer::new().write_qname(name).unwrap_err();
+
+        assert_eq!(write("a..b"), BufferError::EmptyLabel);
+        assert_eq!(write(".a"), BufferError::EmptyLabel);
+        assert_eq!(write(&"a".repeat(64)), BufferError::LabelTooLong { length: 64 });
+        assert_eq!(write(&"\\233".repeat(64)), BufferError::LabelTooLong { length: 64 });
+        assert_eq!(write(&vec!["a".repeat(63); 4].join(".")), BufferError::NameTooLong { length: 257 });
+        assert_eq!(write("a\\256"), BufferError::InvalidValue { field: "name escape" });
+        assert_eq!(write("a\\"), BufferError::InvalidValue { field: "name escape" });
+    }
+
+    #[test]
+    fn reads_within_a_bound() {
+        let mut buffer = PacketBuffer::from_bytes(&[1, b'a', 0, 1, 2, 3, 0xC0, 0, 4]).unwrap();
+        buffer.seek(3);
+
+        let read = buffer.read_within(5, |buffer| buffer.read_bytes(3));
+        assert_eq!(read.unwrap_err(), ParseError::Truncated { position: 5 });
+
+        buffer.seek(6);
+        assert_eq!(buffer.read_within(8, PacketBuffer::read_qname).unwrap(), "a");
+        assert_eq!(buffer.read().unwrap(), 4);
+    }
 }
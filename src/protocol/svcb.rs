@@ -0,0 +1,324 @@
+//! Service parameters of SVCB and HTTPS records (RFC 9460).
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::errors::{BufferError, ParseError};
+
+use super::encoding::{from_base64, to_base64};
+use super::packet_buffer::PacketBuffer;
+
+/// One SvcParam, keyed by its SvcParamKey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    /// Keys the client must understand to use the record.
+    Mandatory(Vec<u16>),
+    /// Application protocol ids, like `h2` or `h3`.
+    Alpn(Vec<Vec<u8>>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// Encrypted ClientHello configuration list.
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    Unknown { key: u16, value: Vec<u8> },
+}
+
+const MANDATORY: u16 = 0;
+const ALPN: u16 = 1;
+const NO_DEFAULT_ALPN: u16 = 2;
+const PORT: u16 = 3;
+const IPV4HINT: u16 = 4;
+const ECH: u16 = 5;
+const IPV6HINT: u16 = 6;
+
+fn key_name(key: u16) -> String {
+    match key {
+        MANDATORY => "mandatory".to_string(),
+        ALPN => "alpn".to_string(),
+        NO_DEFAULT_ALPN => "no-default-alpn".to_string(),
+        PORT => "port".to_string(),
+        IPV4HINT => "ipv4hint".to_string(),
+        ECH => "ech".to_string(),
+        IPV6HINT => "ipv6hint".to_string(),
+        _ => format!("key{}", key),
+    }
+}
+
+fn parse_key(name: &str) -> Result<u16, String> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "mandatory" => MANDATORY,
+        "alpn" => ALPN,
+        "no-default-alpn" => NO_DEFAULT_ALPN,
+        "port" => PORT,
+        "ipv4hint" => IPV4HINT,
+        "ech" => ECH,
+        "ipv6hint" => IPV6HINT,
+        other => other
+            .strip_prefix("key")
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| format!("Unknown SvcParamKey {}", name))?,
+    };
+
+    Ok(key)
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => MANDATORY,
+            SvcParam::Alpn(_) => ALPN,
+            SvcParam::NoDefaultAlpn => NO_DEFAULT_ALPN,
+            SvcParam::Port(_) => PORT,
+            SvcParam::Ipv4Hint(_) => IPV4HINT,
+            SvcParam::Ech(_) => ECH,
+            SvcParam::Ipv6Hint(_) => IPV6HINT,
+            SvcParam::Unknown { key, .. } => *key,
+        }
+    }
+
+    /// Reads every SvcParam up to `end`, the end of the RDATA.
+    pub fn deserialize_all(packet_buffer: &mut PacketBuffer, end: usize) -> Result<Vec<SvcParam>, ParseError> {
+        let start = packet_buffer.pos;
+        let mut params: Vec<SvcParam> = Vec::new();
+
+        while packet_buffer.pos < end {
+            let position = packet_buffer.pos;
+            let key = packet_buffer.read_u16()?;
+            let length = packet_buffer.read_u16()? as usize;
+            let value = packet_buffer.read_bytes(length)?;
+
+            // RFC 9460 2.2: keys appear in strictly increasing order.
+            if params.last().is_some_and(|last| last.key() >= key) {
+                return Err(ParseError::InvalidField { position, field: "SvcParamKey order" });
+            }
+            params.push(SvcParam::from_wire(key, value, position)?);
+        }
+
+        if packet_buffer.pos != end {
+            return Err(ParseError::InvalidField { position: start, field: "SvcParams" });
+        }
+
+        Ok(params)
+    }
+
+    /// Decodes the value of `key`, read from the SvcParam at `position`.
+    fn from_wire(key: u16, value: Vec<u8>, position: usize) -> Result<SvcParam, ParseError> {
+        let invalid = || ParseError::InvalidField { position, field: "SvcParam value" };
+
+        let param = match key {
+            MANDATORY if !value.is_empty() && value.len().is_multiple_of(2) => {
+                SvcParam::Mandatory(value.chunks(2).map(|key| u16::from_be_bytes([key[0], key[1]])).collect())
+            }
+            ALPN => {
+                let mut ids = Vec::new();
+                let mut rest = value.as_slice();
+                while let Some((&length, tail)) = rest.split_first() {
+                    if length == 0 || tail.len() < length as usize {
+                        return Err(invalid());
+                    }
+                    ids.push(tail[..length as usize].to_vec());
+                    rest = &tail[length as usize..];
+                }
+                if ids.is_empty() {
+                    return Err(invalid());
+                }
+                SvcParam::Alpn(ids)
+            }
+            NO_DEFAULT_ALPN if value.is_empty() => SvcParam::NoDefaultAlpn,
+            PORT if value.len() == 2 => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+            IPV4HINT if !value.is_empty() && value.len().is_multiple_of(4) => SvcParam::Ipv4Hint(
+                value
+                    .chunks(4)
+                    .map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+                    .collect(),
+            ),
+            ECH => SvcParam::Ech(value),
+            IPV6HINT if !value.is_empty() && value.len().is_multiple_of(16) => SvcParam::Ipv6Hint(
+                value
+                    .chunks(16)
+                    .map(|octets| Ipv6Addr::from(<[u8; 16]>::try_from(octets).unwrap()))
+                    .collect(),
+            ),
+            MANDATORY | NO_DEFAULT_ALPN | PORT | IPV4HINT | IPV6HINT => return Err(invalid()),
+            _ => SvcParam::Unknown { key, value },
+        };
+
+        Ok(param)
+    }
+
+    fn value(&self) -> Vec<u8> {
+        match self {
+            SvcParam::Mandatory(keys) => keys.iter().flat_map(|key| key.to_be_bytes()).collect(),
+            SvcParam::Alpn(ids) => ids
+                .iter()
+                .flat_map(|id| std::iter::once(id.len() as u8).chain(id.iter().copied()))
+                .collect(),
+            SvcParam::NoDefaultAlpn => Vec::new(),
+            SvcParam::Port(port) => port.to_be_bytes().to_vec(),
+            SvcParam::Ipv4Hint(addresses) => addresses.iter().flat_map(|address| address.octets()).collect(),
+            SvcParam::Ech(config) => config.clone(),
+            SvcParam::Ipv6Hint(addresses) => addresses.iter().flat_map(|address| address.octets()).collect(),
+            SvcParam::Unknown { value, .. } => value.clone(),
+        }
+    }
+
+    /// Writes `params` sorted by key, as the wire format requires.
+    pub fn serialize_all(params: &[SvcParam], packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+        let mut params: Vec<&SvcParam> = params.iter().collect();
+        params.sort_by_key(|param| param.key());
+
+        for param in params {
+            if let SvcParam::Alpn(ids) = param {
+                if ids.iter().any(|id| id.is_empty() || id.len() > 255) {
+                    return Err(BufferError::InvalidValue { field: "alpn" });
+                }
+            }
+            let value = param.value();
+            if value.len() > u16::MAX as usize {
+                return Err(BufferError::InvalidValue { field: "SvcParam" });
+            }
+            packet_buffer.write_u16(param.key())?;
+            packet_buffer.write_u16(value.len() as u16)?;
+            packet_buffer.write_bytes(&value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes one value-list item, escaping commas and bytes that can't appear bare.
+fn write_escaped(f: &mut fmt::Formatter, value: &[u8]) -> fmt::Result {
+    for &byte in value {
+        match byte {
+            b',' | b'\\' => write!(f, "\\{}", byte as char)?,
+            0x21..=0x7E if byte != b'"' && byte != b';' => write!(f, "{}", byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+/// Splits a value-list on unescaped commas, resolving `\,`, `\\` and `\DDD`.
+fn split_value_list(text: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut items = vec![Vec::new()];
+    let mut bytes = text.bytes();
+
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b',' => items.push(Vec::new()),
+            b'\\' => {
+                let escaped = bytes.next().ok_or("Dangling escape")?;
+                let value = if escaped.is_ascii_digit() {
+                    let digits = [escaped, bytes.next().ok_or("Short escape")?, bytes.next().ok_or("Short escape")?];
+                    std::str::from_utf8(&digits).ok().and_then(|digits| digits.parse().ok()).ok_or("Invalid escape")?
+                } else {
+                    escaped
+                };
+                items.last_mut().unwrap().push(value);
+            }
+            _ => items.last_mut().unwrap().push(byte),
+        }
+    }
+
+    Ok(items)
+}
+
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", key_name(self.key()))?;
+
+        let join = |f: &mut fmt::Formatter, items: Vec<String>| write!(f, "={}", items.join(","));
+        match self {
+            SvcParam::Mandatory(keys) => join(f, keys.iter().map(|key| key_name(*key)).collect()),
+            SvcParam::Alpn(ids) => {
+                write!(f, "=")?;
+                for (i, id) in ids.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_escaped(f, id)?;
+                }
+                Ok(())
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(addresses) => join(f, addresses.iter().map(Ipv4Addr::to_string).collect()),
+            SvcParam::Ech(config) => write!(f, "={}", to_base64(config)),
+            SvcParam::Ipv6Hint(addresses) => join(f, addresses.iter().map(Ipv6Addr::to_string).collect()),
+            SvcParam::Unknown { value, .. } => {
+                write!(f, "=")?;
+                write_escaped(f, value)
+            }
+        }
+    }
+}
+
+impl FromStr for SvcParam {
+    type Err = String;
+
+    /// Parses the `key=value` presentation form.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match text.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (text, None),
+        };
+        let key = parse_key(name)?;
+        let invalid = || format!("Invalid {} value {}", name, value.unwrap_or(""));
+
+        let Some(value) = value else {
+            return match key {
+                NO_DEFAULT_ALPN => Ok(SvcParam::NoDefaultAlpn),
+                MANDATORY | ALPN | PORT | IPV4HINT | ECH | IPV6HINT => Err(format!("{} needs a value", name)),
+                _ => Ok(SvcParam::Unknown { key, value: Vec::new() }),
+            };
+        };
+
+        let param = match key {
+            MANDATORY => SvcParam::Mandatory(
+                value.split(',').map(parse_key).collect::<Result<_, _>>()?,
+            ),
+            ALPN => SvcParam::Alpn(split_value_list(value)?),
+            NO_DEFAULT_ALPN => return Err("no-default-alpn takes no value".to_string()),
+            PORT => SvcParam::Port(value.parse().map_err(|_| invalid())?),
+            IPV4HINT => SvcParam::Ipv4Hint(
+                value.split(',').map(|address| address.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?,
+            ),
+            ECH => SvcParam::Ech(from_base64(value).ok_or_else(invalid)?),
+            IPV6HINT => SvcParam::Ipv6Hint(
+                value.split(',').map(|address| address.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?,
+            ),
+            _ => SvcParam::Unknown { key, value: split_value_list(value)?.join(&b","[..]) },
+        };
+
+        Ok(param)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_presentation_format() {
+        let params = [
+            "mandatory=alpn,port",
+            "alpn=h2,h3",
+            "no-default-alpn",
+            "port=8443",
+            "ipv4hint=192.0.2.1,192.0.2.2",
+            "ech=AEX+DQBBpQAgACD/",
+            "ipv6hint=2001:db8::1",
+            "key65333=ex\\,1",
+        ];
+
+        for text in params {
+            let param: SvcParam = text.parse().unwrap();
+            assert_eq!(param.to_string(), text);
+        }
+        assert_eq!("alpn=h2,h3".parse(), Ok(SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()])));
+        assert!("port=http".parse::<SvcParam>().is_err());
+        assert!("bogus=1".parse::<SvcParam>().is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Type bitmaps listing the types present at a name in NSEC and NSEC3
+//! records (RFC 4034 4.1.2).
+
+use crate::errors::{BufferError, ParseError};
+
+use super::dns_record_type::RecordType;
+use super::packet_buffer::PacketBuffer;
+
+/// Reads window blocks up to `end`, returning the types in ascending order.
+pub fn deserialize(packet_buffer: &mut PacketBuffer, end: usize) -> Result<Vec<RecordType>, ParseError> {
+    let start = packet_buffer.pos;
+    let mut types = Vec::new();
+    let mut previous_window = None;
+
+    while packet_buffer.pos < end {
+        let position = packet_buffer.pos;
+        let window = packet_buffer.read()?;
+        let length = packet_buffer.read()? as usize;
+        if previous_window.is_some_and(|previous| window <= previous) {
+            return Err(ParseError::InvalidField { position, field: "type bitmap window order" });
+        }
+        if !(1..=32).contains(&length) {
+            return Err(ParseError::InvalidField { position, field: "type bitmap length" });
+        }
+        previous_window = Some(window);
+
+        for (index, byte) in packet_buffer.read_bytes(length)?.into_iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(RecordType::from((window as u16) << 8 | (index * 8 + bit) as u16));
+                }
+            }
+        }
+    }
+    if packet_buffer.pos != end {
+        return Err(ParseError::InvalidField { position: start, field: "type bitmap" });
+    }
+
+    Ok(types)
+}
+
+/// Writes `types` as window blocks, leaving out empty windows and trailing
+/// zero bytes.
+pub fn serialize(types: &[RecordType], packet_buffer: &mut PacketBuffer) -> Result<(), BufferError> {
+    let mut codes: Vec<u16> = types.iter().map(|record_type| (*record_type).into()).collect();
+    codes.sort_unstable();
+    codes.dedup();
+
+    for window in codes.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let mut bitmap = [0u8; 32];
+        for code in window {
+            let low = (code & 0xFF) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+        }
+        let length = (window[window.len() - 1] & 0xFF) as usize / 8 + 1;
+
+        packet_buffer.write((window[0] >> 8) as u8)?;
+        packet_buffer.write(length as u8)?;
+        packet_buffer.write_bytes(&bitmap[..length])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_rfc_4034_example() {
+        // Types A MX RRSIG NSEC TYPE1234 from RFC 4034 4.3.
+        let types = [RecordType::A, RecordType::MX, RecordType::RRSIG, RecordType::NSEC, RecordType::Unknown(1234)];
+        let mut expected = vec![0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03, 0x04, 0x1B];
+        expected.extend_from_slice(&[0; 26]);
+        expected.push(0x20);
+
+        let mut buffer = PacketBuffer::new();
+        serialize(&types, &mut buffer).unwrap();
+        assert_eq!(buffer.as_bytes(), expected.as_slice());
+
+        buffer.seek(0);
+        assert_eq!(deserialize(&mut buffer, expected.len()).unwrap(), types);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use log::warn;
+
+use crate::dnssec::signer::ZoneSigner;
+use crate::errors::QueryError;
+use crate::protocol::dns_header::ResponseCode;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record_type::DnsRecordType;
+use crate::protocol::domain_name::{is_subdomain_of, normalize};
+use crate::zone::{Zone, ZoneLookup};
+
+use super::{RequestContext, Resolver};
+
+/// Longest CNAME chain followed inside our own zones.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// Answers from a set of zones loaded in memory, with the AA bit set.
+///
+/// Queries for names outside every zone are refused. Zones with a signer
+/// get their answers signed for clients that set the DO bit.
+pub struct AuthoritativeResolver {
+    zones: Vec<Zone>,
+    /// Signers by zone origin.
+    signers: HashMap<String, ZoneSigner>,
+}
+
+impl AuthoritativeResolver {
+    pub fn new(zones: Vec<Zone>) -> Self {
+        AuthoritativeResolver { zones, signers: HashMap::new() }
+    }
+
+    /// Signs the zone `origin` online with `signer`, which also adds its
+    /// DNSKEY, CDS and CDNSKEY records to the zone.
+    pub fn with_signer(mut self, origin: &str, mut signer: ZoneSigner) -> Self {
+        let origin = normalize(origin);
+        match self.zones.iter_mut().find(|zone| zone.origin() == origin) {
+            Some(zone) => {
+                signer.prepare(zone);
+                self.signers.insert(origin, signer);
+            }
+            None => warn!("No zone {} to sign", origin),
+        }
+
+        self
+    }
+
+    /// The most specific zone containing `name`.
+    fn find_zone(&self, name: &str) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| is_subdomain_of(name, zone.origin()))
+            .max_by_key(|zone| zone.origin().len())
+    }
+
+    /// Builds the authoritative response to `request`.
+    pub fn answer(&self, request: &DnsPacket) -> DnsPacket {
+        let response = DnsPacket::response_to(request);
+
+        let Some(question) = request.questions.first() else {
+            return response.with_rcode(ResponseCode::FormatError);
+        };
+        let Some(zone) = self.find_zone(&question.name) else {
+            return response.with_rcode(ResponseCode::Refused);
+        };
+
+        let qtype = question.qtype;
+        let mut response = response;
+        response.header.authoritative_answer = true;
+
+        let dnssec_ok = request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok);
+        let signer = self.signers.get(zone.origin()).filter(|_| dnssec_ok);
+        let mut proof = Vec::new();
+
+        let mut name = question.name.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            match zone.lookup(&name, qtype) {
+                ZoneLookup::Answer(records) => {
+                    for record in records {
+                        response = response.with_answer(record);
+                    }
+                    break;
+                }
+                ZoneLookup::Cname(record) => {
+                    let target = match record.rdata() {
+                        DnsRecordType::CNAME { canonical_name } => canonical_name.clone(),
+                        _ => unreachable!("lookup only returns CNAME records as aliases"),
+                    };
+                    response = response.with_answer(record);
+
+                    // Names outside the zone are left for the client to chase.
+                    if !is_subdomain_of(&target, zone.origin()) {
+                        break;
+                    }
+                    name = target;
+                }
+                ZoneLookup::Delegation { name_servers, glue } => {
+                    // A referral is only authoritative for the aliases leading to it.
+                    response.header.authoritative_answer = !response.answers.is_empty();
+                    if let (Some(signer), Some(cut)) = (signer, name_servers.first()) {
+                        proof = signer.delegation_proof(zone, cut.record());
+                    }
+                    for record in name_servers {
+                        response = response.with_authority(record);
+                    }
+                    for record in glue {
+                        response = response.with_additional(record);
+                    }
+                    break;
+                }
+                ZoneLookup::NoData => {
+                    response = response.with_authority(zone.negative_soa());
+                    if let Some(signer) = signer {
+                        proof = signer.nodata_proof(zone, &name);
+                    }
+                    break;
+                }
+                ZoneLookup::NxDomain => {
+                    response = response
+                        .with_rcode(ResponseCode::NXDomain)
+                        .with_authority(zone.negative_soa());
+                    if let Some(signer) = signer {
+                        proof = signer.nxdomain_proof(zone, &name);
+                    }
+                    break;
+                }
+            }
+        }
+
+        for record in proof {
+            response = response.with_authority(record);
+        }
+        match signer {
+            Some(signer) => signer.sign_response(zone, response),
+            None => response,
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for AuthoritativeResolver {
+    async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        Ok(self.answer(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::protocol::dns_record_type::RecordType;
+
+    const ZONE: &str = r#"
+$ORIGIN example.com.
+$TTL 3600
+@           SOA     ns1 hostmaster 1 7200 900 604800 300
+            NS      ns1
+ns1         A       192.0.2.1
+www         A       192.0.2.10
+alias       CNAME   www
+outside     CNAME   www.example.net.
+deep.empty  A       192.0.2.20
+child       NS      ns.child
+            NS      ns.elsewhere.org.
+            DS      12345 13 2 ABCDEF
+ns.child    A       192.0.2.53
+"#;
+
+    fn resolver() -> AuthoritativeResolver {
+        AuthoritativeResolver::new(vec![Zone::parse(ZONE, "example.com").unwrap()])
+    }
+
+    fn ask(name: &str, qtype: RecordType) -> DnsPacket {
+        resolver().answer(&DnsPacket::new_query(1, name, qtype))
+    }
+
+    #[test]
+    fn answers_authoritatively() {
+        let response = ask("WWW.example.com", RecordType::A);
+
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.header.rcode, ResponseCode::NoError);
+        assert_eq!(response.answers[0].rdata(), &DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 10) });
+    }
+
+    #[test]
+    fn nxdomain_carries_soa_with_minimum_ttl() {
+        let response = ask("missing.example.com", RecordType::A);
+
+        assert_eq!(response.header.rcode, ResponseCode::NXDomain);
+        assert!(response.answers.is_empty());
+        assert!(matches!(response.authority[0].rdata(), DnsRecordType::SOA { .. }));
+        assert_eq!(response.authority[0].ttl(), 300);
+    }
+
+    #[test]
+    fn nodata_for_existing_names_and_empty_non_terminals() {
+        for name in ["www.example.com", "empty.example.com"] {
+            let response = ask(name, RecordType::AAAA);
+
+            assert_eq!(response.header.rcode, ResponseCode::NoError);
+            assert!(response.answers.is_empty());
+            assert!(matches!(response.authority[0].rdata(), DnsRecordType::SOA { .. }));
+        }
+    }
+
+    #[test]
+    fn refers_delegations_with_glue() {
+        let response = ask("host.child.example.com", RecordType::A);
+
+        assert!(!response.header.authoritative_answer);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authority.len(), 2);
+        assert_eq!(response.additional.len(), 1);
+        assert_eq!(response.additional[0].record(), "ns.child.example.com");
+    }
+
+    #[test]
+    fn answers_ds_from_the_parent_side_of_a_cut() {
+        let response = ask("child.example.com", RecordType::DS);
+
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.answers.len(), 1);
+        assert!(matches!(response.answers[0].rdata(), DnsRecordType::DS { .. }));
+    }
+
+    #[test]
+    fn chases_cnames_inside_the_zone() {
+        let response = ask("alias.example.com", RecordType::A);
+        assert_eq!(response.answers.len(), 2);
+        assert!(matches!(response.answers[0].rdata(), DnsRecordType::CNAME { .. }));
+        assert_eq!(response.answers[1].record(), "www.example.com");
+
+        let response = ask("outside.example.com", RecordType::A);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn refuses_names_outside_the_zones() {
+        assert_eq!(ask("example.org", RecordType::A).header.rcode, ResponseCode::Refused);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::errors::QueryError;
+use crate::protocol::dns_header::{ResponseCode, OPCODE_QUERY};
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{DnsRecordType, RecordType};
+use crate::protocol::domain_name::normalize;
+
+use super::{RequestContext, Resolver};
+
+/// Upper bound for how long a positive answer is kept, whatever its TTL.
+const MAX_TTL: u32 = 86400;
+
+/// Upper bound for negative answers, as suggested by RFC 2308 5.
+const MAX_NEGATIVE_TTL: u32 = 10800;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    name: String,
+    qtype: RecordType,
+    qclass: u16,
+    dnssec_ok: bool,
+    /// CD requests may skip validation, so their answers are kept apart.
+    checking_disabled: bool,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: DnsPacket,
+    stored_at: Instant,
+    ttl: u32,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys ordered from least to most recently used.
+    recency: BTreeMap<u64, CacheKey>,
+    clock: u64,
+}
+
+/// Hit and miss counters of a [`CachingResolver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Remembers the answers of another resolver for as long as their TTLs allow.
+///
+/// Served answers have their TTLs lowered by the time they spent in the
+/// cache. NXDOMAIN and NODATA answers are cached for the SOA minimum found in
+/// their authority section (RFC 2308). Once `capacity` entries are stored, the
+/// least recently used one is evicted.
+pub struct CachingResolver {
+    inner: Arc<dyn Resolver>,
+    capacity: usize,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CachingResolver {
+    pub fn new(inner: Arc<dyn Resolver>, capacity: usize) -> Self {
+        CachingResolver {
+            inner,
+            capacity,
+            state: Mutex::new(CacheState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().entries.len(),
+        }
+    }
+
+    /// Drops every cached answer.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.recency.clear();
+    }
+
+    fn lookup(&self, key: &CacheKey, request: &DnsPacket) -> Option<DnsPacket> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let entry = state.entries.get(key)?;
+        let elapsed = now.duration_since(entry.stored_at).as_secs();
+        if elapsed >= entry.ttl as u64 {
+            let last_used = entry.last_used;
+            state.entries.remove(key);
+            state.recency.remove(&last_used);
+            return None;
+        }
+
+        // No record outlives the entry: that caps positive answers at
+        // MAX_TTL and gives the SOA of negative ones the remaining negative
+        // TTL (RFC 2308 5).
+        let elapsed = elapsed as u32;
+        let remaining = entry.ttl - elapsed;
+        let age = |records: &[DnsRecord]| -> Vec<DnsRecord> {
+            records
+                .iter()
+                .map(|record| record.clone().with_ttl(record.ttl().saturating_sub(elapsed).min(remaining)))
+                .collect()
+        };
+
+        let mut response = DnsPacket::response_to(request).with_rcode(entry.response.header.rcode);
+        response.header.recursion_available = entry.response.header.recursion_available;
+        response.header.authoritative_answer = entry.response.header.authoritative_answer;
+        response.header.authentic_data = entry.response.header.authentic_data;
+        response.answers = age(&entry.response.answers);
+        response.authority = age(&entry.response.authority);
+        response.additional = age(&entry.response.additional);
+        response.header.answer_count = response.answers.len() as u16;
+        response.header.nscount = response.authority.len() as u16;
+        response.header.arcount = response.additional.len() as u16;
+
+        let previous = entry.last_used;
+        state.clock += 1;
+        let clock = state.clock;
+        state.recency.remove(&previous);
+        state.recency.insert(clock, key.clone());
+        state.entries.get_mut(key).unwrap().last_used = clock;
+
+        Some(response)
+    }
+
+    fn store(&self, key: CacheKey, response: &DnsPacket) {
+        let Some(ttl) = cache_ttl(response) else {
+            return;
+        };
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        if let Some(previous) = state.entries.get(&key).map(|entry| entry.last_used) {
+            state.recency.remove(&previous);
+        } else if state.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = state.recency.pop_first() {
+                state.entries.remove(&oldest);
+            }
+        }
+
+        state.recency.insert(clock, key.clone());
+        state.entries.insert(key, CacheEntry {
+            response: response.clone(),
+            stored_at: Instant::now(),
+            ttl,
+            last_used: clock,
+        });
+    }
+}
+
+/// How long `response` may be cached, or `None` if it shouldn't be.
+fn cache_ttl(response: &DnsPacket) -> Option<u32> {
+    if response.header.truncated_message {
+        return None;
+    }
+
+    let negative = match response.header.rcode {
+        ResponseCode::NXDomain => true,
+        ResponseCode::NoError => response.answers.is_empty(),
+        _ => return None,
+    };
+
+    if negative {
+        let ttl = response.authority.iter().find_map(|record| match record.rdata() {
+            DnsRecordType::SOA { minimum, .. } => Some(record.ttl().min(*minimum)),
+            _ => None,
+        })?;
+        return Some(ttl.min(MAX_NEGATIVE_TTL)).filter(|ttl| *ttl > 0);
+    }
+
+    response
+        .answers
+        .iter()
+        .chain(response.authority.iter())
+        .map(DnsRecord::ttl)
+        .min()
+        .map(|ttl| ttl.min(MAX_TTL))
+        .filter(|ttl| *ttl > 0)
+}
+
+fn cache_key(request: &DnsPacket) -> Option<CacheKey> {
+    if request.header.opcode != OPCODE_QUERY || request.questions.len() != 1 {
+        return None;
+    }
+
+    let question = &request.questions[0];
+    Some(CacheKey {
+        name: normalize(&question.name),
+        qtype: question.qtype,
+        qclass: question.qclass.into(),
+        dnssec_ok: request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok),
+        checking_disabled: request.header.checking_disabled,
+    })
+}
+
+#[async_trait]
+impl Resolver for CachingResolver {
+    async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        let Some(key) = cache_key(request) else {
+            return self.inner.resolve(request, ctx).await;
+        };
+
+        if let Some(response) = self.lookup(&key, request) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(response);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let response = self.inner.resolve(request, ctx).await?;
+        self.store(key, &response);
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::dns_record_type::Class;
+    use crate::resolver::Protocol;
+
+    /// Answers A queries for names starting with "www" and NXDOMAIN otherwise,
+    /// counting how often it is asked.
+    struct CountingResolver {
+        calls: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Resolver for CountingResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let name = request.questions[0].name.clone();
+
+            if !name.starts_with("www") {
+                return Ok(DnsPacket::response_to(request)
+                    .with_rcode(ResponseCode::NXDomain)
+                    .with_authority(DnsRecord::new(
+                        "example.com".to_string(),
+                        Class::IN,
+                        3600,
+                        DnsRecordType::SOA {
+                            mname: "ns1.example.com".to_string(),
+                            rname: "hostmaster.example.com".to_string(),
+                            serial: 1,
+                            refresh: 7200,
+                            retry: 900,
+                            expire: 604800,
+                            minimum: 30,
+                        },
+                    )));
+            }
+
+            let ttl = if name.starts_with("www-long") { 7 * 86400 } else { 300 };
+            Ok(DnsPacket::response_to(request).with_answer(DnsRecord::new(
+                name,
+                Class::IN,
+                ttl,
+                DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) },
+            )))
+        }
+    }
+
+    fn setup(capacity: usize) -> (Arc<CountingResolver>, CachingResolver, RequestContext) {
+        let inner = Arc::new(CountingResolver { calls: AtomicU64::new(0) });
+        let cache = CachingResolver::new(inner.clone(), capacity);
+        let ctx = RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp);
+
+        (inner, cache, ctx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn serves_hits_with_decremented_ttl() {
+        let (inner, cache, ctx) = setup(10);
+        let query = DnsPacket::new_query(1, "www.example.com", RecordType::A);
+
+        cache.resolve(&query, &ctx).await.unwrap();
+        tokio::time::advance(Duration::from_secs(100)).await;
+        let response = cache.resolve(&DnsPacket::new_query(2, "WWW.example.com", RecordType::A), &ctx).await.unwrap();
+
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 1);
+        assert_eq!(response.header.id, 2);
+        assert_eq!(response.answers[0].ttl(), 200);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, entries: 1 });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hits_are_capped_at_max_ttl() {
+        let (_, cache, ctx) = setup(10);
+        let query = DnsPacket::new_query(1, "www-long.example.com", RecordType::A);
+
+        cache.resolve(&query, &ctx).await.unwrap();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let response = cache.resolve(&query, &ctx).await.unwrap();
+
+        assert_eq!(response.answers[0].ttl(), MAX_TTL - 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_entries_are_fetched_again() {
+        let (inner, cache, ctx) = setup(10);
+        let query = DnsPacket::new_query(1, "www.example.com", RecordType::A);
+
+        cache.resolve(&query, &ctx).await.unwrap();
+        tokio::time::advance(Duration::from_secs(301)).await;
+        cache.resolve(&query, &ctx).await.unwrap();
+
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caches_nxdomain_for_soa_minimum() {
+        let (inner, cache, ctx) = setup(10);
+        let query = DnsPacket::new_query(1, "missing.example.com", RecordType::A);
+
+        cache.resolve(&query, &ctx).await.unwrap();
+        tokio::time::advance(Duration::from_secs(20)).await;
+        let response = cache.resolve(&query, &ctx).await.unwrap();
+        assert_eq!(response.header.rcode, ResponseCode::NXDomain);
+        assert_eq!(response.authority[0].ttl(), 10);
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 1);
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        cache.resolve(&query, &ctx).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn evicts_least_recently_used() {
+        let (inner, cache, ctx) = setup(2);
+        let first = DnsPacket::new_query(1, "www1.example.com", RecordType::A);
+        let second = DnsPacket::new_query(2, "www2.example.com", RecordType::A);
+        let third = DnsPacket::new_query(3, "www3.example.com", RecordType::A);
+
+        cache.resolve(&first, &ctx).await.unwrap();
+        cache.resolve(&second, &ctx).await.unwrap();
+        cache.resolve(&first, &ctx).await.unwrap();
+        cache.resolve(&third, &ctx).await.unwrap();
+        assert_eq!(cache.stats().entries, 2);
+
+        cache.resolve(&first, &ctx).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 3);
+        cache.resolve(&second, &ctx).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 4);
+    }
+
+    /// Sets AA always and AD unless checking is disabled, like a validating
+    /// resolver in front of an authoritative one.
+    struct ValidatedResolver;
+
+    #[async_trait]
+    impl Resolver for ValidatedResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            let mut response = DnsPacket::response_to(request).with_answer(DnsRecord::new(
+                request.questions[0].name.clone(),
+                Class::IN,
+                300,
+                DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) },
+            ));
+            response.header.authoritative_answer = true;
+            response.header.authentic_data = !request.header.checking_disabled;
+            Ok(response)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hits_keep_flags_and_checking_disabled_answers_are_kept_apart() {
+        let (_, _, ctx) = setup(10);
+        let cache = CachingResolver::new(Arc::new(ValidatedResolver), 10);
+        let mut unchecked = DnsPacket::new_query(1, "www.example.com", RecordType::A);
+        unchecked.header.checking_disabled = true;
+        let checked = DnsPacket::new_query(2, "www.example.com", RecordType::A);
+
+        assert!(!cache.resolve(&unchecked, &ctx).await.unwrap().header.authentic_data);
+        cache.resolve(&checked, &ctx).await.unwrap();
+        let response = cache.resolve(&checked, &ctx).await.unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2, entries: 2 });
+        assert!(response.header.authoritative_answer);
+        assert!(response.header.authentic_data);
+    }
+}
@@ -0,0 +1,367 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use rand::seq::SliceRandom;
+use tokio::time::Instant;
+
+use crate::errors::{ErrorChain, QueryError};
+use crate::network::peer::{nslookup, DEFAULT_TIMEOUT};
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record_type::RecordType;
+
+use super::{RequestContext, Resolver};
+
+/// Consecutive failures after which an upstream is marked down.
+const MAX_FAILURES: u32 = 3;
+
+const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How a [`ForwardResolver`] picks the upstream to ask first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Each request starts with the next upstream in turn.
+    RoundRobin,
+    Random,
+    /// Lowest smoothed round trip time first.
+    Fastest,
+    /// Always in the configured order, the next one only if the previous failed.
+    Failover,
+}
+
+/// What a [`ForwardResolver`] currently knows about one of its upstreams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamStatus {
+    pub address: SocketAddr,
+    pub up: bool,
+    pub smoothed_rtt: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct UpstreamState {
+    smoothed_rtt: Option<Duration>,
+    failures: u32,
+    /// When a down upstream should be probed next; `None` while it is up.
+    next_check: Option<Instant>,
+    probing: bool,
+}
+
+#[derive(Debug)]
+struct Upstream {
+    address: SocketAddr,
+    state: Mutex<UpstreamState>,
+}
+
+/// Forwards requests to a pool of upstream servers.
+///
+/// When an upstream fails the next one is tried within the same request.
+/// Upstreams failing several times in a row are marked down and skipped
+/// until a background probe gets an answer from them again. If every
+/// upstream is down, all of them are tried anyway.
+#[derive(Debug)]
+pub struct ForwardResolver {
+    upstreams: Vec<Arc<Upstream>>,
+    strategy: Strategy,
+    timeout: Duration,
+    health_check_interval: Duration,
+    next: AtomicUsize,
+}
+
+impl Upstream {
+    fn is_up(&self) -> bool {
+        self.state.lock().unwrap().next_check.is_none()
+    }
+
+    fn record_success(&self, rtt: Duration) {
+        let mut state = self.state.lock().unwrap();
+
+        // Same smoothing as TCP (RFC 6298): 7/8 of the old value, 1/8 of the sample.
+        state.smoothed_rtt = Some(match state.smoothed_rtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+        state.failures = 0;
+        if state.next_check.take().is_some() {
+            info!("Upstream {} is back up", self.address);
+        }
+    }
+
+    fn record_failure(&self, health_check_interval: Duration) {
+        let mut state = self.state.lock().unwrap();
+
+        state.failures += 1;
+        if state.failures >= MAX_FAILURES && state.next_check.is_none() {
+            warn!("Upstream {} marked down after {} failures", self.address, state.failures);
+            state.next_check = Some(Instant::now() + health_check_interval);
+        }
+    }
+}
+
+impl ForwardResolver {
+    pub fn new(upstreams: Vec<SocketAddr>, strategy: Strategy) -> Self {
+        let upstreams = upstreams
+            .into_iter()
+            .map(|address| {
+                Arc::new(Upstream {
+                    address,
+                    state: Mutex::new(UpstreamState {
+                        smoothed_rtt: None,
+                        failures: 0,
+                        next_check: None,
+                        probing: false,
+                    }),
+                })
+            })
+            .collect();
+
+        ForwardResolver {
+            upstreams,
+            strategy,
+            timeout: DEFAULT_TIMEOUT,
+            health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets how long to wait for each upstream before trying the next one.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        ForwardResolver { timeout, ..self }
+    }
+
+    /// Sets how often upstreams that are down get probed.
+    pub fn with_health_check_interval(self, health_check_interval: Duration) -> Self {
+        ForwardResolver { health_check_interval, ..self }
+    }
+
+    pub fn upstreams(&self) -> Vec<UpstreamStatus> {
+        self.upstreams
+            .iter()
+            .map(|upstream| {
+                let state = upstream.state.lock().unwrap();
+                UpstreamStatus {
+                    address: upstream.address,
+                    up: state.next_check.is_none(),
+                    smoothed_rtt: state.smoothed_rtt,
+                }
+            })
+            .collect()
+    }
+
+    /// The upstreams to try for the next request, in order.
+    fn candidates(&self) -> Vec<Arc<Upstream>> {
+        let mut candidates: Vec<Arc<Upstream>> = self.upstreams.iter().filter(|u| u.is_up()).cloned().collect();
+        if candidates.is_empty() {
+            candidates = self.upstreams.clone();
+        }
+
+        match self.strategy {
+            Strategy::RoundRobin => {
+                if !candidates.is_empty() {
+                    let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                    candidates.rotate_left(start);
+                }
+            }
+            Strategy::Random => candidates.shuffle(&mut rand::thread_rng()),
+            // Upstreams without a measurement yet go first so they get one.
+            Strategy::Fastest => {
+                candidates.sort_by_key(|upstream| upstream.state.lock().unwrap().smoothed_rtt.unwrap_or_default())
+            }
+            Strategy::Failover => (),
+        }
+
+        candidates
+    }
+
+    /// Starts a probe for every down upstream whose check is due.
+    fn schedule_health_checks(&self) {
+        let now = Instant::now();
+
+        for upstream in &self.upstreams {
+            {
+                let mut state = upstream.state.lock().unwrap();
+                match state.next_check {
+                    Some(next_check) if next_check <= now && !state.probing => state.probing = true,
+                    _ => continue,
+                }
+            }
+
+            let upstream = upstream.clone();
+            let timeout = self.timeout;
+            let interval = self.health_check_interval;
+            tokio::spawn(async move {
+                // A root NS query, which any working server can answer.
+                let probe = DnsPacket::new_query(rand::random(), "", RecordType::NS);
+                let started = Instant::now();
+                let result = nslookup(upstream.address.ip(), upstream.address.port(), &probe, timeout).await;
+
+                match result {
+                    Ok(_) => upstream.record_success(started.elapsed()),
+                    Err(e) => {
+                        debug!("Health check failed: {}", ErrorChain(&e));
+                        upstream.state.lock().unwrap().next_check = Some(Instant::now() + interval);
+                    }
+                }
+                upstream.state.lock().unwrap().probing = false;
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for ForwardResolver {
+    async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        self.schedule_health_checks();
+
+        // A fresh ID, so clients can't predict the one upstream answers carry.
+        let mut query = request.clone();
+        let mut last_error = None;
+        for upstream in self.candidates() {
+            query.header.id = rand::random();
+            let started = Instant::now();
+            match nslookup(upstream.address.ip(), upstream.address.port(), &query, self.timeout).await {
+                Ok(mut response) => {
+                    upstream.record_success(started.elapsed());
+                    response.header.id = request.header.id;
+                    return Ok(response);
+                }
+                Err(e) => {
+                    debug!("Upstream failed: {}", ErrorChain(&e));
+                    upstream.record_failure(self.health_check_interval);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.map_or(QueryError::FailetToResolveQuery, QueryError::Lookup))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::atomic::AtomicU64;
+
+    use tokio::net::UdpSocket;
+
+    use super::*;
+    use crate::network::udp_server::Server;
+    use crate::resolver::Protocol;
+
+    /// Answers every request with an empty response, counting them.
+    struct CountingResolver {
+        calls: AtomicU64,
+        ids: Mutex<Vec<u16>>,
+    }
+
+    #[async_trait]
+    impl Resolver for CountingResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.ids.lock().unwrap().push(request.header.id);
+            Ok(DnsPacket::response_to(request))
+        }
+    }
+
+    async fn start_upstream(port: u16) -> (SocketAddr, Arc<CountingResolver>) {
+        let resolver = Arc::new(CountingResolver { calls: AtomicU64::new(0), ids: Mutex::new(Vec::new()) });
+        let server = Server::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port, resolver.clone())
+            .await
+            .unwrap();
+        let address = server.local_addr();
+        server.start();
+
+        (address, resolver)
+    }
+
+    /// An address nothing listens on, so queries to it fail right away.
+    async fn dead_upstream() -> SocketAddr {
+        UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap().local_addr().unwrap()
+    }
+
+    fn ctx() -> RequestContext {
+        RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp)
+    }
+
+    #[tokio::test]
+    async fn fails_over_and_marks_dead_upstreams_down() {
+        let dead = dead_upstream().await;
+        let (live, resolver) = start_upstream(0).await;
+        let forwarder = ForwardResolver::new(vec![dead, live], Strategy::Failover).with_timeout(Duration::from_millis(200));
+
+        for id in 0..MAX_FAILURES as u16 + 2 {
+            forwarder.resolve(&DnsPacket::new_query(id, "example.com", RecordType::A), &ctx()).await.unwrap();
+        }
+
+        assert_eq!(resolver.calls.load(Ordering::Relaxed), MAX_FAILURES as u64 + 2);
+        let status = forwarder.upstreams();
+        assert!(!status[0].up);
+        assert!(status[1].up);
+        assert!(status[1].smoothed_rtt.is_some());
+    }
+
+    #[tokio::test]
+    async fn round_robin_spreads_requests() {
+        let (first, first_calls) = start_upstream(0).await;
+        let (second, second_calls) = start_upstream(0).await;
+        let forwarder = ForwardResolver::new(vec![first, second], Strategy::RoundRobin);
+
+        for id in 0..4 {
+            forwarder.resolve(&DnsPacket::new_query(id, "example.com", RecordType::A), &ctx()).await.unwrap();
+        }
+
+        assert_eq!(first_calls.calls.load(Ordering::Relaxed), 2);
+        assert_eq!(second_calls.calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn upstream_queries_get_fresh_ids() {
+        let (address, upstream) = start_upstream(0).await;
+        let forwarder = ForwardResolver::new(vec![address], Strategy::Failover);
+
+        for _ in 0..4 {
+            let response = forwarder.resolve(&DnsPacket::new_query(7, "example.com", RecordType::A), &ctx()).await.unwrap();
+            assert_eq!(response.header.id, 7);
+        }
+
+        assert!(upstream.ids.lock().unwrap().iter().any(|id| *id != 7));
+    }
+
+    #[tokio::test]
+    async fn fastest_prefers_lowest_smoothed_rtt() {
+        let addresses = [dead_upstream().await, dead_upstream().await, dead_upstream().await];
+        let forwarder = ForwardResolver::new(addresses.to_vec(), Strategy::Fastest);
+        forwarder.upstreams[0].record_success(Duration::from_millis(80));
+        forwarder.upstreams[1].record_success(Duration::from_millis(10));
+        forwarder.upstreams[2].record_success(Duration::from_millis(40));
+
+        let order: Vec<SocketAddr> = forwarder.candidates().iter().map(|upstream| upstream.address).collect();
+
+        assert_eq!(order, vec![addresses[1], addresses[2], addresses[0]]);
+    }
+
+    #[tokio::test]
+    async fn down_upstreams_recover_after_health_check() {
+        let address = dead_upstream().await;
+        let forwarder = ForwardResolver::new(vec![address], Strategy::Failover)
+            .with_timeout(Duration::from_millis(200))
+            .with_health_check_interval(Duration::from_millis(20));
+
+        for id in 0..MAX_FAILURES as u16 {
+            assert!(forwarder.resolve(&DnsPacket::new_query(id, "example.com", RecordType::A), &ctx()).await.is_err());
+        }
+        assert!(!forwarder.upstreams()[0].up);
+
+        start_upstream(address.port()).await;
+        for id in 0..50 {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let _ = forwarder.resolve(&DnsPacket::new_query(id, "example.com", RecordType::A), &ctx()).await;
+            if forwarder.upstreams()[0].up {
+                return;
+            }
+        }
+        panic!("upstream never recovered");
+    }
+}
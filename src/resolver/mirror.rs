@@ -0,0 +1,42 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::errors::QueryError;
+use crate::network::peer::{nslookup, DEFAULT_TIMEOUT};
+use crate::protocol::dns_packet::DnsPacket;
+
+use super::{RequestContext, Resolver};
+
+/// Forwards every request to a single upstream server and returns its answer.
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorResolver {
+    mirror_address: IpAddr,
+    port: u16,
+    timeout: Duration,
+}
+
+impl MirrorResolver {
+    pub fn new(mirror_address: IpAddr, port: u16) -> Self {
+        MirrorResolver {
+            mirror_address,
+            port,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets how long to wait for the upstream before giving up on a query.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        MirrorResolver { timeout, ..self }
+    }
+}
+
+#[async_trait]
+impl Resolver for MirrorResolver {
+    async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        nslookup(self.mirror_address, self.port, request, self.timeout)
+            .await
+            .map_err(QueryError::Lookup)
+    }
+}
@@ -1,26 +1,54 @@
-use std::net::IpAddr;
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
 
-use crate::{network::peer::nslookup, protocol::dns_packet::DnsPacket};
 use crate::errors::QueryError;
+use crate::protocol::dns_packet::DnsPacket;
+
+pub mod authoritative;
+pub mod cache;
+pub mod forward;
+pub mod mirror;
+pub mod recursive;
+pub mod routing;
+pub mod validating;
 
+pub use authoritative::AuthoritativeResolver;
+pub use cache::CachingResolver;
+pub use forward::{ForwardResolver, Strategy};
+pub use mirror::MirrorResolver;
+pub use recursive::RecursiveResolver;
+pub use routing::RoutingResolver;
+pub use validating::ValidatingResolver;
+
+/// Transport a request arrived on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Udp,
+    Tcp,
+}
 
-#[derive(Debug, Clone, Copy)]
-pub enum ResolverType {
-    Mirror { mirror_address: IpAddr, port: u16 }
+/// Information about where a request came from, handed to every resolver.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub client_address: SocketAddr,
+    pub protocol: Protocol,
 }
-impl ResolverType {
-    pub fn resolve(&self, query: DnsPacket) -> Result<DnsPacket, QueryError> {
-        match self {
-            ResolverType::Mirror { mirror_address, port } => {
-                let response = nslookup(*mirror_address, *port, &query);
-                
-                if let Err(_) = response {
-                    return Err(QueryError::FailedToDeserializeResponse);
-                }
-                let response = response.unwrap();
-                
-                return Ok(response);
-            }
+
+impl RequestContext {
+    pub fn new(client_address: SocketAddr, protocol: Protocol) -> Self {
+        RequestContext {
+            client_address,
+            protocol,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Turns a DNS request into a response.
+///
+/// The server holds its resolver as an `Arc<dyn Resolver>`, so any type
+/// implementing this trait can be plugged into [`crate::builder::ServerBuilder::set_resolver`].
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError>;
+}
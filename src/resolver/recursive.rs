@@ -0,0 +1,532 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr};
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::errors::{ErrorChain, GiveUpReason, QueryError};
+use crate::network::peer::{nslookup, DEFAULT_TIMEOUT};
+use crate::protocol::dns_header::ResponseCode;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{DnsRecordType, RecordType};
+use crate::protocol::domain_name::{is_subdomain_of, label_count, normalize};
+use crate::protocol::edns::Edns;
+
+use super::{RequestContext, Resolver};
+
+/// IPv4 addresses of the 13 root servers, a.root-servers.net to m.root-servers.net.
+pub const ROOT_HINTS: [IpAddr; 13] = [
+    IpAddr::V4(Ipv4Addr::new(198, 41, 0, 4)),
+    IpAddr::V4(Ipv4Addr::new(170, 247, 170, 2)),
+    IpAddr::V4(Ipv4Addr::new(192, 33, 4, 12)),
+    IpAddr::V4(Ipv4Addr::new(199, 7, 91, 13)),
+    IpAddr::V4(Ipv4Addr::new(192, 203, 230, 10)),
+    IpAddr::V4(Ipv4Addr::new(192, 5, 5, 241)),
+    IpAddr::V4(Ipv4Addr::new(192, 112, 36, 4)),
+    IpAddr::V4(Ipv4Addr::new(198, 97, 190, 53)),
+    IpAddr::V4(Ipv4Addr::new(192, 36, 148, 17)),
+    IpAddr::V4(Ipv4Addr::new(192, 58, 128, 30)),
+    IpAddr::V4(Ipv4Addr::new(193, 0, 14, 129)),
+    IpAddr::V4(Ipv4Addr::new(199, 7, 83, 42)),
+    IpAddr::V4(Ipv4Addr::new(202, 12, 27, 33)),
+];
+
+/// How many nested lookups (missing glue, CNAME targets) one query may start.
+const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// How many referrals are followed for a single name.
+const MAX_REFERRALS: usize = 16;
+
+/// Upper bound on the upstream queries sent while answering one request.
+const MAX_QUERIES: usize = 64;
+
+type LookupFuture<'a> = Pin<Box<dyn Future<Output = Result<DnsPacket, QueryError>> + Send + 'a>>;
+
+/// Resolves names itself by walking the delegation tree down from the root
+/// servers, instead of relying on another recursive resolver.
+#[derive(Debug, Clone)]
+pub struct RecursiveResolver {
+    root_hints: Vec<IpAddr>,
+    port: u16,
+    timeout: Duration,
+    max_depth: usize,
+}
+
+/// Limits shared by every nested lookup made for one request.
+struct Budget {
+    queries: usize,
+    /// Names and types being resolved, to detect lookups that depend on themselves.
+    in_progress: HashSet<(String, RecordType)>,
+    /// Whether to ask name servers for DNSSEC records.
+    dnssec_ok: bool,
+}
+
+impl Default for RecursiveResolver {
+    fn default() -> Self {
+        RecursiveResolver::new()
+    }
+}
+
+impl RecursiveResolver {
+    pub fn new() -> Self {
+        RecursiveResolver {
+            root_hints: ROOT_HINTS.to_vec(),
+            port: 53,
+            timeout: DEFAULT_TIMEOUT,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Replaces the built-in root server addresses.
+    pub fn with_root_hints(self, root_hints: Vec<IpAddr>) -> Self {
+        RecursiveResolver { root_hints, ..self }
+    }
+
+    /// Port used to reach every name server, 53 unless testing.
+    pub fn with_port(self, port: u16) -> Self {
+        RecursiveResolver { port, ..self }
+    }
+
+    /// Timeout for each query sent to a name server.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        RecursiveResolver { timeout, ..self }
+    }
+
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        RecursiveResolver { max_depth, ..self }
+    }
+
+    /// Resolves `name`, returning the final response with any CNAMEs
+    /// followed on the way prepended to its answers.
+    fn lookup<'a>(&'a self, name: String, qtype: RecordType, depth: usize, budget: &'a mut Budget) -> LookupFuture<'a> {
+        Box::pin(async move {
+            if depth > self.max_depth {
+                debug!("Giving up on {}: maximum depth reached", name);
+                return Err(gave_up(&name, GiveUpReason::MaxDepthReached));
+            }
+            let key = (normalize(&name), qtype);
+            if !budget.in_progress.insert(key.clone()) {
+                debug!("Giving up on {}: lookup loop detected", name);
+                return Err(gave_up(&name, GiveUpReason::LookupLoop));
+            }
+
+            let result = self.iterate(&name, qtype, depth, budget).await;
+            budget.in_progress.remove(&key);
+
+            result
+        })
+    }
+
+    async fn iterate(&self, name: &str, qtype: RecordType, depth: usize, budget: &mut Budget) -> Result<DnsPacket, QueryError> {
+        let mut zone = String::new();
+        let mut servers = self.root_hints.clone();
+
+        for _ in 0..MAX_REFERRALS {
+            let response = self.query_servers(&servers, name, qtype, budget).await?;
+
+            if response.header.rcode != ResponseCode::NoError || !response.answers.is_empty() {
+                return self.follow_cnames(name, qtype, &zone, response, depth, budget).await;
+            }
+
+            let Some((child_zone, name_servers)) = referral(&response, name, &zone) else {
+                // NODATA, the SOA (if any) is already in the authority section.
+                return Ok(response);
+            };
+            debug!("{} referred to {} ({:?})", name, child_zone, name_servers);
+
+            let mut addresses = glue(&response, &name_servers, &zone);
+            if addresses.is_empty() {
+                addresses = self.resolve_name_servers(&name_servers, depth, budget).await?;
+            }
+            if addresses.is_empty() {
+                debug!("No usable name server address for {}", child_zone);
+                return Err(gave_up(name, GiveUpReason::NoNameServerAddress { zone: child_zone }));
+            }
+
+            zone = child_zone;
+            servers = addresses;
+        }
+
+        debug!("Giving up on {}: too many referrals", name);
+        Err(gave_up(name, GiveUpReason::TooManyReferrals))
+    }
+
+    /// Asks each server in turn until one of them answers.
+    async fn query_servers(&self, servers: &[IpAddr], name: &str, qtype: RecordType, budget: &mut Budget) -> Result<DnsPacket, QueryError> {
+        let mut last_error = None;
+        for server in servers {
+            if budget.queries >= MAX_QUERIES {
+                debug!("Giving up on {}: query budget exhausted", name);
+                return Err(gave_up(name, GiveUpReason::QueryBudgetExhausted));
+            }
+            budget.queries += 1;
+
+            let mut query = DnsPacket::new_query(rand::random(), name, qtype);
+            query.header.recursion_desired = false;
+            if budget.dnssec_ok {
+                query.edns = Some(Edns { dnssec_ok: true, ..Edns::default() });
+            }
+
+            match nslookup(*server, self.port, &query, self.timeout).await {
+                Ok(response) if is_usable(&response) => return Ok(response),
+                Ok(response) => debug!("{} answered {:?} for {}", server, response.header.rcode, name),
+                Err(e) => {
+                    debug!("Lookup of {} failed: {}", name, ErrorChain(&e));
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.map_or(QueryError::FailetToResolveQuery, QueryError::Lookup))
+    }
+
+    /// Completes a response that may only contain CNAMEs for `name`.
+    ///
+    /// Records for a CNAME target are only taken from the response when the
+    /// target lies in `zone`, the zone of the server that answered. Other
+    /// targets are looked up again from the root.
+    async fn follow_cnames(
+        &self,
+        name: &str,
+        qtype: RecordType,
+        zone: &str,
+        mut response: DnsPacket,
+        depth: usize,
+        budget: &mut Budget,
+    ) -> Result<DnsPacket, QueryError> {
+        let mut chain = Vec::new();
+        let mut current = name.to_string();
+
+        // Walk the chain as far as the server answered it.
+        loop {
+            let owned: Vec<&DnsRecord> = response
+                .answers
+                .iter()
+                .filter(|record| record.record().eq_ignore_ascii_case(&current))
+                .collect();
+
+            if owned.iter().any(|record| record.rdata().get_type() == qtype) || qtype == RecordType::CNAME {
+                chain.extend(owned.into_iter().cloned());
+                response.answers = chain;
+                return Ok(response);
+            }
+
+            let target = owned.iter().find_map(|record| match record.rdata() {
+                DnsRecordType::CNAME { canonical_name } => Some(canonical_name.clone()),
+                _ => None,
+            });
+            let Some(target) = target else {
+                response.answers = chain;
+                return Ok(response);
+            };
+            if chain.len() > self.max_depth {
+                debug!("Giving up on {}: CNAME chain too long", name);
+                return Err(gave_up(name, GiveUpReason::CnameChainTooLong));
+            }
+
+            chain.extend(owned.into_iter().cloned());
+            let answered_further = is_subdomain_of(&target, zone)
+                && response
+                    .answers
+                    .iter()
+                    .any(|record| record.record().eq_ignore_ascii_case(&target));
+            current = target;
+            if !answered_further {
+                break;
+            }
+        }
+
+        let mut rest = self.lookup(current, qtype, depth + 1, budget).await?;
+        chain.append(&mut rest.answers);
+        rest.answers = chain;
+
+        Ok(rest)
+    }
+
+    /// Looks up the addresses of the first name server that has any. When
+    /// none has, fails with the error of the last lookup that failed.
+    async fn resolve_name_servers(&self, name_servers: &[String], depth: usize, budget: &mut Budget) -> Result<Vec<IpAddr>, QueryError> {
+        let mut last_error = None;
+        for name_server in name_servers {
+            let response = match self.lookup(name_server.clone(), RecordType::A, depth + 1, budget).await {
+                Ok(response) => response,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+
+            let addresses = addresses_of(&response.answers, name_server);
+            if !addresses.is_empty() {
+                return Ok(addresses);
+            }
+        }
+
+        last_error.map_or(Ok(Vec::new()), Err)
+    }
+}
+
+/// Whether a name server gave an answer worth acting on.
+fn is_usable(response: &DnsPacket) -> bool {
+    matches!(response.header.rcode, ResponseCode::NoError | ResponseCode::NXDomain)
+}
+
+/// The zone and name servers a response delegates `name` to, if it is a
+/// referral strictly below the zone we asked.
+fn referral(response: &DnsPacket, name: &str, zone: &str) -> Option<(String, Vec<String>)> {
+    let mut child_zone = None;
+    let mut name_servers = Vec::new();
+
+    for record in &response.authority {
+        let DnsRecordType::NS { name_server } = record.rdata() else {
+            continue;
+        };
+        let owner = normalize(record.record());
+
+        // Only accept delegations towards the name, and deeper than where we are.
+        if !is_subdomain_of(name, &owner) || !is_subdomain_of(&owner, zone) || label_count(&owner) <= label_count(zone) {
+            continue;
+        }
+        if child_zone.get_or_insert_with(|| owner.clone()) == &owner {
+            name_servers.push(normalize(name_server));
+        }
+    }
+
+    child_zone.map(|child_zone| (child_zone, name_servers))
+}
+
+/// Addresses for `name_servers` found in the additional section, ignoring
+/// records the responding server isn't authoritative for.
+fn glue(response: &DnsPacket, name_servers: &[String], zone: &str) -> Vec<IpAddr> {
+    let in_bailiwick: Vec<DnsRecord> = response
+        .additional
+        .iter()
+        .filter(|record| is_subdomain_of(record.record(), zone))
+        .cloned()
+        .collect();
+
+    name_servers
+        .iter()
+        .flat_map(|name_server| addresses_of(&in_bailiwick, name_server))
+        .collect()
+}
+
+fn addresses_of(records: &[DnsRecord], name: &str) -> Vec<IpAddr> {
+    records
+        .iter()
+        .filter(|record| record.record().eq_ignore_ascii_case(name))
+        .filter_map(|record| match record.rdata() {
+            DnsRecordType::A { address } => Some(IpAddr::V4(*address)),
+            DnsRecordType::AAAA { address } => Some(IpAddr::V6(*address)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn gave_up(name: &str, reason: GiveUpReason) -> QueryError {
+    QueryError::GaveUp { name: name.to_string(), reason }
+}
+
+#[async_trait]
+impl Resolver for RecursiveResolver {
+    async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        let Some(question) = request.questions.first() else {
+            return Ok(DnsPacket::response_to(request).with_rcode(ResponseCode::FormatError));
+        };
+
+        let dnssec_ok = request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok);
+        let mut budget = Budget { queries: 0, in_progress: HashSet::new(), dnssec_ok };
+        let resolution = self
+            .lookup(question.name.clone(), question.qtype, 0, &mut budget)
+            .await?;
+
+        let mut response = DnsPacket::response_to(request).with_rcode(resolution.header.rcode);
+        response.header.recursion_available = true;
+        for record in resolution.answers {
+            response = response.with_answer(record);
+        }
+        if response.answers.is_empty() || resolution.header.rcode == ResponseCode::NXDomain {
+            // Keep the SOA, plus the NSEC records and signatures that prove
+            // the denial when DNSSEC records were asked for.
+            for record in resolution.authority {
+                let wanted = match record.rdata().get_type() {
+                    RecordType::SOA => true,
+                    RecordType::NSEC | RecordType::NSEC3 | RecordType::RRSIG => dnssec_ok,
+                    _ => false,
+                };
+                if wanted {
+                    response = response.with_authority(record);
+                }
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::network::udp_server::Server;
+    use crate::protocol::dns_record_type::Class;
+    use crate::resolver::{AuthoritativeResolver, Protocol};
+    use crate::zone::Zone;
+
+    const ROOT: &str = r#"
+$ORIGIN .
+$TTL 3600
+@               SOA ns.root. hostmaster.root. 1 7200 900 604800 300
+                NS  ns.root.
+ns.root.        A   127.0.0.1
+com.            NS  ns.com.
+ns.com.         A   127.0.0.2
+"#;
+
+    const COM: &str = r#"
+$ORIGIN com.
+$TTL 3600
+@               SOA ns hostmaster 1 7200 900 604800 300
+                NS  ns
+ns              A   127.0.0.2
+example         NS  ns1.example
+ns1.example     A   127.0.0.3
+noglue          NS  ns1.example.com.
+loop-a          NS  ns.loop-b.com.
+loop-b          NS  ns.loop-a.com.
+evil            NS  ns.evil
+ns.evil         A   127.0.0.4
+"#;
+
+    const EXAMPLE: &str = r#"
+$ORIGIN example.com.
+$TTL 3600
+@               SOA ns1 hostmaster 1 7200 900 604800 300
+                NS  ns1
+ns1             A   127.0.0.3
+www             A   192.0.2.80
+alias           CNAME www.noglue.com.
+"#;
+
+    const NOGLUE: &str = r#"
+$ORIGIN noglue.com.
+$TTL 3600
+@               SOA ns1.example.com. hostmaster 1 7200 900 604800 300
+                NS  ns1.example.com.
+www             A   192.0.2.81
+"#;
+
+    /// Starts the stand-in authoritative servers on 127.0.0.1-3, all
+    /// listening on the same port, and returns that port.
+    async fn start_hierarchy() -> u16 {
+        let zones: [(u8, Vec<Zone>); 3] = [
+            (1, vec![Zone::parse(ROOT, "").unwrap()]),
+            (2, vec![Zone::parse(COM, "com").unwrap()]),
+            (3, vec![Zone::parse(EXAMPLE, "example.com").unwrap(), Zone::parse(NOGLUE, "noglue.com").unwrap()]),
+        ];
+
+        let mut port = 0;
+        for (host, zones) in zones {
+            let address = IpAddr::V4(Ipv4Addr::new(127, 0, 0, host));
+            let server = Server::new(address, port, Arc::new(AuthoritativeResolver::new(zones)))
+                .await
+                .unwrap();
+            port = server.local_addr().port();
+            server.start();
+        }
+
+        Server::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 4)), port, Arc::new(PoisoningResolver))
+            .await
+            .unwrap()
+            .start();
+
+        port
+    }
+
+    /// Serves evil.com, answering every query with a CNAME to
+    /// www.example.com along with a forged address for it.
+    struct PoisoningResolver;
+
+    #[async_trait]
+    impl Resolver for PoisoningResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            let mut response = DnsPacket::response_to(request)
+                .with_answer(DnsRecord::new(
+                    request.questions[0].name.clone(),
+                    Class::IN,
+                    3600,
+                    DnsRecordType::CNAME { canonical_name: "www.example.com".to_string() },
+                ))
+                .with_answer(DnsRecord::new(
+                    "www.example.com".to_string(),
+                    Class::IN,
+                    3600,
+                    DnsRecordType::A { address: Ipv4Addr::new(203, 0, 113, 66) },
+                ));
+            response.header.authoritative_answer = true;
+            Ok(response)
+        }
+    }
+
+    async fn resolve(name: &str) -> Result<DnsPacket, QueryError> {
+        let port = start_hierarchy().await;
+        let resolver = RecursiveResolver::new()
+            .with_root_hints(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .with_port(port)
+            .with_timeout(Duration::from_millis(500));
+        let ctx = RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp);
+
+        resolver.resolve(&DnsPacket::new_query(1, name, RecordType::A), &ctx).await
+    }
+
+    #[tokio::test]
+    async fn follows_referrals_using_glue() {
+        let response = resolve("www.example.com").await.unwrap();
+
+        assert!(response.header.recursion_available);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].rdata(), &DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 80) });
+    }
+
+    #[tokio::test]
+    async fn resolves_missing_glue_and_chases_cnames() {
+        let response = resolve("alias.example.com").await.unwrap();
+
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.answers[0].rdata(), &DnsRecordType::CNAME { canonical_name: "www.noglue.com".to_string() });
+        assert_eq!(response.answers[1].rdata(), &DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 81) });
+    }
+
+    #[tokio::test]
+    async fn ignores_cname_targets_outside_the_answering_zone() {
+        let response = resolve("x.evil.com").await.unwrap();
+
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.answers[1].record(), "www.example.com");
+        assert_eq!(response.answers[1].rdata(), &DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 80) });
+    }
+
+    #[tokio::test]
+    async fn returns_nxdomain_with_soa() {
+        let response = resolve("missing.example.com").await.unwrap();
+
+        assert_eq!(response.header.rcode, ResponseCode::NXDomain);
+        assert!(matches!(response.authority[0].rdata(), DnsRecordType::SOA { .. }));
+    }
+
+    #[tokio::test]
+    async fn detects_delegation_loops() {
+        let error = resolve("www.loop-a.com").await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            QueryError::GaveUp { name, reason: GiveUpReason::LookupLoop } if name == "ns.loop-b.com"
+        ));
+    }
+}
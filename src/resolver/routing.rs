@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::errors::QueryError;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::domain_name::{normalize, parent};
+
+use super::{RequestContext, Resolver};
+
+/// Sends each request to the resolver of the longest domain suffix matching
+/// its question, or to the default resolver when no rule matches.
+///
+/// Rules match on whole labels, so a rule for `consul` covers
+/// `web.service.consul` but not `notconsul`.
+pub struct RoutingResolver {
+    routes: HashMap<String, Arc<dyn Resolver>>,
+    default: Arc<dyn Resolver>,
+}
+
+impl RoutingResolver {
+    pub fn new(default: Arc<dyn Resolver>) -> Self {
+        RoutingResolver {
+            routes: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Routes `domain` and every name below it to `resolver`, replacing any
+    /// earlier rule for the same domain.
+    pub fn with_route(mut self, domain: &str, resolver: Arc<dyn Resolver>) -> Self {
+        self.routes.insert(normalize(domain), resolver);
+        self
+    }
+
+    /// The resolver responsible for `name`.
+    fn route(&self, name: &str) -> &Arc<dyn Resolver> {
+        let name = normalize(name);
+        let mut current = Some(name.as_str());
+
+        while let Some(candidate) = current {
+            if let Some(resolver) = self.routes.get(candidate) {
+                return resolver;
+            }
+            current = parent(candidate);
+        }
+
+        &self.default
+    }
+}
+
+#[async_trait]
+impl Resolver for RoutingResolver {
+    async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        let resolver = match request.questions.first() {
+            Some(question) => self.route(&question.name),
+            None => &self.default,
+        };
+
+        resolver.resolve(request, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use super::*;
+    use crate::protocol::dns_record::DnsRecord;
+    use crate::protocol::dns_record_type::{Class, DnsRecordType, RecordType};
+    use crate::resolver::Protocol;
+
+    /// Answers every request with a single A record identifying itself.
+    struct TaggedResolver(u8);
+
+    #[async_trait]
+    impl Resolver for TaggedResolver {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            Ok(DnsPacket::response_to(request).with_answer(DnsRecord::new(
+                request.questions[0].name.clone(),
+                Class::IN,
+                60,
+                DnsRecordType::A { address: Ipv4Addr::new(10, 0, 0, self.0) },
+            )))
+        }
+    }
+
+    async fn routed_to(resolver: &RoutingResolver, name: &str) -> u8 {
+        let ctx = RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp);
+        let response = resolver.resolve(&DnsPacket::new_query(1, name, RecordType::A), &ctx).await.unwrap();
+
+        match response.answers[0].rdata() {
+            DnsRecordType::A { address } => address.octets()[3],
+            other => panic!("unexpected record {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_longest_matching_suffix() {
+        let resolver = RoutingResolver::new(Arc::new(TaggedResolver(0)))
+            .with_route("internal.", Arc::new(TaggedResolver(1)))
+            .with_route("corp.internal.", Arc::new(TaggedResolver(2)))
+            .with_route("consul", Arc::new(TaggedResolver(3)));
+
+        assert_eq!(routed_to(&resolver, "host.CORP.internal").await, 2);
+        assert_eq!(routed_to(&resolver, "corp.internal").await, 2);
+        assert_eq!(routed_to(&resolver, "other.internal").await, 1);
+        assert_eq!(routed_to(&resolver, "web.service.consul").await, 3);
+        assert_eq!(routed_to(&resolver, "notconsul").await, 0);
+        assert_eq!(routed_to(&resolver, "example.com").await, 0);
+    }
+}
@@ -0,0 +1,734 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use log::debug;
+
+use crate::dnssec::denial::{self, MissingDs};
+use crate::dnssec::{self, REVOKE_FLAG, ZONE_KEY_FLAG};
+use crate::errors::{ErrorChain, QueryError, ValidationError};
+use crate::protocol::dns_header::ResponseCode;
+use crate::protocol::dns_packet::DnsPacket;
+use crate::protocol::dns_query::Question;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{DnsRecordType, RecordType};
+use crate::protocol::domain_name::{is_subdomain_of, label_count, normalize, parent};
+use crate::protocol::edns::Edns;
+
+use super::{RequestContext, Resolver};
+
+/// Upper bound on the DS and DNSKEY lookups made to validate one response.
+const MAX_QUERIES: usize = 32;
+
+/// Checks the DNSSEC signatures of the answers of another resolver.
+///
+/// Responses proven secure from a trust anchor get the AD bit, those from
+/// unsigned zones pass through without it, and bogus ones are replaced by
+/// SERVFAIL. Requests with the CD bit are passed through unchecked.
+pub struct ValidatingResolver {
+    inner: Arc<dyn Resolver>,
+    trust_anchors: Vec<DnsRecord>,
+}
+
+impl ValidatingResolver {
+    /// Validates the answers of `inner` from the root zone KSK.
+    pub fn new(inner: Arc<dyn Resolver>) -> Self {
+        ValidatingResolver {
+            inner,
+            trust_anchors: vec![dnssec::root_trust_anchor()],
+        }
+    }
+
+    /// Replaces the trust anchors with DS or DNSKEY records, each owned by
+    /// the zone it anchors.
+    pub fn with_trust_anchors(self, trust_anchors: Vec<DnsRecord>) -> Self {
+        ValidatingResolver { trust_anchors, ..self }
+    }
+}
+
+/// What is known about a name while walking down from a trust anchor.
+#[derive(Debug, Clone)]
+enum Cut {
+    /// A signed zone starts here, with these validated DNSKEYs.
+    Secure(Vec<DnsRecordType>),
+    /// An unsigned zone starts here.
+    Insecure,
+    /// The name belongs to the zone above it.
+    None,
+    /// The name doesn't exist, so neither does anything below it.
+    NxDomain,
+}
+
+/// The keys of the signed zone a name belongs to.
+enum ZoneKeys {
+    Secure { zone: String, keys: Vec<DnsRecordType> },
+    Insecure,
+}
+
+/// The outcome of checking one RRset.
+enum Security {
+    Secure,
+    /// Secure, synthesized from the wildcard below this closest encloser.
+    Expanded(String),
+    Insecure,
+}
+
+/// State shared by the lookups made to validate one response.
+struct Validation<'a> {
+    inner: &'a dyn Resolver,
+    trust_anchors: &'a [DnsRecord],
+    ctx: &'a RequestContext,
+    now: u32,
+    queries: usize,
+    cuts: HashMap<String, Cut>,
+}
+
+impl<'a> Validation<'a> {
+    /// Whether `response` to `question` is secure, or an error if it is bogus.
+    async fn validate(&mut self, response: &DnsPacket, question: &Question) -> Result<bool, ValidationError> {
+        let mut secure = true;
+        let mut expansions = Vec::new();
+
+        for (rrset, rrsigs) in rrsets(&response.answers) {
+            match self.verify(&rrset, &rrsigs).await? {
+                Security::Secure => {}
+                Security::Expanded(closest_encloser) => expansions.push((normalize(rrset[0].record()), closest_encloser)),
+                Security::Insecure => secure = false,
+            }
+        }
+
+        let mut denial = Vec::new();
+        for (rrset, rrsigs) in rrsets(&response.authority) {
+            let record_type = rrset[0].rdata().get_type();
+            if !matches!(record_type, RecordType::NSEC | RecordType::NSEC3 | RecordType::SOA) {
+                continue;
+            }
+            match self.verify(&rrset, &rrsigs).await? {
+                Security::Insecure => secure = false,
+                _ if record_type != RecordType::SOA => denial.extend(rrset),
+                _ => {}
+            }
+        }
+
+        // Whatever is missing is missing at the end of the CNAME chain.
+        let mut name = normalize(&question.name);
+        if question.qtype != RecordType::CNAME {
+            for _ in 0..response.answers.len() {
+                let target = response.answers.iter().find_map(|record| match record.rdata() {
+                    DnsRecordType::CNAME { canonical_name } if same_name(record.record(), &name) => Some(normalize(canonical_name)),
+                    _ => None,
+                });
+                match target {
+                    Some(target) => name = target,
+                    None => break,
+                }
+            }
+        }
+        let answered = response.answers.iter().any(|record| {
+            same_name(record.record(), &name)
+                && (question.qtype == RecordType::ANY || record.rdata().get_type() == question.qtype)
+        });
+
+        let proven = match response.header.rcode {
+            ResponseCode::NXDomain => denial::proves_nxdomain(&denial, &name),
+            _ if !answered => denial::proves_nodata(&denial, &name, question.qtype),
+            _ => true,
+        };
+        if !proven {
+            match self.closest_keys(&name).await? {
+                ZoneKeys::Insecure => secure = false,
+                ZoneKeys::Secure { .. } => return Err(ValidationError::MissingDenial { name }),
+            }
+        }
+
+        for (owner, closest_encloser) in expansions {
+            if !denial::proves_wildcard_expansion(&denial, &owner, &closest_encloser) {
+                return Err(ValidationError::MissingDenial { name: owner });
+            }
+        }
+
+        Ok(secure)
+    }
+
+    /// Checks `rrset` against the keys of the zones that may have signed it.
+    async fn verify(&mut self, rrset: &[DnsRecord], rrsigs: &[DnsRecord]) -> Result<Security, ValidationError> {
+        let owner = normalize(rrset[0].record());
+        let record_type = rrset[0].rdata().get_type();
+
+        let mut signers: Vec<String> = rrsigs
+            .iter()
+            .filter_map(|rrsig| match rrsig.rdata() {
+                DnsRecordType::RRSIG { signer_name, .. } => Some(normalize(signer_name)),
+                _ => None,
+            })
+            // A DS RRset is signed by the parent zone, never by the zone it points to.
+            .filter(|signer| is_subdomain_of(&owner, signer) && !(record_type == RecordType::DS && *signer == owner))
+            .collect();
+        signers.sort();
+        signers.dedup();
+
+        for signer in signers {
+            match self.closest_keys(&signer).await? {
+                ZoneKeys::Insecure => return Ok(Security::Insecure),
+                ZoneKeys::Secure { zone, keys } if zone == signer => {
+                    if let Some(expanded) = check_signatures(rrset, rrsigs, &zone, &keys, self.now) {
+                        return Ok(expanded.map_or(Security::Secure, Security::Expanded));
+                    }
+                }
+                ZoneKeys::Secure { .. } => {}
+            }
+        }
+
+        // Nothing verified, which is only fine for data from unsigned zones.
+        let zone_of = match record_type {
+            RecordType::DS => parent(&owner).unwrap_or_default().to_string(),
+            _ => owner.clone(),
+        };
+        match self.closest_keys(&zone_of).await? {
+            ZoneKeys::Insecure => Ok(Security::Insecure),
+            ZoneKeys::Secure { .. } if rrsigs.is_empty() => Err(ValidationError::MissingSignature { name: owner, record_type }),
+            ZoneKeys::Secure { .. } => Err(ValidationError::BadSignature { name: owner, record_type }),
+        }
+    }
+
+    /// Walks from the closest trust anchor down to `name`, following the
+    /// chain of DS and DNSKEY records, to find the zone `name` belongs to.
+    async fn closest_keys(&mut self, name: &str) -> Result<ZoneKeys, ValidationError> {
+        let name = normalize(name);
+        let Some(anchor) = self
+            .trust_anchors
+            .iter()
+            .map(|anchor| normalize(anchor.record()))
+            .filter(|anchor| is_subdomain_of(&name, anchor))
+            .max_by_key(|anchor| label_count(anchor))
+        else {
+            return Ok(ZoneKeys::Insecure);
+        };
+
+        let Cut::Secure(mut keys) = self.find_cut(&anchor, None).await? else {
+            return Ok(ZoneKeys::Insecure);
+        };
+        let mut zone = anchor;
+
+        let labels: Vec<&str> = name.split('.').collect();
+        for start in (0..label_count(&name) - label_count(&zone)).rev() {
+            let child = labels[start..].join(".");
+            match self.find_cut(&child, Some((&zone, &keys))).await? {
+                Cut::Secure(child_keys) => {
+                    zone = child;
+                    keys = child_keys;
+                }
+                Cut::Insecure => return Ok(ZoneKeys::Insecure),
+                Cut::None => {}
+                Cut::NxDomain => break,
+            }
+        }
+
+        Ok(ZoneKeys::Secure { zone, keys })
+    }
+
+    /// Finds out whether a zone starts at `name`, with `parent` being the
+    /// zone and keys right above it.
+    async fn find_cut(&mut self, name: &str, parent: Option<(&str, &[DnsRecordType])>) -> Result<Cut, ValidationError> {
+        if let Some(cut) = self.cuts.get(name) {
+            return Ok(cut.clone());
+        }
+
+        let anchors: Vec<DnsRecordType> = self
+            .trust_anchors
+            .iter()
+            .filter(|anchor| same_name(anchor.record(), name))
+            .map(|anchor| anchor.rdata().clone())
+            .collect();
+
+        let cut = match (anchors.is_empty(), parent) {
+            (false, _) | (true, None) => self.zone_keys(name, anchors).await?,
+            (true, Some((parent_zone, parent_keys))) => {
+                let response = self.fetch(name, RecordType::DS).await?;
+                let ds_set: Vec<DnsRecord> = response
+                    .answers
+                    .iter()
+                    .filter(|record| same_name(record.record(), name) && record.rdata().get_type() == RecordType::DS)
+                    .cloned()
+                    .collect();
+
+                if !ds_set.is_empty() {
+                    let rrsigs = covering_rrsigs(&response.answers, name, RecordType::DS);
+                    if check_signatures(&ds_set, &rrsigs, parent_zone, parent_keys, self.now).is_none() {
+                        return Err(ValidationError::BadSignature { name: name.to_string(), record_type: RecordType::DS });
+                    }
+                    self.zone_keys(name, ds_set.iter().map(|ds| ds.rdata().clone()).collect()).await?
+                } else {
+                    let mut proof = Vec::new();
+                    for (rrset, rrsigs) in rrsets(&response.authority).into_iter().chain(rrsets(&response.answers)) {
+                        if check_signatures(&rrset, &rrsigs, parent_zone, parent_keys, self.now).is_some() {
+                            proof.extend(rrset);
+                        }
+                    }
+
+                    let is_alias = proof
+                        .iter()
+                        .any(|record| same_name(record.record(), name) && record.rdata().get_type() == RecordType::CNAME);
+                    if response.header.rcode == ResponseCode::NXDomain && denial::proves_nxdomain(&proof, name) {
+                        Cut::NxDomain
+                    } else if is_alias {
+                        // Zone cuts can't be aliases.
+                        Cut::None
+                    } else {
+                        match denial::missing_ds(&proof, name) {
+                            Some(MissingDs::InsecureDelegation) => Cut::Insecure,
+                            Some(MissingDs::NotAZoneCut) => Cut::None,
+                            None => return Err(ValidationError::MissingDenial { name: name.to_string() }),
+                        }
+                    }
+                }
+            }
+        };
+
+        debug!("Zone cut at {:?}: {:?}", name, cut);
+        self.cuts.insert(name.to_string(), cut.clone());
+        Ok(cut)
+    }
+
+    /// Fetches and validates the DNSKEYs of the zone `name`, trusting those
+    /// that match one of the DS or DNSKEY records in `trusted`.
+    async fn zone_keys(&mut self, name: &str, trusted: Vec<DnsRecordType>) -> Result<Cut, ValidationError> {
+        // RFC 4035 5.2: a zone only signed with unknown algorithms is treated as unsigned.
+        let trusted: Vec<DnsRecordType> = trusted
+            .into_iter()
+            .filter(|record| match record {
+                DnsRecordType::DS { algorithm, digest_type, .. } => {
+                    dnssec::is_supported_algorithm(*algorithm) && dnssec::is_supported_digest(*digest_type)
+                }
+                DnsRecordType::DNSKEY { algorithm, .. } => dnssec::is_supported_algorithm(*algorithm),
+                _ => false,
+            })
+            .collect();
+        if trusted.is_empty() {
+            return Ok(Cut::Insecure);
+        }
+
+        let response = self.fetch(name, RecordType::DNSKEY).await?;
+        let dnskeys: Vec<DnsRecord> = response
+            .answers
+            .iter()
+            .filter(|record| same_name(record.record(), name) && record.rdata().get_type() == RecordType::DNSKEY)
+            .cloned()
+            .collect();
+        let keys: Vec<DnsRecordType> = dnskeys
+            .iter()
+            .map(|record| record.rdata().clone())
+            .filter(|key| {
+                matches!(key, DnsRecordType::DNSKEY { flags, protocol: 3, .. } if flags & ZONE_KEY_FLAG != 0 && flags & REVOKE_FLAG == 0)
+            })
+            .collect();
+        let entry_points: Vec<DnsRecordType> = keys
+            .iter()
+            .filter(|key| {
+                trusted.iter().any(|record| match record {
+                    DnsRecordType::DS { .. } => dnssec::ds_matches(name, record, key),
+                    _ => record == *key,
+                })
+            })
+            .cloned()
+            .collect();
+
+        let rrsigs = covering_rrsigs(&response.answers, name, RecordType::DNSKEY);
+        if dnskeys.is_empty() || check_signatures(&dnskeys, &rrsigs, name, &entry_points, self.now).is_none() {
+            return Err(ValidationError::NoTrustedKey { zone: name.to_string() });
+        }
+
+        Ok(Cut::Secure(keys))
+    }
+
+    /// Asks the inner resolver for `name`, with the DNSSEC records and
+    /// without any validation it may do itself.
+    async fn fetch(&mut self, name: &str, qtype: RecordType) -> Result<DnsPacket, ValidationError> {
+        if self.queries >= MAX_QUERIES {
+            return Err(ValidationError::TooManyQueries);
+        }
+        self.queries += 1;
+
+        let mut query = DnsPacket::new_query(rand::random(), name, qtype);
+        query.header.checking_disabled = true;
+        query.edns = Some(Edns { dnssec_ok: true, ..Edns::default() });
+
+        match self.inner.resolve(&query, self.ctx).await {
+            Ok(response) if matches!(response.header.rcode, ResponseCode::NoError | ResponseCode::NXDomain) => Ok(response),
+            Ok(_) => Err(ValidationError::FailedToFetch { name: name.to_string(), record_type: qtype, source: None }),
+            Err(e) => Err(ValidationError::FailedToFetch { name: name.to_string(), record_type: qtype, source: Some(Box::new(e)) }),
+        }
+    }
+}
+
+/// Looks for an RRSIG made by `zone` with one of `keys` that verifies
+/// `rrset`. Returns the closest encloser when the RRset was expanded from a
+/// wildcard, `Some(None)` when it wasn't, and `None` when nothing verifies.
+fn check_signatures(rrset: &[DnsRecord], rrsigs: &[DnsRecord], zone: &str, keys: &[DnsRecordType], now: u32) -> Option<Option<String>> {
+    let owner = normalize(rrset[0].record());
+    let owner_labels = label_count(&owner) - owner.starts_with('*') as usize;
+
+    rrsigs.iter().find_map(|rrsig| {
+        let DnsRecordType::RRSIG { type_covered, algorithm, labels, expiration, inception, key_tag, signer_name, .. } = rrsig.rdata()
+        else {
+            return None;
+        };
+        // Serial number arithmetic, the fields wrap around in 2106 (RFC 4034 3.1.5).
+        let is_current = now.wrapping_sub(*inception) as i32 >= 0 && expiration.wrapping_sub(now) as i32 >= 0;
+        let usable = *type_covered == rrset[0].rdata().get_type()
+            && same_name(signer_name, zone)
+            && (*labels as usize) <= owner_labels
+            && is_current
+            && dnssec::is_supported_algorithm(*algorithm);
+        if !usable {
+            return None;
+        }
+
+        let verified = keys
+            .iter()
+            .filter(|key| dnssec::key_tag(key) == Some(*key_tag))
+            .any(|key| dnssec::verify_rrsig(rrsig.rdata(), rrset, key));
+        verified.then(|| {
+            let closest_encloser: Vec<&str> = owner.split('.').skip(label_count(&owner) - *labels as usize).collect();
+            ((*labels as usize) < owner_labels).then(|| closest_encloser.join("."))
+        })
+    })
+}
+
+/// Splits records into RRsets, each paired with the RRSIGs covering it.
+fn rrsets(records: &[DnsRecord]) -> Vec<(Vec<DnsRecord>, Vec<DnsRecord>)> {
+    dnssec::rrsets(records)
+        .into_iter()
+        .map(|rrset| {
+            let rrsigs = covering_rrsigs(records, rrset[0].record(), rrset[0].rdata().get_type());
+            (rrset, rrsigs)
+        })
+        .collect()
+}
+
+fn covering_rrsigs(records: &[DnsRecord], owner: &str, record_type: RecordType) -> Vec<DnsRecord> {
+    records
+        .iter()
+        .filter(|record| {
+            same_name(record.record(), owner)
+                && matches!(record.rdata(), DnsRecordType::RRSIG { type_covered, .. } if *type_covered == record_type)
+        })
+        .cloned()
+        .collect()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn unix_time() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as u32)
+}
+
+#[async_trait]
+impl Resolver for ValidatingResolver {
+    async fn resolve(&self, request: &DnsPacket, ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+        let Some(question) = request.questions.first() else {
+            return self.inner.resolve(request, ctx).await;
+        };
+        if request.header.checking_disabled {
+            return self.inner.resolve(request, ctx).await;
+        }
+
+        let dnssec_ok = request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok);
+        let mut query = request.clone();
+        query.header.checking_disabled = true;
+        query.edns = Some(Edns { dnssec_ok: true, ..request.edns.clone().unwrap_or_default() });
+
+        let mut response = self.inner.resolve(&query, ctx).await?;
+        response.header.checking_disabled = false;
+        response.header.authentic_data = false;
+
+        if matches!(response.header.rcode, ResponseCode::NoError | ResponseCode::NXDomain) {
+            let mut validation = Validation {
+                inner: self.inner.as_ref(),
+                trust_anchors: &self.trust_anchors,
+                ctx,
+                now: unix_time(),
+                queries: 0,
+                cuts: HashMap::new(),
+            };
+
+            match validation.validate(&response, question).await {
+                // RFC 6840 5.7: only clients that asked for it get the AD bit.
+                Ok(secure) => response.header.authentic_data = secure && (dnssec_ok || request.header.authentic_data),
+                Err(e) => {
+                    debug!("Validation of {} {} failed: {}", question.name, question.qtype, ErrorChain(&e));
+                    let mut failure = DnsPacket::response_to(request).with_rcode(ResponseCode::ServerFailure);
+                    failure.header.recursion_available = response.header.recursion_available;
+                    return Ok(failure);
+                }
+            }
+        }
+
+        // RFC 4035 3.2.1: DNSSEC records are only for clients that set DO.
+        if !dnssec_ok {
+            let wanted = |record: &DnsRecord| !dnssec::is_dnssec_record(record) || record.rdata().get_type() == question.qtype;
+            response.answers.retain(wanted);
+            response.authority.retain(wanted);
+            response.additional.retain(wanted);
+            response.header.answer_count = response.answers.len() as u16;
+            response.header.nscount = response.authority.len() as u16;
+            response.header.arcount = response.additional.len() as u16;
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use super::*;
+    use crate::dnssec::denial::nsec3_hash;
+    use crate::dnssec::testing::TestKey;
+    use crate::dnssec::{ECDSAP256SHA256, ED25519, RSASHA256};
+    use crate::protocol::dns_record_type::Class;
+    use crate::protocol::encoding::to_base32hex;
+    use crate::resolver::Protocol;
+
+    /// Serves canned responses for a root zone signed with RSA, which
+    /// delegates to `example` (P-256, NSEC), `nsec3` (Ed25519, NSEC3) and
+    /// the unsigned `unsigned`.
+    struct SignedZones {
+        root: TestKey,
+        responses: HashMap<(String, RecordType), DnsPacket>,
+    }
+
+    fn record(owner: &str, rdata: DnsRecordType) -> DnsRecord {
+        DnsRecord::new(owner.to_string(), Class::IN, 300, rdata)
+    }
+
+    fn soa() -> DnsRecordType {
+        DnsRecordType::SOA {
+            mname: "ns".to_string(),
+            rname: "hostmaster".to_string(),
+            serial: 1,
+            refresh: 7200,
+            retry: 900,
+            expire: 604800,
+            minimum: 300,
+        }
+    }
+
+    fn nsec(next: &str, types: &[RecordType]) -> DnsRecordType {
+        DnsRecordType::NSEC { next_domain_name: next.to_string(), types: types.to_vec() }
+    }
+
+    /// `rrset` followed by its signature.
+    fn signed(key: &TestKey, zone: &str, rrset: Vec<DnsRecord>) -> Vec<DnsRecord> {
+        let rrsig = key.sign(zone, &rrset);
+        rrset.into_iter().chain([rrsig]).collect()
+    }
+
+    impl SignedZones {
+        fn new() -> Self {
+            let root = TestKey::new(RSASHA256, 257);
+            let example = TestKey::new(ECDSAP256SHA256, 257);
+            let nsec3 = TestKey::new(ED25519, 257);
+            let www = DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) };
+
+            let mut zones = SignedZones { root, responses: HashMap::new() };
+            let root = &zones.root;
+
+            let mut tampered = signed(&example, "example", vec![record("tampered.example", www.clone())]);
+            tampered[0] = record("tampered.example", DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 66) });
+
+            let salt = vec![0xAB];
+            let nsec3_owner = |name: &str| format!("{}.nsec3", to_base32hex(&nsec3_hash(name, &salt, 1)));
+            let host = nsec3_owner("host.nsec3");
+            let host_nsec3 = DnsRecordType::NSEC3 {
+                hash_algorithm: 1,
+                flags: 0,
+                iterations: 1,
+                salt: salt.clone(),
+                next_hashed_owner: nsec3_hash("nsec3", &salt, 1),
+                types: vec![RecordType::A, RecordType::RRSIG],
+            };
+
+            let responses = [
+                (("", RecordType::DNSKEY), ResponseCode::NoError, signed(root, "", vec![record("", root.dnskey.clone())]), vec![]),
+                (("example", RecordType::DS), ResponseCode::NoError, signed(root, "", vec![record("example", example.ds("example"))]), vec![]),
+                (("nsec3", RecordType::DS), ResponseCode::NoError, signed(root, "", vec![record("nsec3", nsec3.ds("nsec3"))]), vec![]),
+                (
+                    ("unsigned", RecordType::DS),
+                    ResponseCode::NoError,
+                    vec![],
+                    signed(root, "", vec![record("unsigned", nsec("", &[RecordType::NS, RecordType::RRSIG, RecordType::NSEC]))]),
+                ),
+                (
+                    ("example", RecordType::DNSKEY),
+                    ResponseCode::NoError,
+                    signed(&example, "example", vec![record("example", example.dnskey.clone())]),
+                    vec![],
+                ),
+                (
+                    ("nsec3", RecordType::DNSKEY),
+                    ResponseCode::NoError,
+                    signed(&nsec3, "nsec3", vec![record("nsec3", nsec3.dnskey.clone())]),
+                    vec![],
+                ),
+                (("www.example", RecordType::A), ResponseCode::NoError, signed(&example, "example", vec![record("www.example", www.clone())]), vec![]),
+                (("tampered.example", RecordType::A), ResponseCode::NoError, tampered, vec![]),
+                (
+                    ("missing.example", RecordType::A),
+                    ResponseCode::NXDomain,
+                    vec![],
+                    [
+                        signed(&example, "example", vec![record("example", soa())]),
+                        signed(
+                            &example,
+                            "example",
+                            vec![record(
+                                "example",
+                                nsec("www.example", &[RecordType::NS, RecordType::SOA, RecordType::RRSIG, RecordType::NSEC, RecordType::DNSKEY]),
+                            )],
+                        ),
+                    ]
+                    .concat(),
+                ),
+                (
+                    ("host.nsec3", RecordType::AAAA),
+                    ResponseCode::NoError,
+                    vec![],
+                    [
+                        signed(&nsec3, "nsec3", vec![record("nsec3", soa())]),
+                        signed(&nsec3, "nsec3", vec![record(&host, host_nsec3)]),
+                    ]
+                    .concat(),
+                ),
+                (("www.unsigned", RecordType::A), ResponseCode::NoError, vec![record("www.unsigned", www)], vec![]),
+            ];
+
+            for ((name, qtype), rcode, answers, authority) in responses {
+                let mut response = DnsPacket::new_query(0, name, qtype).with_rcode(rcode);
+                response.header.is_response = true;
+                response.answers = answers;
+                response.authority = authority;
+                zones.responses.insert((name.to_string(), qtype), response);
+            }
+
+            zones
+        }
+
+        /// Removes the records of `record_type` from the canned response to `name`.
+        fn strip(&mut self, name: &str, qtype: RecordType, record_type: RecordType) {
+            let response = self.responses.get_mut(&(name.to_string(), qtype)).unwrap();
+            response.authority.retain(|record| {
+                record.rdata().get_type() != record_type
+                    && !matches!(record.rdata(), DnsRecordType::RRSIG { type_covered, .. } if *type_covered == record_type)
+            });
+        }
+    }
+
+    #[async_trait]
+    impl Resolver for SignedZones {
+        async fn resolve(&self, request: &DnsPacket, _ctx: &RequestContext) -> Result<DnsPacket, QueryError> {
+            let question = &request.questions[0];
+            let canned = self
+                .responses
+                .get(&(question.name.clone(), question.qtype))
+                .ok_or(QueryError::FailetToResolveQuery)?;
+
+            let mut response = DnsPacket::response_to(request).with_rcode(canned.header.rcode);
+            response.answers = canned.answers.clone();
+            response.authority = canned.authority.clone();
+            Ok(response)
+        }
+    }
+
+    fn ctx() -> RequestContext {
+        RequestContext::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5353), Protocol::Udp)
+    }
+
+    fn validating(zones: SignedZones) -> ValidatingResolver {
+        let anchor = record("", zones.root.ds(""));
+        ValidatingResolver::new(Arc::new(zones)).with_trust_anchors(vec![anchor])
+    }
+
+    async fn resolve(resolver: &ValidatingResolver, name: &str, qtype: RecordType, dnssec_ok: bool) -> DnsPacket {
+        let query = DnsPacket::new_query(7, name, qtype).with_edns(Edns { dnssec_ok, ..Edns::default() });
+        resolver.resolve(&query, &ctx()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn sets_ad_on_secure_answers() {
+        let resolver = validating(SignedZones::new());
+
+        let response = resolve(&resolver, "www.example", RecordType::A, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::NoError);
+        assert!(response.header.authentic_data);
+        assert_eq!(response.answers.len(), 2);
+
+        let mut query = DnsPacket::new_query(8, "www.example", RecordType::A);
+        query.header.authentic_data = true;
+        let response = resolver.resolve(&query, &ctx()).await.unwrap();
+        assert!(response.header.authentic_data);
+        assert_eq!(response.answers.len(), 1, "RRSIGs are only sent to clients that set DO");
+    }
+
+    #[tokio::test]
+    async fn fails_bogus_answers() {
+        let resolver = validating(SignedZones::new());
+
+        let response = resolve(&resolver, "tampered.example", RecordType::A, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::ServerFailure);
+        assert!(response.answers.is_empty());
+
+        // Unless the client disabled checking.
+        let mut query = DnsPacket::new_query(7, "tampered.example", RecordType::A);
+        query.header.checking_disabled = true;
+        let response = resolver.resolve(&query, &ctx()).await.unwrap();
+        assert_eq!(response.header.rcode, ResponseCode::NoError);
+        assert!(response.header.checking_disabled);
+    }
+
+    #[tokio::test]
+    async fn requires_proof_of_nonexistence() {
+        let response = resolve(&validating(SignedZones::new()), "missing.example", RecordType::A, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::NXDomain);
+        assert!(response.header.authentic_data);
+
+        let mut zones = SignedZones::new();
+        zones.strip("missing.example", RecordType::A, RecordType::NSEC);
+        let response = resolve(&validating(zones), "missing.example", RecordType::A, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::ServerFailure);
+    }
+
+    #[tokio::test]
+    async fn accepts_nsec3_nodata_with_a_dnskey_anchor() {
+        let zones = SignedZones::new();
+        let anchor = record("", zones.root.dnskey.clone());
+        let resolver = ValidatingResolver::new(Arc::new(zones)).with_trust_anchors(vec![anchor]);
+
+        let response = resolve(&resolver, "host.nsec3", RecordType::AAAA, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::NoError);
+        assert!(response.header.authentic_data);
+    }
+
+    #[tokio::test]
+    async fn passes_insecure_answers_without_ad() {
+        let response = resolve(&validating(SignedZones::new()), "www.unsigned", RecordType::A, true).await;
+
+        assert_eq!(response.header.rcode, ResponseCode::NoError);
+        assert!(!response.header.authentic_data);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_matching_trust_anchor() {
+        let zones = SignedZones::new();
+        let wrong = TestKey::new(ECDSAP256SHA256, 257);
+        let resolver = ValidatingResolver::new(Arc::new(zones)).with_trust_anchors(vec![record("", wrong.ds(""))]);
+
+        let response = resolve(&resolver, "www.example", RecordType::A, true).await;
+        assert_eq!(response.header.rcode, ResponseCode::ServerFailure);
+    }
+}
@@ -0,0 +1,208 @@
+//! In-memory authoritative zones.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::errors::ZoneError;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{DnsRecordType, RecordType};
+use crate::protocol::domain_name::{is_subdomain_of, normalize, parent};
+
+mod parser;
+
+/// The records of a single zone, indexed by owner name.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    origin: String,
+    soa: DnsRecord,
+    records: HashMap<String, Vec<DnsRecord>>,
+    /// Every name that exists in the zone, empty non-terminals included.
+    names: HashSet<String>,
+}
+
+/// What a zone knows about a name and type (RFC 1034 4.3.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZoneLookup {
+    Answer(Vec<DnsRecord>),
+    /// The name is an alias; the caller decides whether to follow it.
+    Cname(DnsRecord),
+    /// The name is at or below a zone cut.
+    Delegation {
+        name_servers: Vec<DnsRecord>,
+        glue: Vec<DnsRecord>,
+    },
+    NoData,
+    NxDomain,
+}
+
+impl Zone {
+    /// Builds a zone from its records, which must include an SOA at `origin`
+    /// and may not contain names outside of it.
+    pub fn new(origin: &str, records: Vec<DnsRecord>) -> Result<Zone, ZoneError> {
+        let origin = normalize(origin);
+        let soa = records
+            .iter()
+            .find(|record| matches!(record.rdata(), DnsRecordType::SOA { .. }))
+            .cloned()
+            .ok_or(ZoneError::MissingSoa)?;
+
+        let mut zone = Zone {
+            origin,
+            soa,
+            records: HashMap::new(),
+            names: HashSet::new(),
+        };
+        for record in records {
+            zone.insert(record)?;
+        }
+
+        Ok(zone)
+    }
+
+    /// Adds `record`, which must be inside the zone, or at its origin for an SOA.
+    pub fn insert(&mut self, record: DnsRecord) -> Result<(), ZoneError> {
+        let name = normalize(record.record());
+        if !is_subdomain_of(&name, &self.origin) {
+            return Err(ZoneError::OutOfZone { name });
+        }
+
+        if let DnsRecordType::SOA { .. } = record.rdata() {
+            if name != self.origin {
+                return Err(ZoneError::OutOfZone { name });
+            }
+            self.soa = record.clone();
+        }
+
+        let mut ancestor = Some(name.as_str());
+        while let Some(current) = ancestor {
+            if !self.names.insert(current.to_string()) || current == self.origin {
+                break;
+            }
+            ancestor = parent(current);
+        }
+
+        self.records.entry(name).or_default().push(record);
+        Ok(())
+    }
+
+    /// Parses a zone from the text of an RFC 1035 master file.
+    pub fn parse(text: &str, origin: &str) -> Result<Zone, ZoneError> {
+        Zone::new(origin, parser::parse(text, origin)?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, origin: &str) -> Result<Zone, ZoneError> {
+        let text = fs::read_to_string(path).map_err(ZoneError::FailedToReadFile)?;
+        Zone::parse(&text, origin)
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn soa(&self) -> &DnsRecord {
+        &self.soa
+    }
+
+    /// The SOA to put in the authority section of negative answers, with the
+    /// TTL lowered to the SOA minimum as RFC 2308 5 requires.
+    pub fn negative_soa(&self) -> DnsRecord {
+        let minimum = match self.soa.rdata() {
+            DnsRecordType::SOA { minimum, .. } => *minimum,
+            _ => unreachable!("zone SOA is always an SOA record"),
+        };
+
+        self.soa.clone().with_ttl(self.soa.ttl().min(minimum))
+    }
+
+    /// All records owned by `name`.
+    pub fn records(&self, name: &str) -> &[DnsRecord] {
+        self.records.get(&normalize(name)).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn records_of_type(&self, name: &str, record_type: RecordType) -> Vec<DnsRecord> {
+        self.records(name)
+            .iter()
+            .filter(|record| record.rdata().get_type() == record_type)
+            .cloned()
+            .collect()
+    }
+
+    /// Every name owning records in the zone, in no particular order.
+    pub fn owner_names(&self) -> impl Iterator<Item = &str> {
+        self.records.keys().map(String::as_str)
+    }
+
+    /// Whether `name` exists in the zone, even if it owns no records itself.
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.names.contains(&normalize(name))
+    }
+
+    pub fn lookup(&self, name: &str, qtype: RecordType) -> ZoneLookup {
+        let name = normalize(name);
+
+        // The parent side of a zone cut holds the DS RRset (RFC 4035 3.1.4.1).
+        if let Some(cut) = self.zone_cut(&name).filter(|cut| qtype != RecordType::DS || *cut != name) {
+            let name_servers = self.records_of_type(cut, RecordType::NS);
+            let glue = self.glue(&name_servers);
+            return ZoneLookup::Delegation { name_servers, glue };
+        }
+
+        if !self.names.contains(&name) {
+            return ZoneLookup::NxDomain;
+        }
+
+        let records = self.records(&name);
+        let matching: Vec<DnsRecord> = records
+            .iter()
+            .filter(|record| qtype == RecordType::ANY || record.rdata().get_type() == qtype)
+            .cloned()
+            .collect();
+        if !matching.is_empty() {
+            return ZoneLookup::Answer(matching);
+        }
+
+        match records.iter().find(|record| record.rdata().get_type() == RecordType::CNAME) {
+            Some(cname) => ZoneLookup::Cname(cname.clone()),
+            None => ZoneLookup::NoData,
+        }
+    }
+
+    /// The highest name between the apex (excluded) and `name` (included)
+    /// that owns NS records.
+    pub fn zone_cut<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let mut cut = None;
+        let mut current = Some(name);
+
+        while let Some(candidate) = current {
+            if candidate == self.origin || !is_subdomain_of(candidate, &self.origin) {
+                break;
+            }
+            if self.records(candidate).iter().any(|record| record.rdata().get_type() == RecordType::NS) {
+                cut = Some(candidate);
+            }
+            current = parent(candidate);
+        }
+
+        cut
+    }
+
+    /// Address records for the name servers that live inside this zone.
+    fn glue(&self, name_servers: &[DnsRecord]) -> Vec<DnsRecord> {
+        name_servers
+            .iter()
+            .filter_map(|record| match record.rdata() {
+                DnsRecordType::NS { name_server } if is_subdomain_of(name_server, &self.origin) => {
+                    Some(name_server)
+                }
+                _ => None,
+            })
+            .flat_map(|name_server| {
+                self.records(name_server)
+                    .iter()
+                    .filter(|record| matches!(record.rdata().get_type(), RecordType::A | RecordType::AAAA))
+                    .cloned()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,704 @@
+//! Reader for RFC 1035 master files (section 5).
+//!
+//! Supports `$ORIGIN` and `$TTL`, relative names, `@`, omitted owners,
+//! parentheses spanning several lines, quoted strings and comments, as well
+//! as the `TYPEnnn` and `\# len hex` forms for any record type (RFC 3597).
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::errors::ZoneError;
+use crate::protocol::dns_record::DnsRecord;
+use crate::protocol::dns_record_type::{split_character_strings, Class, DnsRecordType, RecordType};
+use crate::protocol::encoding::{from_base32hex, from_base64, from_hex, from_timestamp};
+use crate::protocol::packet_buffer::PacketBuffer;
+use crate::protocol::svcb::SvcParam;
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    /// The exact bytes of the token, where `\DDD` escapes may produce any byte.
+    bytes: Vec<u8>,
+    quoted: bool,
+}
+
+/// What a backslash escape stands for.
+enum Escape {
+    Char(char),
+    Byte(u8),
+}
+
+/// One logical line of the file, after joining parenthesized continuations.
+#[derive(Debug)]
+struct Entry {
+    line: usize,
+    starts_with_blank: bool,
+    tokens: Vec<Token>,
+}
+
+pub(crate) fn parse(text: &str, origin: &str) -> Result<Vec<DnsRecord>, ZoneError> {
+    let mut origin = origin.trim_end_matches('.').to_string();
+    let mut default_ttl = None;
+    let mut last_ttl = None;
+    let mut last_owner: Option<String> = None;
+    let mut records = Vec::new();
+
+    for entry in tokenize(text)? {
+        let line = entry.line;
+        let syntax = |message: &str| ZoneError::Syntax { line, message: message.to_string() };
+        let mut tokens = entry.tokens.iter().peekable();
+
+        let Some(first) = tokens.peek() else {
+            continue;
+        };
+
+        if !entry.starts_with_blank && !first.quoted && first.text.starts_with('$') {
+            let directive = tokens.next().unwrap().text.to_ascii_uppercase();
+            let argument = tokens.next().ok_or_else(|| syntax("Missing directive argument"))?;
+            match directive.as_str() {
+                "$ORIGIN" => origin = absolute_name(&argument.text, &origin),
+                "$TTL" => default_ttl = Some(parse_ttl(&argument.text).ok_or_else(|| syntax("Invalid $TTL"))?),
+                _ => return Err(syntax(&format!("Unsupported directive {}", directive))),
+            }
+            continue;
+        }
+
+        let owner = if entry.starts_with_blank {
+            last_owner.clone().ok_or_else(|| syntax("Record without owner name"))?
+        } else {
+            absolute_name(&tokens.next().unwrap().text, &origin)
+        };
+        last_owner = Some(owner.clone());
+
+        let mut ttl = None;
+        let mut class = Class::IN;
+        let record_type = loop {
+            let token = tokens.next().ok_or_else(|| syntax("Missing record type"))?;
+            if let Some(value) = parse_ttl(&token.text) {
+                ttl = Some(value);
+            } else if let Some(value) = parse_class(&token.text) {
+                class = value;
+            } else {
+                break token.text.parse::<RecordType>().map_err(|message| syntax(&message))?;
+            }
+        };
+
+        let rdata: Vec<&Token> = tokens.collect();
+        let rdata = match rdata.first() {
+            Some(token) if token.text == "\\#" && !token.quoted => parse_generic_rdata(record_type, &rdata[1..]),
+            _ => parse_rdata(record_type, &rdata, &origin),
+        }
+        .map_err(|message| syntax(&message))?;
+
+        // Without $TTL, RFC 1035 reuses the last explicitly stated TTL.
+        if ttl.is_some() {
+            last_ttl = ttl;
+        }
+        let ttl = match ttl.or(default_ttl).or(last_ttl) {
+            Some(ttl) => ttl,
+            None => match &rdata {
+                DnsRecordType::SOA { minimum, .. } => *minimum,
+                _ => return Err(syntax("No TTL given and no $TTL in effect")),
+            },
+        };
+
+        records.push(DnsRecord::new(owner, class, ttl, rdata));
+    }
+
+    Ok(records)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Entry>, ZoneError> {
+    let mut entries = Vec::new();
+    let mut current = Entry { line: 1, starts_with_blank: false, tokens: Vec::new() };
+    let mut depth = 0;
+    let mut line = 1;
+    let mut at_line_start = true;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if at_line_start && depth == 0 {
+            current = Entry { line, starts_with_blank: c == ' ' || c == '\t', tokens: Vec::new() };
+        }
+        at_line_start = false;
+
+        match c {
+            '\n' => {
+                line += 1;
+                at_line_start = true;
+                if depth == 0 {
+                    entries.push(std::mem::replace(
+                        &mut current,
+                        Entry { line, starts_with_blank: false, tokens: Vec::new() },
+                    ));
+                }
+            }
+            ';' => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            '(' => depth += 1,
+            ')' => {
+                if depth == 0 {
+                    return Err(ZoneError::Syntax { line, message: "Unbalanced ')'".to_string() });
+                }
+                depth -= 1;
+            }
+            '"' => {
+                let mut text = String::new();
+                let mut bytes = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match unescape(&mut chars) {
+                            Some(Escape::Char(c)) => push_char(&mut text, &mut bytes, c),
+                            Some(Escape::Byte(byte)) => {
+                                text.push(char::from(byte));
+                                bytes.push(byte);
+                            }
+                            None => {
+                                return Err(ZoneError::Syntax { line, message: "Invalid escape sequence".to_string() })
+                            }
+                        },
+                        Some('\n') | None => {
+                            return Err(ZoneError::Syntax { line, message: "Unterminated string".to_string() })
+                        }
+                        Some(c) => push_char(&mut text, &mut bytes, c),
+                    }
+                }
+                current.tokens.push(Token { text, bytes, quoted: true });
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                // The text keeps escapes as written, names are decoded when
+                // they are written to the wire.
+                let mut text = String::new();
+                let mut bytes = Vec::new();
+                let mut next = Some(c);
+                while let Some(c) = next {
+                    match c {
+                        '\\' => match unescape(&mut chars) {
+                            Some(Escape::Char(c)) => {
+                                text.push('\\');
+                                push_char(&mut text, &mut bytes, c);
+                            }
+                            Some(Escape::Byte(byte)) => {
+                                text.push_str(&format!("\\{:03}", byte));
+                                bytes.push(byte);
+                            }
+                            None => {
+                                return Err(ZoneError::Syntax { line, message: "Invalid escape sequence".to_string() })
+                            }
+                        },
+                        c => push_char(&mut text, &mut bytes, c),
+                    }
+                    next = chars.next_if(|c| !c.is_whitespace() && !"();\"".contains(*c));
+                }
+                current.tokens.push(Token { text, bytes, quoted: false });
+            }
+        }
+    }
+
+    if depth != 0 {
+        return Err(ZoneError::Syntax { line, message: "Unbalanced '('".to_string() });
+    }
+    entries.push(current);
+
+    Ok(entries)
+}
+
+fn push_char(text: &mut String, bytes: &mut Vec<u8>, c: char) {
+    text.push(c);
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Decodes what follows a backslash: either `\DDD` or a literal character.
+fn unescape(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Escape> {
+    let first = chars.next()?;
+    if !first.is_ascii_digit() {
+        return Some(Escape::Char(first));
+    }
+
+    let digits = [first, chars.next()?, chars.next()?];
+    let value: String = digits.iter().collect();
+    value.parse::<u8>().ok().map(Escape::Byte)
+}
+
+fn absolute_name(name: &str, origin: &str) -> String {
+    if name == "@" {
+        return origin.to_string();
+    }
+    if let Some(name) = name.strip_suffix('.') {
+        return name.to_string();
+    }
+    if origin.is_empty() {
+        return name.to_string();
+    }
+
+    format!("{}.{}", name, origin)
+}
+
+/// Parses a TTL in seconds, also accepting BIND style units like `1h30m`.
+fn parse_ttl(text: &str) -> Option<u32> {
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Ok(seconds) = text.parse() {
+        return Some(seconds);
+    }
+
+    let mut total: u32 = 0;
+    let mut value: u32 = 0;
+    let mut pending = false;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = value.checked_mul(10)?.checked_add(digit)?;
+            pending = true;
+            continue;
+        }
+
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return None,
+        };
+        if !pending {
+            return None;
+        }
+        total = total.checked_add(value.checked_mul(unit)?)?;
+        value = 0;
+        pending = false;
+    }
+
+    total.checked_add(value)
+}
+
+/// A class for zone data, which can't be NONE or ANY (RFC 3597 5).
+fn parse_class(text: &str) -> Option<Class> {
+    text.parse().ok().filter(|class| !matches!(class, Class::NONE | Class::ANY))
+}
+
+/// Decodes RDATA given as `\# length hex...`, interpreting it if the type is
+/// one we know.
+fn parse_generic_rdata(record_type: RecordType, tokens: &[&Token]) -> Result<DnsRecordType, String> {
+    let length = tokens
+        .first()
+        .and_then(|token| token.text.parse::<u16>().ok())
+        .ok_or("Missing RDATA length after \\#")?;
+
+    let rdata = hex(&tokens[1..])?;
+    if rdata.len() != length as usize {
+        return Err(format!("RDATA is {} bytes long, expected {}", rdata.len(), length));
+    }
+
+    let mut buffer = PacketBuffer::from_bytes(&rdata).map_err(|e| e.to_string())?;
+    let decoded = DnsRecordType::deserialize(&mut buffer, record_type, length).map_err(|e| e.to_string())?;
+    if buffer.pos != rdata.len() {
+        return Err(format!("Invalid RDATA for {}", record_type));
+    }
+
+    Ok(decoded)
+}
+
+/// Joins `tokens` and decodes them as hex, which zone files may split with spaces.
+fn hex(tokens: &[&Token]) -> Result<Vec<u8>, String> {
+    let text: String = tokens.iter().map(|token| token.text.as_str()).collect();
+    from_hex(&text).ok_or_else(|| format!("Invalid hex data {}", text))
+}
+
+/// Joins `tokens` and decodes them as base64, which zone files may split with spaces.
+fn base64(tokens: &[&Token]) -> Result<Vec<u8>, String> {
+    let text: String = tokens.iter().map(|token| token.text.as_str()).collect();
+    from_base64(&text).ok_or_else(|| format!("Invalid base64 data {}", text))
+}
+
+/// Parses the type mnemonics of an NSEC or NSEC3 type bitmap.
+fn record_types(tokens: &[&Token]) -> Result<Vec<RecordType>, String> {
+    tokens.iter().map(|token| token.text.parse::<RecordType>()).collect()
+}
+
+/// Parses an NSEC3 salt, written in hex or as `-` when empty.
+fn salt(token: &Token) -> Result<Vec<u8>, String> {
+    match token.text.as_str() {
+        "-" => Ok(Vec::new()),
+        text => from_hex(text).ok_or_else(|| format!("Invalid salt {}", text)),
+    }
+}
+
+/// Parses `key=value` SvcParams, where the value may be a separate quoted token.
+fn parse_svc_params(tokens: &[&Token]) -> Result<Vec<SvcParam>, String> {
+    let mut params = Vec::new();
+    let mut tokens = tokens.iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        let mut text = token.text.clone();
+        if text.ends_with('=') {
+            if let Some(value) = tokens.next_if(|value| value.quoted) {
+                text.push_str(&value.text);
+            }
+        }
+        params.push(text.parse::<SvcParam>()?);
+    }
+
+    Ok(params)
+}
+
+fn parse_rdata(record_type: RecordType, tokens: &[&Token], origin: &str) -> Result<DnsRecordType, String> {
+    let expect = |count: usize| {
+        if tokens.len() == count {
+            Ok(())
+        } else {
+            Err(format!("{} record expects {} fields, found {}", record_type, count, tokens.len()))
+        }
+    };
+    let name = |index: usize| absolute_name(&tokens[index].text, origin);
+    let number = |index: usize| {
+        tokens[index]
+            .text
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid number {}", tokens[index].text))
+    };
+    let byte = |index: usize| {
+        tokens[index]
+            .text
+            .parse::<u8>()
+            .map_err(|_| format!("Invalid number {}", tokens[index].text))
+    };
+    let at_least = |count: usize| {
+        if tokens.len() >= count {
+            Ok(())
+        } else {
+            Err(format!("{} record expects at least {} fields, found {}", record_type, count, tokens.len()))
+        }
+    };
+    let string = |index: usize| match tokens[index].bytes.len() {
+        0..=255 => Ok(tokens[index].bytes.clone()),
+        length => Err(format!("String of {} bytes is longer than 255", length)),
+    };
+    let long = |index: usize| {
+        tokens[index]
+            .text
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid number {}", tokens[index].text))
+    };
+    let timestamp =
+        |index: usize| from_timestamp(&tokens[index].text).ok_or_else(|| format!("Invalid timestamp {}", tokens[index].text));
+    let time = |index: usize| parse_ttl(&tokens[index].text).ok_or_else(|| format!("Invalid time {}", tokens[index].text));
+
+    let rdata = match record_type {
+        RecordType::A => {
+            expect(1)?;
+            let address = tokens[0].text.parse::<Ipv4Addr>().map_err(|e| e.to_string())?;
+            DnsRecordType::A { address }
+        }
+        RecordType::AAAA => {
+            expect(1)?;
+            let address = tokens[0].text.parse::<Ipv6Addr>().map_err(|e| e.to_string())?;
+            DnsRecordType::AAAA { address }
+        }
+        RecordType::NS => {
+            expect(1)?;
+            DnsRecordType::NS { name_server: name(0) }
+        }
+        RecordType::CNAME => {
+            expect(1)?;
+            DnsRecordType::CNAME { canonical_name: name(0) }
+        }
+        RecordType::PTR => {
+            expect(1)?;
+            DnsRecordType::PTR { domain_name: name(0) }
+        }
+        RecordType::MX => {
+            expect(2)?;
+            DnsRecordType::MX { priority: number(0)?, exchange: name(1) }
+        }
+        RecordType::SOA => {
+            expect(7)?;
+            DnsRecordType::SOA {
+                mname: name(0),
+                rname: name(1),
+                serial: tokens[2].text.parse().map_err(|_| format!("Invalid serial {}", tokens[2].text))?,
+                refresh: time(3)?,
+                retry: time(4)?,
+                expire: time(5)?,
+                minimum: time(6)?,
+            }
+        }
+        RecordType::TXT => {
+            if tokens.is_empty() {
+                return Err("TXT record without text".to_string());
+            }
+            DnsRecordType::TXT {
+                strings: tokens.iter().flat_map(|token| split_character_strings(&token.bytes)).collect(),
+            }
+        }
+        RecordType::HINFO => {
+            expect(2)?;
+            DnsRecordType::HINFO { cpu: string(0)?, os: string(1)? }
+        }
+        RecordType::SRV => {
+            expect(4)?;
+            DnsRecordType::SRV { priority: number(0)?, weight: number(1)?, port: number(2)?, target: name(3) }
+        }
+        RecordType::NAPTR => {
+            expect(6)?;
+            DnsRecordType::NAPTR {
+                order: number(0)?,
+                preference: number(1)?,
+                flags: string(2)?,
+                services: string(3)?,
+                regexp: string(4)?,
+                replacement: name(5),
+            }
+        }
+        RecordType::SSHFP => {
+            at_least(3)?;
+            DnsRecordType::SSHFP { algorithm: byte(0)?, fingerprint_type: byte(1)?, fingerprint: hex(&tokens[2..])? }
+        }
+        RecordType::DS | RecordType::CDS => {
+            at_least(4)?;
+            let (key_tag, algorithm, digest_type, digest) = (number(0)?, byte(1)?, byte(2)?, hex(&tokens[3..])?);
+            match record_type {
+                RecordType::DS => DnsRecordType::DS { key_tag, algorithm, digest_type, digest },
+                _ => DnsRecordType::CDS { key_tag, algorithm, digest_type, digest },
+            }
+        }
+        RecordType::RRSIG => {
+            at_least(9)?;
+            DnsRecordType::RRSIG {
+                type_covered: tokens[0].text.parse()?,
+                algorithm: byte(1)?,
+                labels: byte(2)?,
+                original_ttl: long(3)?,
+                expiration: timestamp(4)?,
+                inception: timestamp(5)?,
+                key_tag: number(6)?,
+                signer_name: name(7),
+                signature: base64(&tokens[8..])?,
+            }
+        }
+        RecordType::NSEC => {
+            at_least(1)?;
+            DnsRecordType::NSEC { next_domain_name: name(0), types: record_types(&tokens[1..])? }
+        }
+        RecordType::DNSKEY | RecordType::CDNSKEY => {
+            at_least(4)?;
+            let (flags, protocol, algorithm, public_key) = (number(0)?, byte(1)?, byte(2)?, base64(&tokens[3..])?);
+            match record_type {
+                RecordType::DNSKEY => DnsRecordType::DNSKEY { flags, protocol, algorithm, public_key },
+                _ => DnsRecordType::CDNSKEY { flags, protocol, algorithm, public_key },
+            }
+        }
+        RecordType::NSEC3 => {
+            at_least(5)?;
+            let next_hashed_owner = from_base32hex(&tokens[4].text)
+                .ok_or_else(|| format!("Invalid hashed owner name {}", tokens[4].text))?;
+            DnsRecordType::NSEC3 {
+                hash_algorithm: byte(0)?,
+                flags: byte(1)?,
+                iterations: number(2)?,
+                salt: salt(tokens[3])?,
+                next_hashed_owner,
+                types: record_types(&tokens[5..])?,
+            }
+        }
+        RecordType::NSEC3PARAM => {
+            expect(4)?;
+            DnsRecordType::NSEC3PARAM { hash_algorithm: byte(0)?, flags: byte(1)?, iterations: number(2)?, salt: salt(tokens[3])? }
+        }
+        RecordType::TLSA => {
+            at_least(4)?;
+            DnsRecordType::TLSA {
+                certificate_usage: byte(0)?,
+                selector: byte(1)?,
+                matching_type: byte(2)?,
+                certificate_data: hex(&tokens[3..])?,
+            }
+        }
+        RecordType::SVCB | RecordType::HTTPS => {
+            at_least(2)?;
+            let priority = number(0)?;
+            let target = name(1);
+            let params = parse_svc_params(&tokens[2..])?;
+            match record_type {
+                RecordType::SVCB => DnsRecordType::SVCB { priority, target, params },
+                _ => DnsRecordType::HTTPS { priority, target, params },
+            }
+        }
+        RecordType::CAA => {
+            expect(3)?;
+            // The tag is a <character-string>, the value just fills the rest of the RDATA.
+            string(1)?;
+            DnsRecordType::CAA { flags: byte(0)?, tag: tokens[1].text.clone(), value: tokens[2].bytes.clone() }
+        }
+        _ => return Err(format!("{} records need RDATA in the \\# form", record_type)),
+    };
+
+    Ok(rdata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = r#"
+$ORIGIN example.com.
+$TTL 1h
+@       IN  SOA ns1 hostmaster (
+                2024010101 ; serial
+                2h         ; refresh
+                15m        ; retry
+                1w         ; expire
+                300 )      ; minimum
+        IN  NS  ns1
+        IN  NS  ns.other.net.
+        IN  MX  10 mail
+ns1     IN  A   192.0.2.1
+mail 600 IN A   192.0.2.2
+        IN  AAAA 2001:db8::2
+www     CNAME   @
+txt     TXT     "hello; \"world\"" more
+empty   TXT     ""
+gap     TXT     "a" "" "b"
+$ORIGIN sub.example.com.
+host    A       192.0.2.3
+"#;
+
+    #[test]
+    fn parses_master_file() {
+        let records = parse(ZONE, "example.com").unwrap();
+
+        assert_eq!(records.len(), 12);
+        assert_eq!(
+            records[0].rdata(),
+            &DnsRecordType::SOA {
+                mname: "ns1.example.com".to_string(),
+                rname: "hostmaster.example.com".to_string(),
+                serial: 2024010101,
+                refresh: 7200,
+                retry: 900,
+                expire: 604800,
+                minimum: 300,
+            }
+        );
+        assert_eq!(records[1].record(), "example.com");
+        assert_eq!(records[2].rdata(), &DnsRecordType::NS { name_server: "ns.other.net".to_string() });
+        assert_eq!(records[5].ttl(), 600);
+        assert_eq!(records[6].record(), "mail.example.com");
+        assert_eq!(records[6].ttl(), 3600);
+        assert_eq!(records[7].rdata(), &DnsRecordType::CNAME { canonical_name: "example.com".to_string() });
+        assert_eq!(
+            records[8].rdata(),
+            &DnsRecordType::TXT { strings: vec![b"hello; \"world\"".to_vec(), b"more".to_vec()] }
+        );
+        assert_eq!(records[9].rdata(), &DnsRecordType::TXT { strings: vec![Vec::new()] });
+        assert_eq!(
+            records[10].rdata(),
+            &DnsRecordType::TXT { strings: vec![b"a".to_vec(), Vec::new(), b"b".to_vec()] }
+        );
+        assert_eq!(records[11].record(), "host.sub.example.com");
+    }
+
+    #[test]
+    fn decodes_escapes_outside_quotes() {
+        let zone = r#"
+$TTL 60
+@           SOA     ns1 hostmaster 1 2 3 4 5
+dkim        TXT     v=DKIM1\;\032k=rsa ; comment
+a\.b        TXT     foo\032bar "foo\032bar" \"x\"
+"#;
+        let records = parse(zone, "example.com").unwrap();
+
+        assert_eq!(records[1].rdata(), &DnsRecordType::TXT { strings: vec![b"v=DKIM1; k=rsa".to_vec()] });
+        assert_eq!(records[2].record(), r"a\.b.example.com");
+        assert_eq!(
+            records[2].rdata(),
+            &DnsRecordType::TXT { strings: vec![b"foo bar".to_vec(), b"foo bar".to_vec(), b"\"x\"".to_vec()] }
+        );
+    }
+
+    #[test]
+    fn reports_line_of_syntax_errors() {
+        let error = parse("$TTL 60\n@ IN SOA ns1 hostmaster 1 2 3 4 5\nbad IN A not-an-ip\n", "example.com")
+            .unwrap_err();
+
+        assert!(matches!(error, ZoneError::Syntax { line: 3, .. }));
+    }
+
+    #[test]
+    fn parses_generic_rdata() {
+        let zone = "$TTL 60\n@ SOA ns1 hostmaster 1 2 3 4 5\nx TYPE65534 \\# 3 ABCD ef\ny A \\# 4 C0000201\n";
+        let records = parse(zone, "example.com").unwrap();
+
+        assert_eq!(records[1].rdata(), &DnsRecordType::Unknown { type_code: 65534, rdata: vec![0xAB, 0xCD, 0xEF] });
+        assert_eq!(records[2].rdata(), &DnsRecordType::A { address: Ipv4Addr::new(192, 0, 2, 1) });
+        assert!(parse("$TTL 60\nx TYPE65534 \\# 2 ABCDEF\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn parses_service_and_security_records() {
+        let zone = r#"
+$TTL 60
+@       SOA     ns1 hostmaster 1 2 3 4 5
+_sip._tcp SRV   10 60 5060 sip
+@       NAPTR   100 10 "S" "SIP+D2T" "" _sip._tcp
+@       CAA     0 issue "ca.example.net"
+@       HINFO   "x86_64" "Linux"
+host    SSHFP   4 2 ( 0123456789ABCDEF
+                      0123456789abcdef )
+_443._tcp TLSA  3 1 1 ABCDEF
+@       HTTPS   1 . alpn="h2,h3" port=443 ipv4hint=192.0.2.1
+"#;
+        let records = parse(zone, "example.com").unwrap();
+
+        assert_eq!(
+            records[1].rdata(),
+            &DnsRecordType::SRV { priority: 10, weight: 60, port: 5060, target: "sip.example.com".to_string() }
+        );
+        assert_eq!(records[2].rdata().to_string(), r#"100 10 "S" "SIP+D2T" "" _sip._tcp.example.com."#);
+        assert_eq!(records[3].rdata().to_string(), r#"0 issue "ca.example.net""#);
+        assert_eq!(records[4].rdata().to_string(), r#""x86_64" "Linux""#);
+        assert_eq!(records[5].rdata().to_string(), "4 2 0123456789ABCDEF0123456789ABCDEF");
+        assert_eq!(records[6].rdata().to_string(), "3 1 1 ABCDEF");
+        assert_eq!(records[7].rdata().to_string(), "1 . alpn=h2,h3 port=443 ipv4hint=192.0.2.1");
+
+        let long = format!("$TTL 60\n@ SOA ns1 hostmaster 1 2 3 4 5\n@ HINFO \"{}\" \"Linux\"\n", "x".repeat(256));
+        assert!(matches!(parse(&long, "example.com").unwrap_err(), ZoneError::Syntax { line: 3, .. }));
+    }
+
+    #[test]
+    fn parses_dnssec_records() {
+        let lines = [
+            "257 3 8 AwEAAagAIKlVZrpC6Ia7gEzahOR+9W29",
+            "20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+            "A 13 2 3600 20240229235959 20240201000000 12345 example.com. AAECAw==",
+            "www.example.com. A NS SOA RRSIG NSEC DNSKEY TYPE1234",
+            "1 0 10 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR A RRSIG",
+            "1 0 0 -",
+            "0 0 0 00",
+            "257 3 15 YWJj",
+        ];
+        let types = ["DNSKEY", "DS", "RRSIG", "NSEC", "NSEC3", "NSEC3PARAM", "CDS", "CDNSKEY"];
+        let zone: String = types
+            .iter()
+            .zip(lines)
+            .map(|(record_type, rdata)| format!("@ 60 {} {}\n", record_type, rdata))
+            .collect();
+        let records = parse(&zone, "example.com").unwrap();
+
+        for (record, rdata) in records.iter().zip(lines) {
+            assert_eq!(record.rdata().to_string(), rdata);
+        }
+        assert!(parse("@ 60 DNSKEY 257 3 8 not-base64\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn parses_ttl_units() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("1W"), Some(604800));
+        assert_eq!(parse_ttl("IN"), None);
+        assert_eq!(parse_ttl("1x"), None);
+    }
+}